@@ -1,11 +1,14 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use dive_computer_deco::{
-    ceiling::{ceiling, max_ceiling, binary_ceiling},
+    ceiling::{ceiling, max_ceiling, binary_ceiling, max_ceiling_pressure, GfSlope},
     ndl::{ndl, binary_ndl},
     simulate::simulate,
+    stop::time_to_ceiling,
     tissue::calculate_tissue,
+    tissue::TissueUpdater,
+    tissue_state::TissueState,
     tissue::Tissue,
-    DiveParameters,
+    DiveParameters, Gas,
     water_vapor_pressure, FN2, FHE,
 };
 
@@ -27,6 +30,77 @@ fn benchmark_tissue_calculations(c: &mut Criterion) {
         b.iter(|| calculate_tissue(saturated_tissue, 0, 1.0, 20.0, 1.0 / 60.0))
     });
 
+    // Benchmark one second of all 16 compartments, per call and with precomputed decay factors
+    group.bench_function("all_compartments_per_call", |b| {
+        let mut tissues = [tissue; 16];
+        b.iter(|| {
+            for (i, tissue) in tissues.iter_mut().enumerate() {
+                *tissue = calculate_tissue(*tissue, i, 3.0, 20.0, 1.0 / 60.0);
+            }
+        })
+    });
+
+    group.bench_function("all_compartments_precomputed", |b| {
+        let mut tissues = [tissue; 16];
+        let updater = TissueUpdater::new(1.0 / 60.0);
+        b.iter(|| updater.update_all(&mut tissues, 3.0, 20.0, Gas::air()))
+    });
+
+    group.finish();
+}
+
+fn benchmark_tissue_state(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tissue_state");
+    let gas = Gas::new(0.21, 0.35);
+    let updater = TissueUpdater::new(1.0 / 60.0);
+    let tissues: [Tissue; 16] = core::array::from_fn(|i| Tissue {
+        load_n2: 1.0 + (i as f32 * 0.1),
+        load_he: 0.5 - (i as f32 * 0.02),
+    });
+    let state = TissueState::from(&tissues);
+
+    // One second of all compartments, [Tissue; 16] against structure-of-arrays
+    group.bench_function("update_tissue_array", |b| {
+        let mut tissues = tissues;
+        b.iter(|| updater.update_all(&mut tissues, 4.0, 20.0, gas))
+    });
+
+    group.bench_function("update_tissue_state", |b| {
+        let mut state = state;
+        b.iter(|| state.update(&updater, 4.0, 20.0, gas))
+    });
+
+    // Deepest ceiling at one gradient factor
+    group.bench_function("max_ceiling_tissue_array", |b| {
+        b.iter(|| max_ceiling_pressure(0.7, &tissues))
+    });
+
+    group.bench_function("max_ceiling_tissue_state", |b| {
+        b.iter(|| state.max_ceiling(0.7))
+    });
+
+    group.bench_function("m_values_tissue_state", |b| {
+        b.iter(|| state.m_values(2.0))
+    });
+
+    group.finish();
+}
+
+fn benchmark_deco_stop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deco_stop");
+    // 30 minutes at 40 m on air, waiting at 6 m for the ceiling to clear 3 m
+    let tissues: [Tissue; 16] = core::array::from_fn(|i| {
+        let surface = Tissue { load_n2: (1.0 - water_vapor_pressure(20.0)) * FN2, load_he: 0.0 };
+        calculate_tissue(surface, i, 5.0, 20.0, 30.0)
+    });
+
+    let mut slope = GfSlope::new(0.3, 0.85, 1.0);
+    slope.anchor(&tissues);
+
+    group.bench_function("time_to_ceiling", |b| {
+        b.iter(|| time_to_ceiling(&slope, &tissues, 1.6, 20.0, Gas::air(), 3.001, 9999))
+    });
+
     group.finish();
 }
 
@@ -197,6 +271,8 @@ fn benchmark_simulation(c: &mut Criterion) {
 criterion_group!(
     benches,
     benchmark_tissue_calculations,
+    benchmark_tissue_state,
+    benchmark_deco_stop,
     benchmark_ceiling_calculations,
     benchmark_ndl_calculations,
     benchmark_method_comparisons,
@@ -19,6 +19,7 @@ use dive_deco::{
     Gas, 
     Time
 };
+use dive_computer_deco::DiveParameters;
 use std::io::{self, Write};
 
 fn get_float_input(prompt: &str, default: f32) -> f32 {
@@ -41,40 +42,6 @@ fn get_float_input(prompt: &str, default: f32) -> f32 {
     }
 }
 
-fn validate_gradient_factors(gf_high: f32, gf_low: f32) -> (f32, f32) {
-    let mut validated_gf_high = gf_high;
-    let mut validated_gf_low = gf_low;
-    
-    // Warn about extreme gradient factor values
-    if gf_high >= 1.0 || gf_low >= 1.0 {
-        println!("⚠️  WARNING: Gradient factors of 1.0 or higher can cause mathematical instability!");
-        println!("   This may result in infinite loops or unrealistic decompression calculations.");
-        println!("   Clamping gradient factors to maximum safe value of 0.99.");
-        
-        if validated_gf_high >= 1.0 {
-            validated_gf_high = 0.99;
-        }
-        if validated_gf_low >= 1.0 {
-            validated_gf_low = 0.99;
-        }
-    }
-    
-    // Ensure GF Low <= GF High
-    if validated_gf_low > validated_gf_high {
-        println!("⚠️  WARNING: GF Low should not be higher than GF High. Swapping values.");
-        let temp = validated_gf_high;
-        validated_gf_high = validated_gf_low;
-        validated_gf_low = temp;
-    }
-    
-    // Warn about very conservative values
-    if validated_gf_high < 0.3 || validated_gf_low < 0.2 {
-        println!("⚠️  WARNING: Very conservative gradient factors may result in excessive decompression times.");
-    }
-    
-    (validated_gf_high, validated_gf_low)
-}
-
 fn main() {
     println!("=== Dive-Deco Library Decompression Planner ===");
     println!("Using external dive-deco crate as reference implementation");
@@ -87,8 +54,16 @@ fn main() {
     let surface_pressure = get_float_input("Surface pressure (bar)", 1.0);
     let temperature = get_float_input("Water temperature (°C)", 37.0);
 
-    // Validate and adjust gradient factors if necessary
-    let (gf_high, gf_low) = validate_gradient_factors(gf_high_input, gf_low_input);
+    // Validate the gradient factors with the main library's rules
+    let (gf_high, gf_low) = match DiveParameters::builder().gradient_factors(gf_low_input, gf_high_input).build() {
+        Ok(params) => (params.gf_high, params.gf_low),
+        Err(errors) => {
+            for problem in errors.problems() {
+                println!("⚠️  {}", problem);
+            }
+            return;
+        }
+    };
 
     // Note: dive-deco library doesn't expose descent/ascent speeds in the same way
     println!("Dive Parameters:");
@@ -2,140 +2,143 @@ use eframe::egui;
 use egui_plot::{Line, Plot, PlotPoints};
 use dive_computer_deco::{
     DiveParameters,
+    conservatism::Conservatism,
     tissue::Tissue,
     simulate::SimulationOutputs,
+    cylinder::{self, CATALOGUE},
+    gas::{gas_usage, gas_used, minimum_gas, sac_rate_from_usage, turn_points, Cylinder, CylinderRole, GasRule, GasUsage, MinimumGasSettings, TurnPoint},
+    plan::{self, Segment, SegmentTime, WaterType},
+    plan_file::{PlanCylinder, PlanFile, PlanGas, PlanSegment, PLAN_FILE_VERSION},
     ceiling::max_ceiling_with_gf,
     m_value::calculate_m_values,
-    water_vapor_pressure, FN2, FHE,
+    water_vapor_pressure, Gas, FN2, FHE,
 };
 use std::path::Path;
 use fitparser;
 
-#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-enum TankType {
-    Alu80,
-    Steel15L,
-    Steel12L,
-    Steel10L,
-    Alu63,
-    Steel8L,
-    Custom,
-}
-
-impl TankType {
-    fn name(&self) -> &'static str {
-        match self {
-            TankType::Alu80 => "Aluminum 80 cuft",
-            TankType::Steel15L => "Steel 15L",
-            TankType::Steel12L => "Steel 12L", 
-            TankType::Steel10L => "Steel 10L",
-            TankType::Alu63 => "Aluminum 63 cuft",
-            TankType::Steel8L => "Steel 8L",
-            TankType::Custom => "Custom Tank",
-        }
-    }
-    
-    fn water_volume_liters(&self) -> f32 {
-        match self {
-            TankType::Alu80 => 11.1,   // 80 cuft = ~11.1L water volume
-            TankType::Steel15L => 15.0,
-            TankType::Steel12L => 12.0,
-            TankType::Steel10L => 10.0,
-            TankType::Alu63 => 8.7,    // 63 cuft = ~8.7L water volume
-            TankType::Steel8L => 8.0,
-            TankType::Custom => 12.0,  // Default for custom
-        }
-    }
-    
-    fn working_pressure_bar(&self) -> f32 {
-        match self {
-            TankType::Alu80 => 207.0,    // 3000 psi
-            TankType::Steel15L => 232.0, // 15L at 232 bar
-            TankType::Steel12L => 232.0, // 12L at 232 bar
-            TankType::Steel10L => 232.0, // 10L at 232 bar
-            TankType::Alu63 => 207.0,    // 3000 psi
-            TankType::Steel8L => 200.0,  // 8L at 200 bar
-            TankType::Custom => 200.0,   // Default for custom
-        }
-    }
-}
-
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 struct AirConsumption {
-    tank_type: TankType,
+    tank: Option<usize>,     // Index into the cylinder catalogue, None for a custom tank
     custom_volume: f32,      // Water volume in liters for custom tank
     custom_pressure: f32,    // Working pressure in bar for custom tank
     starting_pressure: f32,  // Starting pressure in bar
     ending_pressure: Option<f32>, // Ending pressure in bar (for SAC calculation)
     sac_rate: f32,          // Surface Air Consumption in L/min
     calculated_sac: bool,    // Whether SAC was calculated from dive data
+    gas_rule: Option<GasRule>, // Penetration gas rule, None for open water
+    buddy_tank: Option<usize>, // Buddy's catalogue tank filled to its working pressure, None if the same as ours
 }
 
 impl Default for AirConsumption {
     fn default() -> Self {
         Self {
-            tank_type: TankType::Alu80,
+            tank: CATALOGUE.iter().position(|spec| spec.name == "AL80"),
             custom_volume: 12.0,
             custom_pressure: 200.0,
             starting_pressure: 200.0,
             ending_pressure: None,
             sac_rate: 20.0,
             calculated_sac: false,
+            gas_rule: None,
+            buddy_tank: None,
         }
     }
 }
 
 impl AirConsumption {
+    fn tank_name(&self) -> &'static str {
+        self.tank.map_or("Custom Tank", |tank| CATALOGUE[tank].name)
+    }
+    
     fn get_tank_volume(&self) -> f32 {
-        if self.tank_type == TankType::Custom {
-            self.custom_volume
-        } else {
-            self.tank_type.water_volume_liters()
-        }
+        self.tank.map_or(self.custom_volume, |tank| CATALOGUE[tank].volume)
     }
     
+    fn set_cylinder(&mut self, cylinder: &PlanCylinder) {
+        self.tank = cylinder::find_by_size(cylinder.volume, cylinder.working_pressure)
+            .and_then(|spec| CATALOGUE.iter().position(|known| known == spec));
+        self.custom_volume = cylinder.volume;
+        self.custom_pressure = cylinder.working_pressure;
+        self.starting_pressure = cylinder.start_pressure;
+    }
+
     fn get_working_pressure(&self) -> f32 {
-        if self.tank_type == TankType::Custom {
-            self.custom_pressure
-        } else {
-            self.tank_type.working_pressure_bar()
-        }
+        self.tank.map_or(self.custom_pressure, |tank| CATALOGUE[tank].working_pressure)
+    }
+    
+    /// Real-gas litres of air between two tank pressures
+    fn air_between(&self, start_pressure: f32, end_pressure: f32) -> f32 {
+        let cylinder = self.cylinder(0);
+        cylinder.gas_volume(start_pressure, &Gas::air()) - cylinder.gas_volume(end_pressure, &Gas::air())
+    }
+    
+    fn cylinder(&self, gas: usize) -> Cylinder {
+        Cylinder::new(self.get_tank_volume(), self.get_working_pressure(), self.starting_pressure, gas)
+    }
+    
+    fn buddy_cylinder(&self, gas: usize) -> Option<Cylinder> {
+        self.buddy_tank.map(|tank| CATALOGUE[tank].cylinder(CATALOGUE[tank].working_pressure, gas))
     }
     
     fn air_consumed_liters(&self, depth_m: f32, time_minutes: f32) -> f32 {
         let pressure_factor = (depth_m / 10.0) + 1.0; // Pressure at depth
-        self.sac_rate * pressure_factor * time_minutes
+        gas_used(self.sac_rate, pressure_factor, time_minutes)
     }
     
     fn remaining_pressure(&self, consumed_liters: f32) -> f32 {
-        let tank_volume = self.get_tank_volume();
-        let consumed_bar = consumed_liters / tank_volume;
-        (self.starting_pressure - consumed_bar).max(0.0)
+        (self.starting_pressure - self.cylinder(0).pressure_drop(consumed_liters, &Gas::air())).max(0.0)
     }
     
-    fn calculate_sac_from_dive(&mut self, depths: &[f32], time_intervals: &[f32], ending_pressure: f32) {
+    fn calculate_sac_from_dive(&mut self, times: &[f32], pressures: &[f32], ending_pressure: f32) {
         if let Some(_) = self.ending_pressure {
-            let pressure_used = self.starting_pressure - ending_pressure;
-            let air_used_liters = pressure_used * self.get_tank_volume();
+            let air_used_liters = self.air_between(self.starting_pressure, ending_pressure);
             
-            // Calculate average depth weighted by time
-            let total_pressure_time: f32 = depths.iter()
-                .zip(time_intervals.iter())
-                .map(|(&depth, &time)| ((depth / 10.0) + 1.0) * time)
-                .sum();
-            
-            if total_pressure_time > 0.0 {
-                self.sac_rate = air_used_liters / total_pressure_time;
+            if let Some(sac_rate) = sac_rate_from_usage(air_used_liters, times, pressures) {
+                self.sac_rate = sac_rate;
                 self.calculated_sac = true;
             }
         }
     }
 }
 
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
+/// A cylinder carried besides the main tank: a stage, deco gas or bailout
+#[derive(Clone)]
+struct ExtraTank {
+    tank: usize,             // Index into the cylinder catalogue
+    starting_pressure: f32,  // Starting pressure in bar
+    o2: f32,                 // Oxygen fraction of the gas it holds
+    role: CylinderRole,
+}
+
+impl ExtraTank {
+    fn new() -> Self {
+        Self {
+            tank: CATALOGUE.iter().position(|spec| spec.name == "AL40").unwrap_or(0),
+            starting_pressure: 200.0,
+            o2: 0.5,
+            role: CylinderRole::Deco,
+        }
+    }
+
+    fn cylinder(&self, gas: usize) -> Cylinder {
+        Cylinder { role: self.role, ..CATALOGUE[self.tank].cylinder(self.starting_pressure, gas) }
+    }
+}
+
+fn role_name(role: CylinderRole) -> &'static str {
+    match role {
+        CylinderRole::BackGas => "Back Gas",
+        CylinderRole::Stage => "Stage",
+        CylinderRole::Deco => "Deco",
+        CylinderRole::Bailout => "Bailout",
+    }
+}
+
+#[derive(Clone)]
 struct DiveStep {
     depth: f32,
     duration: f32, // in minutes
+    o2: f32,       // Oxygen fraction breathed during the step
 }
 
 impl DiveStep {
@@ -143,20 +146,11 @@ impl DiveStep {
         Self {
             depth: 18.0,
             duration: 20.0,
+            o2: 0.21,
         }
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
-struct DivePlan {
-    gf_low: f32,
-    gf_high: f32,
-    surface_pressure: f32,
-    descent_speed: f32,
-    ascent_speed: f32,
-    dive_steps: Vec<DiveStep>,
-}
-
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum PlotTab {
     DiveProfile,
@@ -183,12 +177,14 @@ struct DivePlannerApp {
     // Dive parameters
     gf_low: f32,
     gf_high: f32,
+    conservatism: Conservatism, // GFs above come from the preset unless Custom
     surface_pressure: f32,
     descent_speed: f32,
     ascent_speed: f32,
     
     // Air consumption parameters
     air_consumption: AirConsumption,
+    extra_tanks: Vec<ExtraTank>, // Stages, deco and bailout cylinders
     
     // Dive profile
     dive_steps: Vec<DiveStep>,
@@ -197,6 +193,8 @@ struct DivePlannerApp {
     simulation_results: Option<SimulationOutputs>,
     simulation_text: String,
     air_remaining: Vec<f32>, // Air remaining at each time interval
+    turn_point: Option<TurnPoint>, // Where the gas rule turns the dive
+    gas_usage: Option<GasUsage>, // Gas drawn from each cylinder of the plan
     
     // FIT activity data
     fit_activity_data: Option<FitActivityData>,
@@ -222,14 +220,18 @@ impl Default for DivePlannerApp {
         Self {
             gf_low: 0.30,
             gf_high: 0.85,
+            conservatism: Conservatism::Custom { gf_low: 0.30, gf_high: 0.85 },
             surface_pressure: 1.0,
             descent_speed: 20.0,  // m/min
             ascent_speed: 10.0,   // m/min
             air_consumption: AirConsumption::default(),
+            extra_tanks: Vec::new(),
             dive_steps: vec![DiveStep::new()],
             simulation_results: None,
             simulation_text: String::new(),
             air_remaining: Vec::new(),
+            turn_point: None,
+            gas_usage: None,
             fit_activity_data: None,
             show_ceiling: true,
             show_depth: true,
@@ -310,19 +312,38 @@ impl DivePlannerApp {
             .num_columns(2)
             .spacing([40.0, 8.0])
             .show(ui, |ui| {
-                ui.label("GF Low:");
-                ui.add(egui::Slider::new(&mut self.gf_low, 0.1..=0.99)
-                    .suffix("%")
-                    .custom_formatter(|n, _| format!("{:.0}", n * 100.0))
-                    .custom_parser(|s| s.parse::<f64>().ok().map(|v| v / 100.0)));
+                ui.label("Conservatism:");
+                let old_conservatism = self.conservatism;
+                let custom = Conservatism::Custom { gf_low: self.gf_low, gf_high: self.gf_high };
+                egui::ComboBox::from_id_salt("conservatism")
+                    .selected_text(self.conservatism.name())
+                    .show_ui(ui, |ui| {
+                        for level in [Conservatism::Low, Conservatism::Medium, Conservatism::High, custom] {
+                            ui.selectable_value(&mut self.conservatism, level, level.name());
+                        }
+                    });
+                if self.conservatism != old_conservatism {
+                    (self.gf_low, self.gf_high) = self.conservatism.gradient_factors();
+                }
                 ui.end_row();
                 
-                ui.label("GF High:");
-                ui.add(egui::Slider::new(&mut self.gf_high, 0.1..=0.99)
-                    .suffix("%")
-                    .custom_formatter(|n, _| format!("{:.0}", n * 100.0))
-                    .custom_parser(|s| s.parse::<f64>().ok().map(|v| v / 100.0)));
-                ui.end_row();
+                // raw gradient factors only for divers who ask for them
+                if let Conservatism::Custom { .. } = self.conservatism {
+                    ui.label("GF Low:");
+                    ui.add(egui::Slider::new(&mut self.gf_low, 0.1..=0.99)
+                        .suffix("%")
+                        .custom_formatter(|n, _| format!("{:.0}", n * 100.0))
+                        .custom_parser(|s| s.parse::<f64>().ok().map(|v| v / 100.0)));
+                    ui.end_row();
+                    
+                    ui.label("GF High:");
+                    ui.add(egui::Slider::new(&mut self.gf_high, 0.1..=0.99)
+                        .suffix("%")
+                        .custom_formatter(|n, _| format!("{:.0}", n * 100.0))
+                        .custom_parser(|s| s.parse::<f64>().ok().map(|v| v / 100.0)));
+                    ui.end_row();
+                    self.conservatism = Conservatism::Custom { gf_low: self.gf_low, gf_high: self.gf_high };
+                }
                 
                 ui.label("Surface Pressure:");
                 ui.add(egui::DragValue::new(&mut self.surface_pressure)
@@ -359,26 +380,23 @@ impl DivePlannerApp {
             .spacing([40.0, 8.0])
             .show(ui, |ui| {
                 ui.label("Tank Type:");
-                let old_tank_type = self.air_consumption.tank_type;
+                let old_tank = self.air_consumption.tank;
                 egui::ComboBox::from_id_salt("tank_type")
-                    .selected_text(self.air_consumption.tank_type.name())
+                    .selected_text(self.air_consumption.tank_name())
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(&mut self.air_consumption.tank_type, TankType::Alu80, TankType::Alu80.name());
-                        ui.selectable_value(&mut self.air_consumption.tank_type, TankType::Steel15L, TankType::Steel15L.name());
-                        ui.selectable_value(&mut self.air_consumption.tank_type, TankType::Steel12L, TankType::Steel12L.name());
-                        ui.selectable_value(&mut self.air_consumption.tank_type, TankType::Steel10L, TankType::Steel10L.name());
-                        ui.selectable_value(&mut self.air_consumption.tank_type, TankType::Alu63, TankType::Alu63.name());
-                        ui.selectable_value(&mut self.air_consumption.tank_type, TankType::Steel8L, TankType::Steel8L.name());
-                        ui.selectable_value(&mut self.air_consumption.tank_type, TankType::Custom, TankType::Custom.name());
+                        for (i, spec) in CATALOGUE.iter().enumerate() {
+                            ui.selectable_value(&mut self.air_consumption.tank, Some(i), spec.name);
+                        }
+                        ui.selectable_value(&mut self.air_consumption.tank, None, "Custom Tank");
                     });
                 // Recalculate if tank type changed
-                if old_tank_type != self.air_consumption.tank_type {
+                if old_tank != self.air_consumption.tank {
                     self.recalculate_air_consumption();
                 }
                 ui.end_row();
                 
                 // Show custom tank parameters if Custom is selected
-                if self.air_consumption.tank_type == TankType::Custom {
+                if self.air_consumption.tank.is_none() {
                     ui.label("Volume:");
                     let old_volume = self.air_consumption.custom_volume;
                     ui.add(egui::DragValue::new(&mut self.air_consumption.custom_volume)
@@ -473,6 +491,50 @@ impl DivePlannerApp {
                     }
                 });
                 ui.end_row();
+                
+                ui.label("Gas Rule:");
+                ui.horizontal(|ui| {
+                    let old_gas_rule = self.air_consumption.gas_rule;
+                    let rule_name = |rule: Option<GasRule>| match rule {
+                        None => "None",
+                        Some(GasRule::Thirds) => "Thirds",
+                        Some(GasRule::Halves) => "Halves",
+                        Some(GasRule::Fraction(_)) => "Custom",
+                    };
+                    egui::ComboBox::from_id_salt("gas_rule")
+                        .selected_text(rule_name(self.air_consumption.gas_rule))
+                        .show_ui(ui, |ui| {
+                            for rule in [None, Some(GasRule::Thirds), Some(GasRule::Halves), Some(GasRule::Fraction(0.25))] {
+                                ui.selectable_value(&mut self.air_consumption.gas_rule, rule, rule_name(rule));
+                            }
+                        });
+                    if let Some(GasRule::Fraction(ref mut fraction)) = self.air_consumption.gas_rule {
+                        ui.add(egui::DragValue::new(fraction)
+                            .speed(0.01)
+                            .range(0.05..=1.0));
+                    }
+                    if old_gas_rule != self.air_consumption.gas_rule {
+                        self.recalculate_air_consumption();
+                    }
+                });
+                ui.end_row();
+                
+                if self.air_consumption.gas_rule.is_some() {
+                    ui.label("Buddy Tank:");
+                    let old_buddy_tank = self.air_consumption.buddy_tank;
+                    egui::ComboBox::from_id_salt("buddy_tank")
+                        .selected_text(self.air_consumption.buddy_tank.map_or("Same as mine", |tank| CATALOGUE[tank].name))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.air_consumption.buddy_tank, None, "Same as mine");
+                            for (i, spec) in CATALOGUE.iter().enumerate() {
+                                ui.selectable_value(&mut self.air_consumption.buddy_tank, Some(i), spec.name);
+                            }
+                        });
+                    if old_buddy_tank != self.air_consumption.buddy_tank {
+                        self.recalculate_air_consumption();
+                    }
+                    ui.end_row();
+                }
             });
         
         ui.add_space(8.0);
@@ -484,16 +546,67 @@ impl DivePlannerApp {
             ui.colored_label(egui::Color32::from_rgb(100, 150, 255), "🗂️ Tank Info:");
             let volume = self.air_consumption.get_tank_volume();
             let pressure = self.air_consumption.get_working_pressure();
-            let total_air = volume * pressure;
+            let total_air = self.air_consumption.cylinder(0).gas_volume(pressure, &Gas::air());
             ui.colored_label(egui::Color32::LIGHT_GRAY, 
                 format!("{:.1}L @ {:.0}bar = {:.0}L air", volume, pressure, total_air));
         });
         
+        ui.add_space(8.0);
+        ui.separator();
+        ui.add_space(4.0);
+        
+        // Stages, deco and bailout cylinders
+        ui.horizontal(|ui| {
+            ui.strong("🧯 Extra Tanks");
+            if ui.button("➕ Add Tank").clicked() {
+                self.extra_tanks.push(ExtraTank::new());
+            }
+        });
+        let mut to_remove = None;
+        egui::Grid::new("extra_tanks_grid")
+            .num_columns(5)
+            .spacing([10.0, 4.0])
+            .striped(true)
+            .show(ui, |ui| {
+                for (i, extra) in self.extra_tanks.iter_mut().enumerate() {
+                    egui::ComboBox::from_id_salt(("extra_tank", i))
+                        .selected_text(CATALOGUE[extra.tank].name)
+                        .show_ui(ui, |ui| {
+                            for (j, spec) in CATALOGUE.iter().enumerate() {
+                                ui.selectable_value(&mut extra.tank, j, spec.name);
+                            }
+                        });
+                    ui.add(egui::DragValue::new(&mut extra.starting_pressure)
+                        .speed(1.0)
+                        .range(10.0..=CATALOGUE[extra.tank].working_pressure)
+                        .suffix(" bar"));
+                    ui.add(egui::DragValue::new(&mut extra.o2)
+                        .speed(0.01)
+                        .range(0.1..=1.0)
+                        .custom_formatter(|n, _| format!("{:.0}% O2", n * 100.0))
+                        .custom_parser(|s| s.trim_end_matches("% O2").parse::<f64>().ok().map(|v| v / 100.0)));
+                    egui::ComboBox::from_id_salt(("extra_role", i))
+                        .selected_text(role_name(extra.role))
+                        .show_ui(ui, |ui| {
+                            for role in [CylinderRole::Stage, CylinderRole::Deco, CylinderRole::Bailout, CylinderRole::BackGas] {
+                                ui.selectable_value(&mut extra.role, role, role_name(role));
+                            }
+                        });
+                    if ui.small_button("🗑").clicked() {
+                        to_remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(index) = to_remove {
+            self.extra_tanks.remove(index);
+        }
+        
         ui.horizontal(|ui| {
             ui.colored_label(egui::Color32::from_rgb(100, 150, 255), "📊 Current GF:");
             let color = egui::Color32::LIGHT_GRAY;
             ui.colored_label(color, 
-                format!("{:.0}%/{:.0}%", self.gf_low * 100.0, self.gf_high * 100.0));
+                format!("{:.0}%/{:.0}% ({})", self.gf_low * 100.0, self.gf_high * 100.0, self.conservatism.name()));
         });
     }
     
@@ -524,7 +637,7 @@ impl DivePlannerApp {
         
         // Table with grid layout
         egui::Grid::new("dive_profile_grid")
-            .num_columns(4)
+            .num_columns(5)
             .spacing([20.0, 4.0])
             .striped(true)
             .show(ui, |ui| {
@@ -532,6 +645,7 @@ impl DivePlannerApp {
                 ui.strong("Step");
                 ui.strong("Depth (m)");
                 ui.strong("Duration (min)");
+                ui.strong("Gas");
                 ui.strong("Actions");
                 ui.end_row();
                 
@@ -551,6 +665,12 @@ impl DivePlannerApp {
                         .range(0.1..=300.0)
                         .suffix(" min"));
                     
+                    ui.add(egui::DragValue::new(&mut step.o2)
+                        .speed(0.01)
+                        .range(0.1..=1.0)
+                        .custom_formatter(|n, _| format!("{:.0}% O2", n * 100.0))
+                        .custom_parser(|s| s.trim_end_matches("% O2").parse::<f64>().ok().map(|v| v / 100.0)));
+                    
                     if dive_steps_len > 1 && ui.small_button("🗑").clicked() {
                         to_remove = Some(i);
                     }
@@ -797,9 +917,9 @@ impl DivePlannerApp {
                 if self.show_depth && !results.depths.is_empty() {
                     let depth_points: PlotPoints = results.depths
                         .iter()
-                        .enumerate()
-                        .map(|(i, &depth)| {
-                            let time_minutes = i as f64 * 10.0 / 60.0; // Assuming 10-second intervals
+                        .zip(results.times.iter())
+                        .map(|(&depth, &time)| {
+                            let time_minutes = time as f64 / 60.0;
                             [time_minutes, -depth as f64] // Negative depth for proper visualization
                         })
                         .collect();
@@ -811,13 +931,13 @@ impl DivePlannerApp {
                     );
                 }
                 
-                if self.show_ceiling && !results.tissues_per_interval.is_empty() {
-                    let ceiling_points: PlotPoints = results.tissues_per_interval
+                if self.show_ceiling && !results.exact_ceilings.is_empty() {
+                    // Ceiling as recorded by the simulator, not recomputed from the tissues
+                    let ceiling_points: PlotPoints = results.exact_ceilings
                         .iter()
-                        .enumerate()
-                        .map(|(i, tissues)| {
-                            let time_minutes = i as f64 * 10.0 / 60.0;
-                            let (ceiling, _) = max_ceiling_with_gf(self.gf_low, self.gf_high, tissues);
+                        .zip(results.times.iter())
+                        .map(|(&ceiling, &time)| {
+                            let time_minutes = time as f64 / 60.0;
                             [time_minutes, -(ceiling as f64)] // Negative for proper visualization
                         })
                         .collect();
@@ -833,9 +953,9 @@ impl DivePlannerApp {
                 if self.show_pressure && !results.pressures.is_empty() {
                     let pressure_points: PlotPoints = results.pressures
                         .iter()
-                        .enumerate()
-                        .map(|(i, &pressure)| {
-                            let time_minutes = i as f64 * 10.0 / 60.0;
+                        .zip(results.times.iter())
+                        .map(|(&pressure, &time)| {
+                            let time_minutes = time as f64 / 60.0;
                             [time_minutes, (pressure as f64 - 1.0) * 10.0] // Convert to depth equivalent
                         })
                         .collect();
@@ -881,13 +1001,13 @@ impl DivePlannerApp {
                 };
                 
                 // Create time points based on data source
-                let air_points: PlotPoints = if let Some(ref _results) = self.simulation_results {
-                    // For simulation data, use 10-second intervals
+                let air_points: PlotPoints = if let Some(ref results) = self.simulation_results {
+                    // For simulation data, use the recorded sample times
                     self.air_remaining
                         .iter()
-                        .enumerate()
-                        .map(|(i, &pressure)| {
-                            let time_minutes = i as f64 * 10.0 / 60.0;
+                        .zip(results.times.iter())
+                        .map(|(&pressure, &time)| {
+                            let time_minutes = time as f64 / 60.0;
                             // Scale pressure to depth range and offset to be visible above surface
                             let scaled_pressure = (pressure - min_air_pressure) * scale_factor;
                             [time_minutes, scaled_pressure as f64] // Scaled pressure to match depth range
@@ -913,6 +1033,15 @@ impl DivePlannerApp {
                         .color(egui::Color32::LIGHT_BLUE)
                         .width(2.0)
                 );
+                
+                // Turn point of the gas rule on the simulated profile
+                if let (Some(TurnPoint { turn_pressure, time: Some(time), .. }), Some(_)) = (self.turn_point, &self.simulation_results) {
+                    plot_ui.vline(
+                        egui_plot::VLine::new(format!("Turn ({:.0} bar)", turn_pressure), time as f64 / 60.0)
+                            .color(egui::Color32::YELLOW)
+                            .style(egui_plot::LineStyle::Dashed { length: 8.0 })
+                    );
+                }
             }
             
             // Always show FIT activity data if available and enabled
@@ -1273,8 +1402,9 @@ impl DivePlannerApp {
         use egui::*;
         
         // Calculate time points (x-axis)
-        let time_points: Vec<f64> = (0..results.tissues_per_interval.len())
-            .map(|i| i as f64 * 10.0 / 60.0) // Convert 10-second intervals to minutes
+        let time_points: Vec<f64> = results.times
+            .iter()
+            .map(|&time| time as f64 / 60.0)
             .collect();
         
         if time_points.is_empty() {
@@ -1575,20 +1705,22 @@ impl DivePlannerApp {
             return;
         }
         
-        let mut total_consumed_liters = 0.0;
-        
-        for &depth in results.depths.iter() {
-            // Calculate time interval (10 seconds = 1/6 minute)
-            let time_interval_minutes = 10.0 / 60.0;
-            
-            // Calculate air consumed during this interval
-            let air_consumed_this_interval = self.air_consumption.air_consumed_liters(depth, time_interval_minutes);
-            total_consumed_liters += air_consumed_this_interval;
-            
-            // Calculate remaining pressure
-            let remaining_pressure = self.air_consumption.remaining_pressure(total_consumed_liters);
-            self.air_remaining.push(remaining_pressure);
+        let mut dive_params = DiveParameters::new(self.gf_high, self.gf_low);
+        dive_params.sac_rate = self.air_consumption.sac_rate;
+        dive_params.deco_sac_rate = self.air_consumption.sac_rate;
+        
+        // The main tank is the plan's first cylinder, the one plotted
+        let plan = self.dive_plan();
+        let usage = gas_usage(&plan, &dive_params, results);
+        if let Some(remaining) = usage.remaining.first() {
+            self.air_remaining = remaining.iter().map(|pressure| pressure.max(0.0)).collect();
         }
+        
+        self.turn_point = self.air_consumption.gas_rule.and_then(|rule| {
+            let buddies: Vec<Cylinder> = self.air_consumption.buddy_cylinder(plan.cylinders[0].gas).into_iter().collect();
+            turn_points(&plan, &usage, results, rule, &buddies).first().copied()
+        });
+        self.gas_usage = Some(usage);
     }
     
     fn calculate_air_consumption_for_fit(&mut self) {
@@ -1629,24 +1761,14 @@ impl DivePlannerApp {
                 return;
             }
             
-            let pressure_used = self.air_consumption.starting_pressure - ending_pressure;
-            let air_used_liters = pressure_used * self.air_consumption.get_tank_volume();
+            let air_used_liters = self.air_consumption.air_between(self.air_consumption.starting_pressure, ending_pressure);
             
-            // Calculate total pressure-time (depth-adjusted time)
-            let mut total_pressure_time = 0.0_f32;
-            let mut prev_time = 0.0_f64;
+            // Timestamps are in minutes, the library works in seconds
+            let times: Vec<f32> = fit_data.timestamps.iter().map(|&time| time as f32 * 60.0).collect();
+            let pressures: Vec<f32> = fit_data.depths.iter().map(|&depth| (depth / 10.0) + 1.0).collect();
             
-            for (i, (&depth, &time)) in fit_data.depths.iter().zip(fit_data.timestamps.iter()).enumerate() {
-                if i > 0 {
-                    let time_interval = (time - prev_time) as f32;
-                    let pressure_factor = (depth / 10.0) + 1.0;
-                    total_pressure_time += pressure_factor * time_interval;
-                }
-                prev_time = time;
-            }
-            
-            if total_pressure_time > 0.0 {
-                self.air_consumption.sac_rate = air_used_liters / total_pressure_time;
+            if let Some(sac_rate) = sac_rate_from_usage(air_used_liters, &times, &pressures) {
+                self.air_consumption.sac_rate = sac_rate;
                 self.air_consumption.calculated_sac = true;
                 
                 // Recalculate air consumption with new SAC rate
@@ -1672,13 +1794,13 @@ impl DivePlannerApp {
                     let total_dive_time = *fit_data.timestamps.last().unwrap_or(&0.0);
                     let final_air_pressure = self.air_remaining.last().unwrap_or(&0.0);
                     let total_air_consumed = self.air_consumption.starting_pressure - final_air_pressure;
-                    let total_air_consumed_liters = total_air_consumed * self.air_consumption.get_tank_volume();
+                    let total_air_consumed_liters = self.air_consumption.air_between(self.air_consumption.starting_pressure, *final_air_pressure);
                     let average_consumption = if total_dive_time > 0.0 { total_air_consumed_liters / (total_dive_time as f32) } else { 0.0 };
                     
                     let mut result_text = format!("Loaded FIT activity with {} data points\n", fit_data.depths.len());
                     result_text.push_str(&format!("\n=== AIR CONSUMPTION (FIT Activity) ===\n"));
                     result_text.push_str(&format!("Tank: {} ({:.1}L @ {:.0} bar)\n", 
-                        self.air_consumption.tank_type.name(),
+                        self.air_consumption.tank_name(),
                         self.air_consumption.get_tank_volume(),
                         self.air_consumption.get_working_pressure()));
                     result_text.push_str(&format!("Starting Pressure: {:.0} bar\n", self.air_consumption.starting_pressure));
@@ -1739,13 +1861,14 @@ impl DivePlannerApp {
         
         // Create dive parameters
         let mut dive_params = DiveParameters::new(self.gf_high, self.gf_low);
+        dive_params.conservatism = self.conservatism;
         dive_params.descent_speed = self.descent_speed / 60.0; // Convert m/min to m/s
         dive_params.ascent_speed = self.ascent_speed / 60.0;   // Convert m/min to m/s
         
         let mut dive_text = String::new();
         dive_text.push_str(&format!("=== DIVE PLAN ===\n"));
-        dive_text.push_str(&format!("GF Low/High: {:.0}%/{:.0}%\n", 
-            self.gf_low * 100.0, self.gf_high * 100.0));
+        dive_text.push_str(&format!("GF Low/High: {:.0}%/{:.0}% ({} conservatism)\n", 
+            self.gf_low * 100.0, self.gf_high * 100.0, self.conservatism.name()));
         dive_text.push_str(&format!("Surface Pressure: {:.2} bar\n", self.surface_pressure));
         dive_text.push_str(&format!("Descent Speed: {:.1} m/min\n", self.descent_speed));
         dive_text.push_str(&format!("Ascent Speed: {:.1} m/min\n\n", self.ascent_speed));
@@ -1753,7 +1876,7 @@ impl DivePlannerApp {
         // Add air consumption info
         dive_text.push_str(&format!("=== AIR CONSUMPTION ===\n"));
         dive_text.push_str(&format!("Tank: {} ({:.1}L @ {:.0} bar)\n", 
-            self.air_consumption.tank_type.name(),
+            self.air_consumption.tank_name(),
             self.air_consumption.get_tank_volume(),
             self.air_consumption.get_working_pressure()));
         dive_text.push_str(&format!("Starting Pressure: {:.0} bar\n", self.air_consumption.starting_pressure));
@@ -1775,8 +1898,7 @@ impl DivePlannerApp {
         
         // Calculate SAC rate if ending pressure is provided
         if let Some(ending_pressure) = self.air_consumption.ending_pressure {
-            let time_intervals: Vec<f32> = vec![10.0 / 60.0; all_results.depths.len()]; // 10-second intervals in minutes
-            self.air_consumption.calculate_sac_from_dive(&all_results.depths, &time_intervals, ending_pressure);
+            self.air_consumption.calculate_sac_from_dive(&all_results.times, &all_results.pressures, ending_pressure);
             
             // Recalculate air consumption with new SAC rate
             self.calculate_air_consumption(&all_results);
@@ -1795,8 +1917,8 @@ impl DivePlannerApp {
         let responsible_tissues = self.get_responsible_tissues(&tissues);
         
         // Calculate total dive time from simulation results
-        let total_dive_time = if !all_results.depths.is_empty() {
-            (all_results.depths.len() as f32 * 10.0) / 60.0 // Convert from 10-second intervals to minutes
+        let total_dive_time = if let Some(&last_time) = all_results.times.last() {
+            last_time / 60.0
         } else {
             total_runtime // Fallback to bottom time only
         };
@@ -1818,7 +1940,7 @@ impl DivePlannerApp {
         if !self.air_remaining.is_empty() {
             let final_air_pressure = self.air_remaining.last().unwrap_or(&0.0);
             let total_air_consumed = self.air_consumption.starting_pressure - final_air_pressure;
-            let total_air_consumed_liters = total_air_consumed * self.air_consumption.get_tank_volume();
+            let total_air_consumed_liters = self.air_consumption.air_between(self.air_consumption.starting_pressure, *final_air_pressure);
             let average_consumption = if total_dive_time > 0.0 { total_air_consumed_liters / total_dive_time } else { 0.0 };
             
             dive_text.push_str(&format!("\n=== AIR CONSUMPTION RESULTS ===\n"));
@@ -1829,6 +1951,37 @@ impl DivePlannerApp {
             if self.air_consumption.calculated_sac {
                 dive_text.push_str(&format!("Calculated SAC Rate: {:.1} L/min\n", self.air_consumption.sac_rate));
             }
+            
+            if let Some(turn) = self.turn_point {
+                match turn.time {
+                    Some(time) => dive_text.push_str(&format!("Turn Pressure: {:.0} bar, reached at {:.1} min\n", turn.turn_pressure, time / 60.0)),
+                    None => dive_text.push_str(&format!("Turn Pressure: {:.0} bar, not reached\n", turn.turn_pressure)),
+                }
+            }
+            
+            // Shared out-of-gas ascent from the worst point of the dive
+            if let Some(reserve) = minimum_gas(&self.dive_plan(), &dive_params, &all_results, &MinimumGasSettings::default(), temperature, 10.0).first() {
+                dive_text.push_str(&format!("Minimum Gas: {:.0} bar ({:.0} L) from {:.0}m at {:.1} min, {:.0} bar left there: {}\n",
+                    reserve.bar, reserve.litres, reserve.depth, reserve.time / 60.0, reserve.available,
+                    if reserve.pass { "OK" } else { "NOT ENOUGH GAS" }));
+            }
+        }
+        
+        // Every cylinder carried, with the reserve each should keep
+        if let Some(usage) = &self.gas_usage {
+            let plan = self.dive_plan();
+            dive_text.push_str("\n=== CYLINDERS ===\n");
+            for (i, (cylinder, used)) in plan.cylinders.iter().zip(usage.cylinders.iter()).enumerate() {
+                dive_text.push_str(&format!("{}. {} {:.1}L {:.0}% O2: {:.0} -> {:.0} bar ({:.0} L)\n",
+                    i + 1, role_name(cylinder.role), cylinder.volume, plan.gases[cylinder.gas].o2() * 100.0,
+                    cylinder.start_pressure, used.end_pressure.max(0.0), used.litres));
+                if let Some(time) = used.below_reserve {
+                    dive_text.push_str(&format!("   ⚠️ below the {:.0} bar reserve at {:.1} min\n", cylinder.reserve, time / 60.0));
+                }
+            }
+            if usage.unassigned > 0.0 {
+                dive_text.push_str(&format!("⚠️ {:.0} L breathed from gases no cylinder carries\n", usage.unassigned));
+            }
         }
         
         if final_ceiling > 0 || !deco_stops.is_empty() {
@@ -1871,151 +2024,41 @@ impl DivePlannerApp {
         self.simulation_text = dive_text;
     }
     
-    fn simulate_dive_steps(&self, dive_params: &mut DiveParameters, tissues: &mut [Tissue; 16], temperature: f32) -> SimulationOutputs {
-        use dive_computer_deco::simulate::simulate_with_ascent_from_depth;
-        
-        let mut combined_results = SimulationOutputs::new();
-        let mut current_depth = 0.0;
-        
-        for (step_num, step) in self.dive_steps.iter().enumerate() {
-            let is_last_step = step_num == self.dive_steps.len() - 1;
-            
-            // For each step, simulate from current depth to target depth
-            let step_results = simulate_with_ascent_from_depth(
-                dive_params,
-                tissues,
-                self.surface_pressure,
-                current_depth,
-                step.depth,
-                temperature,
-                10.0, // 10-second intervals
-                step.duration * 60.0, // Convert minutes to seconds
-                is_last_step, // Only include ascent on the last step
-            );
-            
-            // Append results to combined results
-            combined_results.depths.extend(step_results.depths);
-            combined_results.pressures.extend(step_results.pressures);
-            combined_results.tissues_per_interval.extend(step_results.tissues_per_interval);
-            
-            // Update current depth for next step
-            current_depth = step.depth;
+    fn dive_plan(&self) -> plan::DivePlan {
+        // The whole profile is one continuous plan, each step starting where the previous one ended
+        let mut plan = plan::DivePlan::new(self.surface_pressure, WaterType::Salt);
+        let gas_index = |plan: &mut plan::DivePlan, o2: f32| {
+            let gas = Gas::new(o2, 0.0);
+            match plan.gases.iter().position(|known| *known == gas) {
+                Some(index) => index,
+                None => plan.add_gas(gas),
+            }
+        };
+        for step in &self.dive_steps {
+            let gas = gas_index(&mut plan, step.o2);
+            plan.add_segment(Segment::new(step.depth, SegmentTime::Duration(step.duration * 60.0), gas));
         }
-        
-        combined_results
+        // The main tank holds the gas of the first step
+        let bottom_gas = plan.segments.first().map_or(0, |segment| segment.gas);
+        plan.add_cylinder(self.air_consumption.cylinder(bottom_gas));
+        for extra in &self.extra_tanks {
+            let gas = gas_index(&mut plan, extra.o2);
+            plan.add_cylinder(extra.cylinder(gas));
+        }
+        plan
+    }
+
+    fn simulate_dive_steps(&self, dive_params: &mut DiveParameters, tissues: &mut [Tissue; 16], temperature: f32) -> SimulationOutputs {
+        // 10-second intervals
+        self.dive_plan().simulate(dive_params, tissues, temperature, 10.0).unwrap_or_default()
     }
 
     fn calculate_deco_stops_from_results(&self, results: &SimulationOutputs) -> Vec<(f32, f32)> {
-        let mut deco_stops: Vec<(f32, f32)> = Vec::new();
-        
-        if results.depths.is_empty() {
-            return deco_stops;
-        }
-        
-        let mut current_stop_depth: Option<f32> = None;
-        let mut stop_start_time: f32 = 0.0;
-        let mut in_ascent_phase: bool = false;
-        let mut max_depth_reached: f32 = 0.0;
-        let mut ascending_from_max = false;
-        
-        // First pass: find maximum depth to determine when ascent starts
-        for &depth in &results.depths {
-            if depth > max_depth_reached {
-                max_depth_reached = depth;
-            }
-        }
-        
-        // Calculate total bottom time to help identify when ascent truly begins
-        let total_bottom_time_seconds: f32 = self.dive_steps.iter().map(|step| step.duration * 60.0).sum();
-        let estimated_bottom_intervals = (total_bottom_time_seconds / 10.0) as usize;
-        
-        for (i, &depth) in results.depths.iter().enumerate() {
-            let time_minutes = i as f32 * 10.0 / 60.0; // Convert from 10-second intervals
-            
-            // More sophisticated ascent detection:
-            // 1. We must be past the estimated bottom time
-            // 2. We must be ascending from the maximum depth
-            // 3. We must be at a depth that could be a decompression stop (multiple of 3m, >= 3m)
-            if !in_ascent_phase {
-                if i > estimated_bottom_intervals && 
-                   depth < max_depth_reached - 1.0_f32 && 
-                   !ascending_from_max {
-                    ascending_from_max = true;
-                }
-                
-                if ascending_from_max && depth < max_depth_reached - 2.0_f32 {
-                    in_ascent_phase = true;
-                }
-            }
-            
-            if in_ascent_phase && depth > 0.0 {
-                // Only consider depths that are likely decompression stops:
-                // - Multiple of 3 meters (standard deco stop depths)
-                // - Between 3m and 50m
-                // - Not the original dive step depths
-                let is_deco_stop_depth = depth >= 3.0_f32 && 
-                                       depth <= 50.0_f32 && 
-                                       (depth % 3.0_f32).abs() < 0.5_f32 &&
-                                       !self.dive_steps.iter().any(|step| (step.depth - depth).abs() < 1.0_f32);
-                
-                if !is_deco_stop_depth {
-                    // Reset current stop if we're not at a valid deco depth
-                    if let Some(stop_depth) = current_stop_depth {
-                        let stop_duration = time_minutes - stop_start_time;
-                        if stop_duration >= 1.0_f32 { // Minimum 1 minute for a deco stop
-                            deco_stops.push((stop_depth, stop_duration));
-                        }
-                        current_stop_depth = None;
-                    }
-                    continue;
-                }
-                
-                // Check if we're at a constant depth (potential deco stop)
-                if let Some(stop_depth) = current_stop_depth {
-                    if (depth - stop_depth).abs() < 0.5_f32 {
-                        // Still at the same stop depth
-                        continue;
-                    } else {
-                        // We've moved from the stop depth
-                        let stop_duration = time_minutes - stop_start_time;
-                        if stop_duration >= 1.0_f32 { // Minimum 1 minute for a deco stop
-                            deco_stops.push((stop_depth, stop_duration));
-                        }
-                        current_stop_depth = None;
-                    }
-                }
-                
-                // Check if we're starting a new deco stop
-                // Look ahead to see if we stay at this depth
-                if current_stop_depth.is_none() {
-                    let mut same_depth_count = 0;
-                    let check_ahead = 12; // Check next 12 intervals (2 minutes)
-                    
-                    for j in (i + 1)..(i + 1 + check_ahead).min(results.depths.len()) {
-                        if (results.depths[j] - depth).abs() < 0.5_f32 {
-                            same_depth_count += 1;
-                        } else {
-                            break;
-                        }
-                    }
-                    
-                    // If we stay at the same depth for at least 1 minute, it's likely a deco stop
-                    if same_depth_count >= 6 {
-                        current_stop_depth = Some(depth);
-                        stop_start_time = time_minutes;
-                    }
-                }
-            }
-        }
-        
-        // Handle any ongoing stop at the end
-        if let Some(stop_depth) = current_stop_depth {
-            let final_time = results.depths.len() as f32 * 10.0 / 60.0;
-            let stop_duration = final_time - stop_start_time;
-            if stop_duration >= 1.0_f32 {
-                deco_stops.push((stop_depth, stop_duration));
-            }
-        }
+        let mut deco_stops: Vec<(f32, f32)> = results.stops()
+            .into_iter()
+            .filter(|(_, _, duration)| *duration >= 60.0) // Minimum 1 minute for a deco stop
+            .map(|(depth, _, duration)| (depth, duration / 60.0))
+            .collect();
         
         // Sort by depth (deepest first)
         deco_stops.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
@@ -2026,14 +2069,45 @@ impl DivePlannerApp {
     fn load_dive_plan(&mut self, path: &Path) {
         match std::fs::read_to_string(path) {
             Ok(contents) => {
-                match serde_json::from_str::<DivePlan>(&contents) {
+                match PlanFile::from_json(&contents) {
                     Ok(plan) => {
-                        self.gf_low = plan.gf_low;
-                        self.gf_high = plan.gf_high;
-                        self.surface_pressure = plan.surface_pressure;
-                        self.descent_speed = plan.descent_speed;
-                        self.ascent_speed = plan.ascent_speed;
-                        self.dive_steps = plan.dive_steps;
+                        let params = plan.dive_parameters();
+                        self.gf_low = params.gf_low;
+                        self.gf_high = params.gf_high;
+                        self.conservatism = params.conservatism;
+                        self.surface_pressure = plan.surface_pressure();
+                        self.descent_speed = plan.descent_rate;
+                        self.ascent_speed = plan.ascent_rate;
+                        self.air_consumption.sac_rate = plan.sac_rate;
+                        if let Some(cylinder) = plan.cylinders.first() {
+                            self.air_consumption.set_cylinder(cylinder);
+                        }
+                        // The GUI only carries catalogue tanks besides the main one
+                        let o2 = |gas: usize| plan.gases.get(gas).map_or(0.21, |gas| gas.o2);
+                        self.extra_tanks = plan.cylinders.iter().skip(1)
+                            .map(|cylinder| ExtraTank {
+                                tank: cylinder::find_by_size(cylinder.volume, cylinder.working_pressure)
+                                    .and_then(|spec| CATALOGUE.iter().position(|known| known == spec))
+                                    .unwrap_or_default(),
+                                starting_pressure: cylinder.start_pressure,
+                                o2: o2(cylinder.gas),
+                                role: cylinder.role,
+                            })
+                            .collect();
+
+                        // The GUI only knows times at depth, runtimes become durations
+                        let mut runtime = 0.0;
+                        let mut depth = 0.0;
+                        self.dive_steps = plan.segments.iter()
+                            .map(|segment| {
+                                let rate = segment.rate.unwrap_or(if segment.depth > depth { plan.descent_rate } else { plan.ascent_rate });
+                                runtime += (segment.depth - depth).abs() / rate;
+                                let duration = segment.duration.unwrap_or_else(|| (segment.runtime.unwrap_or(runtime) - runtime).max(0.0));
+                                runtime += duration;
+                                depth = segment.depth;
+                                DiveStep { depth: segment.depth, duration, o2: o2(segment.gas) }
+                            })
+                            .collect();
                         // Clear simulation results when loading a new plan
                         self.simulation_results = None;
                         self.simulation_text = String::new();
@@ -2050,16 +2124,37 @@ impl DivePlannerApp {
     }
     
     fn save_dive_plan(&self, path: &Path) {
-        let plan = DivePlan {
+        let dive_plan = self.dive_plan();
+        let plan = PlanFile {
+            version: PLAN_FILE_VERSION,
             gf_low: self.gf_low,
             gf_high: self.gf_high,
-            surface_pressure: self.surface_pressure,
-            descent_speed: self.descent_speed,
-            ascent_speed: self.ascent_speed,
-            dive_steps: self.dive_steps.clone(),
+            conservatism: Some(self.conservatism).filter(|conservatism| !matches!(conservatism, Conservatism::Custom { .. })),
+            descent_rate: self.descent_speed,
+            ascent_rate: self.ascent_speed,
+            sac_rate: self.air_consumption.sac_rate,
+            deco_sac_rate: None,
+            altitude: 0.0,
+            surface_pressure: Some(self.surface_pressure),
+            water: WaterType::Salt,
+            gases: dive_plan.gases.iter().map(|gas| PlanGas { o2: gas.o2(), he: gas.he }).collect(),
+            cylinders: dive_plan.cylinders.iter()
+                .map(|cylinder| PlanCylinder {
+                    volume: cylinder.volume,
+                    working_pressure: cylinder.working_pressure,
+                    start_pressure: cylinder.start_pressure,
+                    gas: cylinder.gas,
+                    role: cylinder.role,
+                    reserve: None,
+                })
+                .collect(),
+            segments: dive_plan.segments.iter()
+                .zip(self.dive_steps.iter())
+                .map(|(segment, step)| PlanSegment { depth: step.depth, duration: Some(step.duration), runtime: None, gas: segment.gas, rate: None })
+                .collect(),
         };
         
-        match serde_json::to_string_pretty(&plan) {
+        match plan.to_json() {
             Ok(json) => {
                 if let Err(e) = std::fs::write(path, json) {
                     eprintln!("Error saving dive plan: {}", e);
@@ -2153,12 +2248,12 @@ impl DivePlannerApp {
             if !self.air_remaining.is_empty() {
                 let final_air_pressure = self.air_remaining.last().unwrap_or(&0.0);
                 let total_air_consumed = self.air_consumption.starting_pressure - final_air_pressure;
-                let total_air_consumed_liters = total_air_consumed * self.air_consumption.get_tank_volume();
+                let total_air_consumed_liters = self.air_consumption.air_between(self.air_consumption.starting_pressure, *final_air_pressure);
                 let average_consumption = if total_dive_time > 0.0 { total_air_consumed_liters / (total_dive_time as f32) } else { 0.0 };
                 
                 result_text.push_str(&format!("\n=== AIR CONSUMPTION (FIT Activity) ===\n"));
                 result_text.push_str(&format!("Tank: {} ({:.1}L @ {:.0} bar)\n", 
-                    self.air_consumption.tank_type.name(),
+                    self.air_consumption.tank_name(),
                     self.air_consumption.get_tank_volume(),
                     self.air_consumption.get_working_pressure()));
                 result_text.push_str(&format!("Starting Pressure: {:.0} bar\n", self.air_consumption.starting_pressure));
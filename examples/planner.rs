@@ -10,11 +10,14 @@
 
 use dive_computer_deco::{
     DiveParameters, 
+    conservatism::Conservatism,
     tissue::Tissue, 
     default_tissue_load,
     ceiling::max_ceiling,
     simulate::simulate_with_ascent,
 };
+#[cfg(feature = "alloc")]
+use dive_computer_deco::simulate::{DivePhase, SimulationEventKind, SimulationOutputs};
 use std::io::{self, Write};
 
 fn get_float_input(prompt: &str, default: f32) -> f32 {
@@ -37,38 +40,30 @@ fn get_float_input(prompt: &str, default: f32) -> f32 {
     }
 }
 
-fn validate_gradient_factors(gf_high: f32, gf_low: f32) -> (f32, f32) {
-    let mut validated_gf_high = gf_high;
-    let mut validated_gf_low = gf_low;
-    
-    // Warn about extreme gradient factor values
-    if gf_high >= 1.0 || gf_low >= 1.0 {
-        println!("⚠️  WARNING: Gradient factors of 1.0 or higher can cause mathematical instability!");
-        println!("   This may result in infinite loops or unrealistic decompression calculations.");
-        println!("   Clamping gradient factors to maximum safe value of 0.99.");
-        
-        if validated_gf_high >= 1.0 {
-            validated_gf_high = 0.99;
+fn get_conservatism_input(prompt: &str, default: Conservatism) -> Conservatism {
+    loop {
+        print!("{} (default: {}): ", prompt, default.name());
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+
+        let input = input.trim();
+        if input.is_empty() {
+            return default;
         }
-        if validated_gf_low >= 1.0 {
-            validated_gf_low = 0.99;
+        if input.eq_ignore_ascii_case("custom") {
+            // only divers who pick custom are asked for raw gradient factors
+            let gf_high = get_float_input("GF High (0.0-1.0)", 0.8);
+            let gf_low = get_float_input("GF Low (0.0-1.0)", 0.8);
+            return Conservatism::Custom { gf_low, gf_high };
+        }
+
+        match Conservatism::from_name(input) {
+            Some(conservatism) => return conservatism,
+            None => println!("Invalid input. Please enter low, medium, high or custom."),
         }
     }
-    
-    // Ensure GF Low <= GF High
-    if validated_gf_low > validated_gf_high {
-        println!("⚠️  WARNING: GF Low should not be higher than GF High. Swapping values.");
-        let temp = validated_gf_high;
-        validated_gf_high = validated_gf_low;
-        validated_gf_low = temp;
-    }
-    
-    // Warn about very conservative values
-    if validated_gf_high < 0.3 || validated_gf_low < 0.2 {
-        println!("⚠️  WARNING: Very conservative gradient factors may result in excessive decompression times.");
-    }
-    
-    (validated_gf_high, validated_gf_low)
 }
 
 fn main() {
@@ -76,23 +71,28 @@ fn main() {
 
     // Get dive parameters from user input
     println!("Enter dive parameters:");
-    let gf_high_input = get_float_input("GF High (0.0-1.0)", 0.8);
-    let gf_low_input = get_float_input("GF Low (0.0-1.0)", 0.8);
+    // Ask again until the gradient factors pass validation
+    let mut dive_params = loop {
+        let conservatism = get_conservatism_input("Conservatism (low/medium/high/custom)", Conservatism::Medium);
+        match DiveParameters::builder().conservatism(conservatism).build() {
+            Ok(params) => break params,
+            Err(errors) => {
+                for problem in errors.problems() {
+                    println!("⚠️  {}", problem);
+                }
+            }
+        }
+    };
     let surface_pressure = get_float_input("Surface pressure (bar)", 1.0);
     let temperature = get_float_input("Water temperature (°C)", 37.0);
 
-    // Validate and adjust gradient factors if necessary
-    let (gf_high, gf_low) = validate_gradient_factors(gf_high_input, gf_low_input);
-
-    // Initialize dive parameters with validated input
-    let mut dive_params = DiveParameters::new(gf_high, gf_low);
-
     // Initialize tissues with user-specified conditions
     let mut tissues = initialize_tissues(surface_pressure, temperature);
 
     println!("Dive Parameters:");
     println!("  Descent Speed: {:.2} m/s", dive_params.descent_speed);
     println!("  Ascent Speed: {:.2} m/s", dive_params.ascent_speed);
+    println!("  Conservatism: {}", dive_params.conservatism.name());
     println!("  GF Low: {:.0}%", dive_params.gf_low * 100.0);
     println!("  GF High: {:.0}%", dive_params.gf_high * 100.0);
     println!();
@@ -168,8 +168,8 @@ fn demonstrate_dive_simulation(dive_params: &mut DiveParameters, tissues: &mut [
         println!("✅ Successful ascent to surface - all tissues cleared");
     }
 
-    // Show simulation details - only available with serde feature
-    #[cfg(feature = "serde")]
+    // Show simulation details - only available with the alloc feature
+    #[cfg(feature = "alloc")]
     {
         println!("\nSimulation recorded {} data points", outputs.depths.len());
         if !outputs.depths.is_empty() {
@@ -179,93 +179,35 @@ fn demonstrate_dive_simulation(dive_params: &mut DiveParameters, tissues: &mut [
             println!("Final depth: {:.1}m", min_depth);
             
             // Calculate total dive time
-            let total_time = outputs.depths.len() as f32 * interval_seconds / 60.0;
+            let total_time = outputs.times.last().copied().unwrap_or(0.0) / 60.0;
             println!("Total simulation time: {:.1} minutes", total_time);
             
-            // Analyze and print decompression stops
-            analyze_decompression_stops(&outputs.depths, interval_seconds);
+            // Print decompression stops from the simulation event log
+            analyze_decompression_stops(&outputs);
         }
     }
 
-    #[cfg(not(feature = "serde"))]
+    #[cfg(not(feature = "alloc"))]
     {
-        println!("\n⚠️  Note: Detailed decompression stop analysis requires the 'serde' feature.");
-        println!("   Run with: cargo run --features serde --example planner");
+        println!("\n⚠️  Note: Detailed decompression stop analysis requires the 'alloc' feature.");
+        println!("   Run with: cargo run --features alloc --example planner");
         println!("   The simulation does include decompression stops, but they're not recorded for analysis.");
     }
 }
 
-fn analyze_decompression_stops(depths: &[f32], interval_seconds: f32) {
+#[cfg(feature = "alloc")]
+fn analyze_decompression_stops(outputs: &SimulationOutputs) {
     println!("\n=== Decompression Stop Analysis ===");
-    
-    if depths.len() < 2 {
-        println!("Insufficient data for stop analysis");
-        return;
-    }
-    
-    let max_depth = depths.iter().fold(0.0f32, |a, &b| a.max(b));
-    let mut stops = Vec::new();
-    let mut current_stop_depth = None;
-    let mut stop_start_time = 0.0;
-    let mut ascending = false;
-    
-    // Track when we start ascending from the bottom
-    let mut bottom_phase_ended = false;
-    
-    // Find when we actually reach maximum depth (or close to it) first
-    let mut reached_max_depth = false;
-    
-    for (i, &depth) in depths.iter().enumerate() {
-        let time_minutes = i as f32 * interval_seconds / 60.0;
-        
-        // First, check if we've reached near the maximum depth
-        if !reached_max_depth && depth >= max_depth * 0.95 {
-            reached_max_depth = true;
-        }
-        
-        // Only start looking for ascent after we've reached the maximum depth
-        if reached_max_depth && !bottom_phase_ended && depth < max_depth * 0.95 {
-            bottom_phase_ended = true;
-            ascending = true;
-            println!("Ascent phase starts at {:.1} minutes", time_minutes);
-        }
-        
-        if !bottom_phase_ended {
-            continue; // Still in descent/bottom phase
-        }
-        
-        if i == 0 {
-            continue;
-        }
-        
-        let prev_depth = depths[i - 1];
-        
-        // Detect start of a stop (depth stays the same while ascending)
-        if current_stop_depth.is_none() && depth == prev_depth && depth > 0.5 && ascending {
-            current_stop_depth = Some(depth);
-            stop_start_time = time_minutes;
-        }
-        // Detect end of a stop (depth changes)
-        else if let Some(stop_depth) = current_stop_depth {
-            if depth != stop_depth {
-                let stop_duration = time_minutes - stop_start_time;
-                // Only record stops that last at least one interval and are at least 1m deep
-                if stop_duration >= interval_seconds / 60.0 && stop_depth >= 1.0 {
-                    stops.push((stop_depth, stop_duration, stop_start_time));
-                }
-                current_stop_depth = None;
-            }
-        }
-    }
-    
-    // Handle case where simulation ends during a stop
-    if let Some(stop_depth) = current_stop_depth {
-        let final_time = (depths.len() - 1) as f32 * interval_seconds / 60.0;
-        let stop_duration = final_time - stop_start_time;
-        if stop_duration >= interval_seconds / 60.0 && stop_depth >= 1.0 {
-            stops.push((stop_depth, stop_duration, stop_start_time));
-        }
+
+    if let Some(ascent) = outputs.events.iter().find(|event| event.kind == SimulationEventKind::PhaseChange(DivePhase::Ascent)) {
+        println!("Ascent phase starts at {:.1} minutes", ascent.time / 60.0);
     }
+
+    // (depth, start time, duration) in minutes
+    let stops: Vec<(f32, f32, f32)> = outputs.stops()
+        .into_iter()
+        .map(|(depth, arrival, duration)| (depth, arrival / 60.0, duration / 60.0))
+        .collect();
     
     if stops.is_empty() {
         println!("No decompression stops detected");
@@ -274,10 +216,10 @@ fn analyze_decompression_stops(depths: &[f32], interval_seconds: f32) {
         println!("Depth (m) | Duration (secs) | Duration (min) | Start Time (min) | End Time (min)");
         println!("----------|----------------|----------------|------------------|----------------");
         
-        let total_deco_time: f32 = stops.iter().map(|(_, duration, _)| duration).sum();
+        let total_deco_time: f32 = stops.iter().map(|(_, _, duration)| duration).sum();
         let num_stops = stops.len();
         
-        for (depth, duration, start_time) in &stops {
+        for (depth, start_time, duration) in &stops {
             println!("   {:4.1}   |     {:6.1}     |     {:6.1}     |      {:6.1}      |      {:6.1}", depth, duration * 60., duration, start_time, start_time + duration);
         }
         
@@ -0,0 +1,298 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use defmt::Format;
+
+pub const ALARM_KINDS: usize = 9;
+
+#[derive(Debug, Format, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AlarmKind {
+    MaxDepth,           // m
+    DiveTime,           // s
+    NdlLow,             // min
+    CeilingViolation,   // m above the ceiling
+    AscentRate,         // m/min
+    Ppo2High,           // bar
+    Ppo2Low,            // bar
+    Cns,                // %
+    GasReserve,         // bar
+}
+
+impl AlarmKind {
+    pub const ALL: [AlarmKind; ALARM_KINDS] = [
+        AlarmKind::MaxDepth,
+        AlarmKind::DiveTime,
+        AlarmKind::NdlLow,
+        AlarmKind::CeilingViolation,
+        AlarmKind::AscentRate,
+        AlarmKind::Ppo2High,
+        AlarmKind::Ppo2Low,
+        AlarmKind::Cns,
+        AlarmKind::GasReserve,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Low-limit alarms trigger when the value drops below the threshold instead of above it.
+    pub fn is_low_limit(self) -> bool {
+        matches!(self, AlarmKind::NdlLow | AlarmKind::Ppo2Low | AlarmKind::GasReserve)
+    }
+
+    fn value(self, inputs: &AlarmInputs) -> Option<f32> {
+        match self {
+            AlarmKind::MaxDepth => Some(inputs.depth),
+            AlarmKind::DiveTime => Some(inputs.dive_time),
+            AlarmKind::NdlLow => inputs.ndl,
+            AlarmKind::CeilingViolation => Some(inputs.ceiling - inputs.depth),
+            AlarmKind::AscentRate => Some(inputs.ascent_rate),
+            AlarmKind::Ppo2High | AlarmKind::Ppo2Low => Some(inputs.ppo2),
+            AlarmKind::Cns => Some(inputs.cns),
+            AlarmKind::GasReserve => inputs.gas_pressure,
+        }
+    }
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AlarmPriority {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AlarmRule {
+    pub kind: AlarmKind,
+    pub enabled: bool,
+    pub threshold: f32,
+    /// Distance the value has to move back past the threshold before the alarm clears
+    pub hysteresis: f32,
+    pub priority: AlarmPriority,
+}
+
+impl AlarmRule {
+    pub fn new(kind: AlarmKind, threshold: f32, hysteresis: f32, priority: AlarmPriority) -> Self {
+        AlarmRule {
+            kind,
+            enabled: true,
+            threshold,
+            hysteresis,
+            priority,
+        }
+    }
+
+    fn triggers(&self, value: f32) -> bool {
+        if self.kind.is_low_limit() {
+            value < self.threshold
+        } else {
+            value > self.threshold
+        }
+    }
+
+    fn clears(&self, value: f32) -> bool {
+        if self.kind.is_low_limit() {
+            value >= self.threshold + self.hysteresis
+        } else {
+            value <= self.threshold - self.hysteresis
+        }
+    }
+}
+
+/// A complete set of alarm rules, one per kind, that can be stored and shared as a profile.
+/// The rules may be in any order; a stored profile missing a kind is rejected when loaded.
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "StoredProfile"))]
+pub struct AlarmProfile {
+    pub rules: [AlarmRule; ALARM_KINDS],
+}
+
+impl AlarmProfile {
+    /// Profile from one rule per kind in any order, sorted by kind. `None` when a kind has no rule.
+    pub fn from_rules(rules: [AlarmRule; ALARM_KINDS]) -> Option<Self> {
+        let mut sorted = rules;
+        for kind in AlarmKind::ALL {
+            sorted[kind.index()] = *rules.iter().find(|rule| rule.kind == kind)?;
+        }
+        Some(AlarmProfile { rules: sorted })
+    }
+
+    /// Rule for `kind`, wherever it is in `rules`. `None` only for a hand-built profile without a
+    /// rule for `kind`.
+    pub fn rule(&self, kind: AlarmKind) -> Option<&AlarmRule> {
+        self.rules.iter().find(|rule| rule.kind == kind)
+    }
+
+    pub fn rule_mut(&mut self, kind: AlarmKind) -> Option<&mut AlarmRule> {
+        self.rules.iter_mut().find(|rule| rule.kind == kind)
+    }
+}
+
+/// `AlarmProfile` as stored, before checking that it has a rule for every kind
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct StoredProfile {
+    rules: [AlarmRule; ALARM_KINDS],
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<StoredProfile> for AlarmProfile {
+    type Error = &'static str;
+
+    fn try_from(stored: StoredProfile) -> Result<Self, Self::Error> {
+        AlarmProfile::from_rules(stored.rules).ok_or("alarm profile needs one rule for every alarm kind")
+    }
+}
+
+impl Default for AlarmProfile {
+    fn default() -> Self {
+        AlarmProfile {
+            rules: [
+                AlarmRule::new(AlarmKind::MaxDepth, 40.0, 1.0, AlarmPriority::Warning),
+                AlarmRule::new(AlarmKind::DiveTime, 60.0 * 60.0, 0.0, AlarmPriority::Info),
+                AlarmRule::new(AlarmKind::NdlLow, 3.0, 1.0, AlarmPriority::Warning),
+                AlarmRule::new(AlarmKind::CeilingViolation, 0.0, 0.3, AlarmPriority::Critical),
+                AlarmRule::new(AlarmKind::AscentRate, 12.0, 2.0, AlarmPriority::Critical),
+                AlarmRule::new(AlarmKind::Ppo2High, 1.6, 0.05, AlarmPriority::Critical),
+                AlarmRule::new(AlarmKind::Ppo2Low, 0.18, 0.02, AlarmPriority::Critical),
+                AlarmRule::new(AlarmKind::Cns, 80.0, 5.0, AlarmPriority::Warning),
+                AlarmRule::new(AlarmKind::GasReserve, 50.0, 5.0, AlarmPriority::Warning),
+            ],
+        }
+    }
+}
+
+/// Current readings the alarms are evaluated against.
+#[derive(Debug, Format, Copy, Clone, Default)]
+pub struct AlarmInputs {
+    pub depth: f32,                 // m
+    pub dive_time: f32,             // s
+    pub ndl: Option<f32>,           // min, None while in deco
+    pub ceiling: f32,               // m
+    pub ascent_rate: f32,           // m/min, positive when ascending
+    pub ppo2: f32,                  // bar
+    pub cns: f32,                   // %
+    pub gas_pressure: Option<f32>,  // bar, None without a transmitter
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AlarmStatus {
+    #[default]
+    Inactive,
+    Active,
+    /// Still active, but silenced by the diver until it clears
+    Acknowledged,
+}
+
+/// Bit set of alarm kinds.
+#[derive(Debug, Format, Copy, Clone, PartialEq, Eq, Default)]
+pub struct AlarmSet(u16);
+
+impl AlarmSet {
+    pub fn contains(&self, kind: AlarmKind) -> bool {
+        self.0 & (1 << kind.index()) != 0
+    }
+
+    pub fn insert(&mut self, kind: AlarmKind) {
+        self.0 |= 1 << kind.index();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = AlarmKind> + '_ {
+        AlarmKind::ALL.into_iter().filter(|kind| self.contains(*kind))
+    }
+}
+
+#[derive(Debug, Format, Copy, Clone)]
+pub struct AlarmMonitor {
+    pub profile: AlarmProfile,
+    status: [AlarmStatus; ALARM_KINDS],
+}
+
+impl AlarmMonitor {
+    pub fn new(profile: AlarmProfile) -> Self {
+        AlarmMonitor {
+            profile,
+            status: [AlarmStatus::Inactive; ALARM_KINDS],
+        }
+    }
+
+    /// Update every alarm from the current readings and return the ones that were raised by this call.
+    pub fn evaluate(&mut self, inputs: &AlarmInputs) -> AlarmSet {
+        let mut raised = AlarmSet::default();
+
+        for rule in self.profile.rules.iter() {
+            let status = &mut self.status[rule.kind.index()];
+            let value = match rule.kind.value(inputs) {
+                Some(value) if rule.enabled => value,
+                _ => {
+                    *status = AlarmStatus::Inactive;
+                    continue;
+                }
+            };
+
+            match *status {
+                AlarmStatus::Inactive => {
+                    if rule.triggers(value) {
+                        *status = AlarmStatus::Active;
+                        raised.insert(rule.kind);
+                    }
+                }
+                AlarmStatus::Active | AlarmStatus::Acknowledged => {
+                    if rule.clears(value) {
+                        *status = AlarmStatus::Inactive;
+                    }
+                }
+            }
+        }
+
+        raised
+    }
+
+    pub fn status(&self, kind: AlarmKind) -> AlarmStatus {
+        self.status[kind.index()]
+    }
+
+    pub fn acknowledge(&mut self, kind: AlarmKind) {
+        let status = &mut self.status[kind.index()];
+        if *status == AlarmStatus::Active {
+            *status = AlarmStatus::Acknowledged;
+        }
+    }
+
+    pub fn acknowledge_all(&mut self) {
+        for kind in AlarmKind::ALL {
+            self.acknowledge(kind);
+        }
+    }
+
+    /// Alarms that are active and not yet acknowledged.
+    pub fn active(&self) -> AlarmSet {
+        let mut set = AlarmSet::default();
+        for kind in AlarmKind::ALL {
+            if self.status(kind) == AlarmStatus::Active {
+                set.insert(kind);
+            }
+        }
+        set
+    }
+
+    /// The unacknowledged alarm with the highest priority, the one a display should show.
+    pub fn highest_active(&self) -> Option<AlarmKind> {
+        self.active().iter().max_by_key(|kind| self.profile.rule(*kind).map(|rule| rule.priority))
+    }
+}
+
+impl Default for AlarmMonitor {
+    fn default() -> Self {
+        AlarmMonitor::new(AlarmProfile::default())
+    }
+}
@@ -0,0 +1,705 @@
+//! Non-interactive decompression planner.
+//!
+//! Plans come from a plan file (see `plan_file`) or from the command line, the runtime table and
+//! the gas used from each cylinder are printed to stdout as text, JSON or CSV, optionally with the
+//! lost-gas contingency schedules and deeper/longer slates (text and JSON only). With
+//! `--table-depths` and `--table-times` a decompression table is printed instead (see `table`).
+//!
+//! Exit codes: 0 the plan is within limits, 1 invalid arguments or plan file, 2 the plan breaks
+//! a limit (ceiling violation, stop time limit, ppO2 out of range, a cylinder runs out or does not
+//! hold its minimum gas). A cylinder dropping below its reserve is reported as a warning only.
+
+use std::fmt::Write as _;
+use std::process::ExitCode;
+
+use dive_computer_deco::conservatism::Conservatism;
+use dive_computer_deco::contingency::{deeper_longer_plans, lost_gas_plans, slate, Contingency, Loss, Variant};
+use dive_computer_deco::cylinder;
+use dive_computer_deco::gas::{gas_usage, minimum_gas, turn_points, Cylinder, CylinderRole, GasRule, GasUsage, MinimumGas, MinimumGasSettings, TurnPoint, DEFAULT_RESERVE};
+use dive_computer_deco::plan::{surface_pressure_at_altitude, DivePlan, Segment, SegmentTime, WaterType};
+use dive_computer_deco::plan_file::PlanFile;
+use dive_computer_deco::simulate::{RuntimeEntry, RuntimeKind, SimulationEventKind, SimulationOutputs};
+use dive_computer_deco::table::deco_table;
+use dive_computer_deco::tissue::Tissue;
+use dive_computer_deco::{water_vapor_pressure, DiveParameters, Gas, FN2};
+use serde::Serialize;
+
+const USAGE: &str = "\
+Usage: deco-planner [OPTIONS]
+
+Plan:
+  --plan FILE             read the plan from a plan file, other plan options are ignored
+  --depth M --time MIN    single level dive
+  --level M:MIN[:GAS]     add a level, repeat for multi-level dives
+  --gas GAS               bottom gas for levels without one, O2 or O2/HE in percent (default 21)
+  --conservatism LEVEL    low (GF 45/95), medium (GF 40/85) or high (GF 35/75)
+  --gf LOW/HIGH           custom gradient factors in percent (default 30/85)
+  --altitude M            altitude of the dive site (default 0)
+  --fresh                 fresh water
+  --sac L/MIN             surface air consumption on the bottom (default 20)
+  --deco-sac L/MIN        surface air consumption on the ascent and at stops (default --sac)
+  --descent-rate M/MIN    descent speed (default 20)
+  --ascent-rate M/MIN     ascent speed (default 10)
+  --cylinder L:BAR[:GAS[:ROLE]]
+                          cylinder water volume or catalogue name (AL80, S12 232, ...) and fill for
+                          a gas, repeat for several (default bottom gas); ROLE is back, stage, deco
+                          or bailout (default back), cylinders of one gas are drawn stages first,
+                          then the fullest, bailout last
+  --reserve BAR           pressure each cylinder should keep, below it a warning is printed (default 50)
+
+Minimum gas:
+  --stressed-sac L/MIN    SAC of each diver during a shared out-of-gas ascent (default 30)
+  --problem-time MIN      time at depth before the shared ascent starts (default 1)
+
+Turn pressures:
+  --rule thirds|halves|F  gas rule, F is the usable fraction of the supply (e.g. 0.25)
+  --buddy L:BAR[:GAS]     buddy cylinder, volume or catalogue name, repeat for each; mismatched cylinders lower the turn pressure
+
+Contingencies:
+  --lost-gas              add a schedule for each lost deco gas and each lost cylinder
+  --deeper M[,M...]       add a slate of variants this much deeper, e.g. 3,6
+  --longer MIN[,MIN...]   add a slate of variants this much longer, combined with --deeper
+
+Decompression table (replaces the plan, uses --gas, --gf, the rates, --altitude and --fresh):
+  --table-depths M[,M...] depths of the table
+  --table-times MIN[,MIN...]
+                          bottom times from leaving the surface, for every depth
+
+Limits:
+  --max-ppo2 BAR          highest ppO2 accepted, also for switching to deco gases on the ascent, for
+                          replacement gases and for the MOD of minimum gas points (default 1.6)
+  --min-ppo2 BAR          lowest ppO2 accepted (default 0.16)
+
+Output:
+  --format table|json|csv|markdown (default table, markdown for decompression tables only)
+  -h, --help
+
+Exit codes: 0 within limits, 1 invalid input, 2 the plan breaks a limit";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Table,
+    Json,
+    Csv,
+    Markdown,
+}
+
+struct Options {
+    plan_path: Option<String>,
+    depth: Option<f32>,
+    time: Option<f32>,
+    levels: Vec<(f32, f32, Option<Gas>)>,
+    gas: Gas,
+    conservatism: Conservatism,
+    altitude: f32,
+    water: WaterType,
+    sac_rate: f32,
+    deco_sac_rate: Option<f32>,
+    descent_rate: f32,
+    ascent_rate: f32,
+    cylinders: Vec<(Cylinder, Option<Gas>)>,
+    reserve: f32,
+    minimum_gas: MinimumGasSettings,
+    rule: Option<GasRule>,
+    buddies: Vec<(Cylinder, Option<Gas>)>,
+    lost_gas: bool,
+    deeper: Vec<f32>,
+    longer: Vec<f32>,
+    table_depths: Vec<f32>,
+    table_times: Vec<f32>,
+    max_ppo2: f32,
+    min_ppo2: f32,
+    format: Format,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            plan_path: None,
+            depth: None,
+            time: None,
+            levels: Vec::new(),
+            gas: Gas::air(),
+            conservatism: Conservatism::Custom { gf_low: 0.3, gf_high: 0.85 },
+            altitude: 0.0,
+            water: WaterType::Salt,
+            sac_rate: DiveParameters::default().sac_rate,
+            deco_sac_rate: None,
+            descent_rate: DiveParameters::default().descent_speed * 60.0,
+            ascent_rate: DiveParameters::default().ascent_speed * 60.0,
+            cylinders: Vec::new(),
+            reserve: DEFAULT_RESERVE,
+            minimum_gas: MinimumGasSettings::default(),
+            rule: None,
+            buddies: Vec::new(),
+            lost_gas: false,
+            deeper: Vec::new(),
+            longer: Vec::new(),
+            table_depths: Vec::new(),
+            table_times: Vec::new(),
+            max_ppo2: 1.6,
+            min_ppo2: 0.16,
+            format: Format::Table,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RuntimeRow {
+    #[serde(flatten)]
+    entry: RuntimeEntry,
+    gas_used: f32,          // L
+}
+
+#[derive(Serialize)]
+struct CylinderReport {
+    cylinder: Cylinder,
+    gas: Gas,
+    used: f32,              // L
+    used_bar: f32,          // bar
+    end_pressure: f32,      // bar
+    #[serde(skip_serializing_if = "Option::is_none")]
+    below_reserve: Option<f32>,     // s
+    minimum_gas: MinimumGas,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    turn: Option<TurnPoint>,
+}
+
+#[derive(Serialize)]
+struct ContingencyReport {
+    lost: String,
+    runtime: Vec<RuntimeEntry>,
+    total_runtime: f32,     // s
+    extra_time: f32,        // s
+    enough: bool,
+}
+
+#[derive(Serialize)]
+struct VariantReport {
+    deeper: f32,            // m
+    longer: f32,            // s
+    total_runtime: f32,     // s
+    time_to_surface: f32,   // s
+    stops: Vec<(f32, f32)>, // m, s
+    gas_used: Vec<f32>,     // L per cylinder
+    enough: bool,
+}
+
+#[derive(Serialize)]
+struct Report {
+    runtime: Vec<RuntimeRow>,
+    cylinders: Vec<CylinderReport>,
+    total_runtime: f32,     // s
+    deco_time: f32,         // s
+    max_depth: f32,         // m
+    limits: Vec<String>,
+    warnings: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    contingencies: Vec<ContingencyReport>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    variants: Vec<VariantReport>,
+    #[serde(skip)]
+    slate: String,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(1);
+        }
+    };
+
+    if !options.table_depths.is_empty() || !options.table_times.is_empty() {
+        return print_table(&options);
+    }
+    if options.format == Format::Markdown {
+        eprintln!("error: markdown is only available for decompression tables\n\n{}", USAGE);
+        return ExitCode::from(1);
+    }
+
+    let (mut plan, params) = match build_plan(&options) {
+        Ok(plan) => plan,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return ExitCode::from(1);
+        }
+    };
+
+    // carried deco gases are switched to on the ascent
+    plan.deco_ppo2.get_or_insert(options.max_ppo2);
+
+    let temperature = 20.0;
+    let mut tissues = [Tissue::default(); 16];
+    for tissue in tissues.iter_mut() {
+        tissue.load_n2 = (plan.surface_pressure - water_vapor_pressure(temperature)) * FN2;
+        tissue.load_he = 0.0;
+    }
+
+    let start_tissues = tissues;
+    let outputs = match plan.simulate(&params, &mut tissues, temperature, 10.0) {
+        Ok(outputs) => outputs,
+        Err(error) => {
+            eprintln!("error: plan could not be simulated: {:?}", error);
+            return ExitCode::from(1);
+        }
+    };
+
+    let usage = gas_usage(&plan, &params, &outputs);
+    let minimum = minimum_gas(&plan, &params, &outputs, &MinimumGasSettings { max_ppo2: options.max_ppo2, ..options.minimum_gas }, temperature, 10.0);
+    let turns = match options.rule {
+        Some(rule) => turn_points(&plan, &usage, &outputs, rule, &buddy_cylinders(&plan, &options)).into_iter().map(Some).collect(),
+        None => Vec::from_iter(plan.cylinders.iter().map(|_| None)),
+    };
+    let contingencies = if options.lost_gas {
+        match lost_gas_plans(&plan, &params, &start_tissues, temperature, 10.0, options.max_ppo2) {
+            Ok(contingencies) => contingencies,
+            Err(error) => {
+                eprintln!("error: lost gas plans could not be simulated: {:?}", error);
+                return ExitCode::from(1);
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    let mut report = report(&outputs, &usage, &minimum, &turns, &plan, &options);
+    report.contingencies = contingencies.iter().map(|contingency| contingency_report(contingency, &plan)).collect();
+    if !options.deeper.is_empty() || !options.longer.is_empty() {
+        // the planned dive heads the slate
+        let deeper: Vec<f32> = std::iter::once(0.0).chain(options.deeper.iter().copied()).collect();
+        let longer: Vec<f32> = std::iter::once(0.0).chain(options.longer.iter().map(|minutes| minutes * 60.0)).collect();
+        let variants = match deeper_longer_plans(&plan, &params, &start_tissues, temperature, 10.0, &deeper, &longer) {
+            Ok(variants) => variants,
+            Err(error) => {
+                eprintln!("error: deeper and longer plans could not be simulated: {:?}", error);
+                return ExitCode::from(1);
+            }
+        };
+        report.slate = slate(&variants);
+        report.variants = variants.iter().map(variant_report).collect();
+    }
+    let printed = match options.format {
+        Format::Table => format_table(&report),
+        Format::Json => serde_json::to_string_pretty(&report).unwrap_or_default(),
+        Format::Csv => format_csv(&report),
+        Format::Markdown => unreachable!("rejected before planning"),
+    };
+    println!("{}", printed);
+    if options.format != Format::Table {
+        for warning in report.warnings.iter() {
+            eprintln!("warning: {}", warning);
+        }
+    }
+
+    if report.limits.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        if options.format != Format::Table {
+            for limit in report.limits.iter() {
+                eprintln!("limit: {}", limit);
+            }
+        }
+        ExitCode::from(2)
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--plan" => options.plan_path = Some(value()?.clone()),
+            "--depth" => options.depth = Some(parse_number(value()?)?),
+            "--time" => options.time = Some(parse_number(value()?)?),
+            "--level" => options.levels.push(parse_level(value()?)?),
+            "--gas" => options.gas = parse_gas(value()?)?,
+            "--gf" => {
+                let (low, high) = value()?.split_once('/').ok_or("--gf expects LOW/HIGH")?;
+                options.conservatism = Conservatism::Custom { gf_low: parse_number(low)? / 100.0, gf_high: parse_number(high)? / 100.0 };
+            }
+            "--conservatism" => {
+                let level = value()?;
+                options.conservatism = Conservatism::from_name(level).ok_or_else(|| format!("unknown conservatism '{}', use --gf for custom gradient factors", level))?;
+            }
+            "--altitude" => options.altitude = parse_number(value()?)?,
+            "--fresh" => options.water = WaterType::Fresh,
+            "--sac" => options.sac_rate = parse_number(value()?)?,
+            "--deco-sac" => options.deco_sac_rate = Some(parse_number(value()?)?),
+            "--descent-rate" => options.descent_rate = parse_positive(value()?)?,
+            "--ascent-rate" => options.ascent_rate = parse_positive(value()?)?,
+            "--cylinder" => options.cylinders.push(parse_cylinder(value()?)?),
+            "--reserve" => options.reserve = parse_number(value()?)?,
+            "--stressed-sac" => options.minimum_gas.stressed_sac_rate = parse_number(value()?)?,
+            "--problem-time" => options.minimum_gas.problem_solving_time = parse_number(value()?)? * 60.0,
+            "--rule" => {
+                options.rule = Some(match value()?.as_str() {
+                    "thirds" => GasRule::Thirds,
+                    "halves" => GasRule::Halves,
+                    fraction => match parse_number(fraction)? {
+                        fraction if fraction > 0.0 && fraction <= 1.0 => GasRule::Fraction(fraction),
+                        _ => return Err(format!("gas rule fraction '{}' is not between 0 and 1", fraction)),
+                    },
+                })
+            }
+            "--buddy" => options.buddies.push(parse_cylinder(value()?)?),
+            "--lost-gas" => options.lost_gas = true,
+            "--deeper" => options.deeper = parse_list(value()?)?,
+            "--longer" => options.longer = parse_list(value()?)?,
+            "--table-depths" => options.table_depths = parse_list(value()?)?,
+            "--table-times" => options.table_times = parse_list(value()?)?,
+            "--max-ppo2" => options.max_ppo2 = parse_number(value()?)?,
+            "--min-ppo2" => options.min_ppo2 = parse_number(value()?)?,
+            "--format" => {
+                options.format = match value()?.as_str() {
+                    "table" => Format::Table,
+                    "json" => Format::Json,
+                    "csv" => Format::Csv,
+                    "markdown" => Format::Markdown,
+                    other => return Err(format!("unknown format '{}'", other)),
+                }
+            }
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_number(value: &str) -> Result<f32, String> {
+    value.trim().parse::<f32>().map_err(|_| format!("'{}' is not a number", value))
+}
+
+fn parse_positive(value: &str) -> Result<f32, String> {
+    match parse_number(value)? {
+        number if number > 0.0 => Ok(number),
+        _ => Err(format!("'{}' must be above 0", value)),
+    }
+}
+
+/// Comma-separated positive numbers
+fn parse_list(value: &str) -> Result<Vec<f32>, String> {
+    value.split(',').map(parse_positive).collect()
+}
+
+/// O2 or O2/HE in percent
+fn parse_gas(value: &str) -> Result<Gas, String> {
+    let (o2, he) = match value.split_once('/') {
+        Some((o2, he)) => (parse_number(o2)?, parse_number(he)?),
+        None => (parse_number(value)?, 0.0),
+    };
+    if o2 <= 0.0 || he < 0.0 || o2 + he > 100.0 {
+        return Err(format!("invalid gas '{}'", value));
+    }
+    Ok(Gas::new(o2 / 100.0, he / 100.0))
+}
+
+/// DEPTH:MINUTES[:GAS]
+fn parse_level(value: &str) -> Result<(f32, f32, Option<Gas>), String> {
+    let mut parts = value.splitn(3, ':');
+    let depth = parse_number(parts.next().unwrap_or_default())?;
+    let time = parse_number(parts.next().ok_or_else(|| format!("level '{}' needs DEPTH:MINUTES", value))?)?;
+    let gas = parts.next().map(parse_gas).transpose()?;
+    Ok((depth, time, gas))
+}
+
+/// LITRES:BAR[:GAS[:ROLE]] or NAME:BAR[:GAS[:ROLE]], the gas index is filled in once the plan's
+/// gases are known; an empty GAS is the bottom gas
+fn parse_cylinder(value: &str) -> Result<(Cylinder, Option<Gas>), String> {
+    let mut parts = value.splitn(4, ':');
+    let size = parts.next().unwrap_or_default();
+    let pressure = parse_number(parts.next().ok_or_else(|| format!("cylinder '{}' needs LITRES:BAR", value))?)?;
+    let cylinder = match cylinder::find(size) {
+        Some(spec) => spec.cylinder(pressure, 0),
+        None => {
+            let volume = parse_number(size).map_err(|_| format!("'{}' is neither a volume nor a known cylinder", size))?;
+            Cylinder::new(volume, pressure, pressure, 0)
+        }
+    };
+    if cylinder.volume <= 0.0 || pressure <= 0.0 {
+        return Err(format!("invalid cylinder '{}'", value));
+    }
+    let gas = parts.next().filter(|gas| !gas.is_empty()).map(parse_gas).transpose()?;
+    let role = match parts.next() {
+        None | Some("back") => CylinderRole::BackGas,
+        Some("stage") => CylinderRole::Stage,
+        Some("deco") => CylinderRole::Deco,
+        Some("bailout") => CylinderRole::Bailout,
+        Some(role) => return Err(format!("unknown cylinder role '{}'", role)),
+    };
+    Ok((Cylinder { role, ..cylinder }, gas))
+}
+
+fn build_plan(options: &Options) -> Result<(DivePlan, DiveParameters), String> {
+    if let Some(path) = &options.plan_path {
+        let contents = std::fs::read_to_string(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+        let file = PlanFile::from_json(&contents).map_err(|error| error.to_string())?;
+        let plan = file.dive_plan().map_err(|error| format!("invalid plan in {}: {:?}", path, error))?;
+        let params = file.dive_parameters();
+        params.validate().map_err(|errors| format!("invalid dive parameters in {}: {}", path, errors))?;
+        return Ok((plan, params));
+    }
+
+    let mut levels = options.levels.clone();
+    match (options.depth, options.time) {
+        (Some(depth), Some(time)) => levels.insert(0, (depth, time, None)),
+        (None, None) => {}
+        _ => return Err(String::from("--depth and --time go together")),
+    }
+    if levels.is_empty() {
+        return Err(String::from("nothing to plan, use --plan, --depth/--time or --level"));
+    }
+
+    let mut plan = DivePlan::new(surface_pressure_at_altitude(options.altitude), options.water);
+    let gas_index = |plan: &mut DivePlan, gas: Gas| match plan.gases.iter().position(|known| *known == gas) {
+        Some(index) => index,
+        None => plan.add_gas(gas),
+    };
+    for (depth, minutes, gas) in levels {
+        let index = gas_index(&mut plan, gas.unwrap_or(options.gas));
+        plan.add_segment(Segment::new(depth, SegmentTime::Duration(minutes * 60.0), index));
+    }
+    for (cylinder, gas) in options.cylinders.iter() {
+        let index = gas_index(&mut plan, gas.unwrap_or(options.gas));
+        plan.add_cylinder(Cylinder { gas: index, reserve: options.reserve, ..*cylinder });
+    }
+
+    Ok((plan, parameters(options)?))
+}
+
+fn parameters(options: &Options) -> Result<DiveParameters, String> {
+    DiveParameters::builder()
+        .conservatism(options.conservatism)
+        .descent_speed(options.descent_rate / 60.0)
+        .ascent_speed(options.ascent_rate / 60.0)
+        .sac_rate(options.sac_rate)
+        .deco_sac_rate(options.deco_sac_rate.unwrap_or(options.sac_rate))
+        .build()
+        .map_err(|errors| format!("invalid dive parameters: {}", errors))
+}
+
+fn print_table(options: &Options) -> ExitCode {
+    if options.table_depths.is_empty() || options.table_times.is_empty() {
+        eprintln!("error: --table-depths and --table-times go together\n\n{}", USAGE);
+        return ExitCode::from(1);
+    }
+    let params = match parameters(options) {
+        Ok(params) => params,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return ExitCode::from(1);
+        }
+    };
+    let times: Vec<f32> = options.table_times.iter().map(|minutes| minutes * 60.0).collect();
+    let table = match deco_table(&params, options.gas, surface_pressure_at_altitude(options.altitude), options.water, 20.0, 10.0, &options.table_depths, &times) {
+        Ok(table) => table,
+        Err(error) => {
+            eprintln!("error: table could not be simulated: {:?}", error);
+            return ExitCode::from(1);
+        }
+    };
+    let printed = match options.format {
+        Format::Table | Format::Markdown => table.to_markdown(),
+        Format::Json => table.to_json().unwrap_or_default(),
+        Format::Csv => table.to_csv(),
+    };
+    println!("{}", printed);
+
+    let limited: Vec<_> = table.rows.iter().filter(|row| row.stop_time_limit).collect();
+    if limited.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        if !matches!(options.format, Format::Table | Format::Markdown) {
+            for row in limited {
+                eprintln!("limit: {:.0} m for {:.0} min cuts a stop at the stop time limit", row.depth, row.bottom_time / 60.0);
+            }
+        }
+        ExitCode::from(2)
+    }
+}
+
+/// Buddy cylinders refer to the plan's gases, a buddy gas the plan does not use matches nothing.
+fn buddy_cylinders(plan: &DivePlan, options: &Options) -> Vec<Cylinder> {
+    options.buddies.iter()
+        .map(|(cylinder, gas)| {
+            let gas = gas.unwrap_or(options.gas);
+            let index = plan.gases.iter().position(|known| *known == gas).unwrap_or(plan.gases.len());
+            Cylinder { gas: index, ..*cylinder }
+        })
+        .collect()
+}
+
+fn report(outputs: &SimulationOutputs, usage: &GasUsage, minimum: &[MinimumGas], turns: &[Option<TurnPoint>], plan: &DivePlan, options: &Options) -> Report {
+    let runtime = outputs.runtime_table();
+    let mut limits = Vec::new();
+
+    for event in outputs.events.iter() {
+        match event.kind {
+            SimulationEventKind::CeilingViolation { ceiling } => {
+                limits.push(format!("ceiling of {:.0} m violated at {:.1} m, {:.0} min", ceiling, event.depth, event.time / 60.0));
+            }
+            SimulationEventKind::StopTimeLimit => {
+                limits.push(format!("stop at {:.0} m cut short by the per-stop time limit at {:.0} min", event.depth, event.time / 60.0));
+            }
+            _ => {}
+        }
+    }
+
+    let ppo2s: Vec<f32> = outputs.pressures.iter().zip(outputs.gases.iter()).map(|(pressure, gas)| pressure * gas.o2()).collect();
+    if let Some(i) = (0..ppo2s.len()).find(|i| ppo2s[*i] > options.max_ppo2) {
+        limits.push(format!("ppO2 {:.2} bar above {:.2} at {:.1} m", ppo2s[i], options.max_ppo2, outputs.depths[i]));
+    }
+    if let Some(i) = (0..ppo2s.len()).find(|i| ppo2s[*i] < options.min_ppo2) {
+        limits.push(format!("ppO2 {:.2} bar below {:.2} at {:.1} m", ppo2s[i], options.min_ppo2, outputs.depths[i]));
+    }
+
+    let cylinders: Vec<CylinderReport> = plan.cylinders.iter().zip(usage.cylinders.iter()).zip(minimum.iter()).zip(turns.iter())
+        .map(|(((cylinder, used), minimum_gas), turn)| CylinderReport {
+            cylinder: *cylinder,
+            gas: plan.gases[cylinder.gas],
+            used: used.litres,
+            used_bar: used.bar,
+            end_pressure: used.end_pressure,
+            below_reserve: used.below_reserve,
+            minimum_gas: *minimum_gas,
+            turn: *turn,
+        })
+        .collect();
+    let mut warnings = Vec::new();
+    for (i, cylinder) in cylinders.iter().enumerate() {
+        if let (Some(time), true) = (cylinder.below_reserve, cylinder.end_pressure >= 0.0) {
+            warnings.push(format!("cylinder {} ({}) drops below its {:.0} bar reserve at {:.1} min, {:.0} bar at the end", i + 1, gas_name(&cylinder.gas), cylinder.cylinder.reserve, time / 60.0, cylinder.end_pressure));
+        }
+        if cylinder.end_pressure < 0.0 {
+            limits.push(format!("cylinder {} ({}) runs out, {:.0} L needed, {:.0} L available", i + 1, gas_name(&cylinder.gas), cylinder.used, cylinder.cylinder.gas_volume(cylinder.cylinder.start_pressure, &cylinder.gas)));
+        }
+        let minimum_gas = &cylinder.minimum_gas;
+        if !minimum_gas.pass {
+            limits.push(format!("cylinder {} ({}) holds {:.0} bar at {:.0} m, {:.0} min, minimum gas is {:.0} bar", i + 1, gas_name(&cylinder.gas), minimum_gas.available, minimum_gas.depth, minimum_gas.time / 60.0, minimum_gas.bar));
+        }
+    }
+
+    Report {
+        total_runtime: runtime.last().map_or(0.0, |entry| entry.runtime),
+        deco_time: runtime.iter().filter(|entry| entry.kind == RuntimeKind::Stop).map(|entry| entry.duration).sum(),
+        max_depth: outputs.depths.iter().fold(0.0, |a, b| f32::max(a, *b)),
+        runtime: usage.segments.iter().map(|segment| RuntimeRow { entry: segment.entry, gas_used: segment.litres }).collect(),
+        cylinders,
+        limits,
+        warnings,
+        contingencies: Vec::new(),
+        variants: Vec::new(),
+        slate: String::new(),
+    }
+}
+
+fn variant_report(variant: &Variant) -> VariantReport {
+    VariantReport {
+        deeper: variant.deeper,
+        longer: variant.longer,
+        total_runtime: variant.total_runtime,
+        time_to_surface: variant.time_to_surface,
+        stops: variant.stops(),
+        gas_used: variant.usage.cylinders.iter().map(|cylinder| cylinder.litres).collect(),
+        enough: variant.enough,
+    }
+}
+
+fn contingency_report(contingency: &Contingency, plan: &DivePlan) -> ContingencyReport {
+    let lost = match contingency.loss {
+        Loss::Gas(gas) => gas_name(&plan.gases[gas]),
+        Loss::Cylinder(index) => format!("cylinder {} ({})", index + 1, gas_name(&plan.gases[plan.cylinders[index].gas])),
+    };
+    ContingencyReport {
+        lost,
+        runtime: contingency.runtime.clone(),
+        total_runtime: contingency.total_runtime,
+        extra_time: contingency.extra_time,
+        enough: contingency.enough,
+    }
+}
+
+fn gas_name(gas: &Gas) -> String {
+    let o2 = (gas.o2() * 100.0).round();
+    let he = (gas.he * 100.0).round();
+    if he > 0.0 {
+        format!("{}/{}", o2, he)
+    } else if o2 == 21.0 {
+        String::from("Air")
+    } else {
+        format!("EAN{}", o2)
+    }
+}
+
+fn format_table(report: &Report) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{:<8} {:>9} {:>9} {:>9} {:>9}  Gas", "", "Depth m", "Time min", "Run min", "Used L");
+    for row in report.runtime.iter() {
+        let entry = &row.entry;
+        let kind = match entry.kind {
+            RuntimeKind::Level => "Level",
+            RuntimeKind::Stop => "Stop",
+            RuntimeKind::Surface => "Surface",
+        };
+        let _ = writeln!(out, "{:<8} {:>9.0} {:>9.1} {:>9.1} {:>9.0}  {}", kind, entry.depth, entry.duration / 60.0, entry.runtime / 60.0, row.gas_used, gas_name(&entry.gas));
+    }
+    let _ = writeln!(out);
+    let _ = writeln!(out, "Max depth: {:.1} m", report.max_depth);
+    let _ = writeln!(out, "Deco time: {:.1} min", report.deco_time / 60.0);
+    let _ = write!(out, "Runtime: {:.1} min", report.total_runtime / 60.0);
+    for (i, cylinder) in report.cylinders.iter().enumerate() {
+        let role = match cylinder.cylinder.role {
+            CylinderRole::BackGas => "back gas",
+            CylinderRole::Stage => "stage",
+            CylinderRole::Deco => "deco",
+            CylinderRole::Bailout => "bailout",
+        };
+        let _ = write!(out, "\nCylinder {}: {:.1} L {} {}, {:.0} -> {:.0} bar ({:.0} L used)", i + 1, cylinder.cylinder.volume, gas_name(&cylinder.gas), role, cylinder.cylinder.start_pressure, cylinder.end_pressure, cylinder.used);
+        let minimum_gas = &cylinder.minimum_gas;
+        let _ = write!(out, "\n  minimum gas {:.0} bar ({:.0} L) from {:.0} m at {:.1} min, {:.0} bar left there: {}", minimum_gas.bar, minimum_gas.litres, minimum_gas.depth, minimum_gas.time / 60.0, minimum_gas.available, if minimum_gas.pass { "OK" } else { "FAIL" });
+        if let Some(turn) = &cylinder.turn {
+            match (turn.time, turn.depth) {
+                (Some(time), Some(depth)) => { let _ = write!(out, "\n  turn at {:.0} bar, reached at {:.1} min at {:.0} m", turn.turn_pressure, time / 60.0, depth); }
+                _ => { let _ = write!(out, "\n  turn at {:.0} bar, not reached", turn.turn_pressure); }
+            }
+        }
+    }
+    for contingency in report.contingencies.iter() {
+        let _ = write!(out, "\n\nLost {}: runtime {:.1} min ({:+.1} min), gas {}", contingency.lost, contingency.total_runtime / 60.0, contingency.extra_time / 60.0, if contingency.enough { "OK" } else { "NOT ENOUGH" });
+        for entry in contingency.runtime.iter().filter(|entry| entry.kind != RuntimeKind::Surface) {
+            let _ = write!(out, "\n  {:>4.0} m {:>5.1} min  run {:>5.1}  {}", entry.depth, entry.duration / 60.0, entry.runtime / 60.0, gas_name(&entry.gas));
+        }
+    }
+    if !report.slate.is_empty() {
+        let _ = write!(out, "\n\nDeeper/longer (depth time, TTS and runtime in min | stops m:min | gas per cylinder)\n{}", report.slate.trim_end());
+    }
+    for warning in report.warnings.iter() {
+        let _ = write!(out, "\nWARNING: {}", warning);
+    }
+    for limit in report.limits.iter() {
+        let _ = write!(out, "\nLIMIT: {}", limit);
+    }
+    out
+}
+
+fn format_csv(report: &Report) -> String {
+    let mut out = String::from("kind,depth_m,duration_min,runtime_min,o2,he,gas_used_l");
+    for row in report.runtime.iter() {
+        let entry = &row.entry;
+        let kind = match entry.kind {
+            RuntimeKind::Level => "level",
+            RuntimeKind::Stop => "stop",
+            RuntimeKind::Surface => "surface",
+        };
+        let _ = write!(out, "\n{},{:.1},{:.2},{:.2},{:.2},{:.2},{:.1}", kind, entry.depth, entry.duration / 60.0, entry.runtime / 60.0, entry.gas.o2(), entry.gas.he, row.gas_used);
+    }
+    out
+}
@@ -1,7 +1,11 @@
 use crate::tissue::Tissue;
 use crate::zh16c::ZhL16cGf;
-use crate::DiveParameters;
-use libm::fabsf;
+use crate::{check_gradient_factors, check_input, check_tissue_index, check_tissues, DecoError, DiveParameters, Input};
+use libm::sqrtf;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use defmt::Format;
 #[cfg(feature = "std")]
 use std::println;
 
@@ -33,7 +37,7 @@ fn interpolate_gf(
         fraction = 1.0;
     }
 
-    gf_low + (gf_high - gf_low) * fraction
+    gf_high + (gf_low - gf_high) * fraction
 }
 
 
@@ -46,237 +50,339 @@ pub fn ceiling_with_gf(
     surface_pressure: f32, // usually 1.0 bar
     round: bool,
 ) -> u32 {
-    let first_stop_pressure = first_stop_pressure(&[tissue.clone()], surface_pressure);
-
-    let pn2 = tissue.load_n2;
-    let phe = tissue.load_he;
-    let p_total = pn2 + phe;
+    let (result_meters, _) = ceiling_with_gf_exact(gf_low, gf_high, tissue, tissue_index, surface_pressure);
+    whole_metres(result_meters, round)
+}
 
+/// Lowest ambient pressure one compartment tolerates with gradient factor `gf` (bar, absolute).
+/// Every other ceiling of the crate is built on this one. A compartment without inert gas
+/// tolerates any pressure and gets 0.
+pub fn ceiling_pressure(gf: f32, tissue: &Tissue, tissue_index: usize) -> f32 {
+    let p_total = tissue.load_n2 + tissue.load_he;
     if p_total <= 0.0 {
-        return 0;
+        return 0.0;
     }
+    let (a, b) = coefficients(tissue, tissue_index);
+    tolerated_pressure(a, b, p_total, gf)
+}
 
-    // Bühlmann coefficients
-    let an2: f32 = ZhL16cGf::N2_A[tissue_index];
-    let bn2: f32 = ZhL16cGf::N2_B[tissue_index];
-    let ahe: f32 = ZhL16cGf::HE_A[tissue_index];
-    let bhe: f32 = ZhL16cGf::HE_B[tissue_index];
-    
-
-    let a = ((an2 * pn2) + (ahe * phe)) / p_total;
-    let b = ((bn2 * pn2) + (bhe * phe)) / p_total;
-
-    // Interpolate GF based on current pressure
-    // Clamp denominator in case first_stop == surface
-    let gf = interpolate_gf(
-        gf_low,
-        gf_high,
-        p_total,          // current tissue tension
-        surface_pressure,
-        first_stop_pressure,
-    );
-
-
-    // Bühlmann ceiling with GF
-    let denominator = (1.0 - b) * gf + b;
-    if denominator.abs() < 1e-10 {
-        return 0;
-    }
+/// Deepest `ceiling_pressure` with gradient factor `gf` across all tissues (bar) and the leading
+/// compartment
+pub fn max_ceiling_pressure(gf: f32, tissues: &[Tissue; 16]) -> (f32, usize) {
+    tissues
+        .iter()
+        .enumerate()
+        .map(|(i, tissue)| (ceiling_pressure(gf, tissue, i), i))
+        .fold((f32::NEG_INFINITY, 0), |max, current| if current.0 > max.0 { current } else { max })
+}
 
-    let result_bar = (b * p_total - gf * a * b) / denominator;
+/// Depth of an ambient pressure below a surface at `surface_pressure` (m at 10 m per bar),
+/// negative above the surface
+pub fn pressure_to_depth(pressure: f32, surface_pressure: f32) -> f32 {
+    (pressure - surface_pressure) * 10.0
+}
 
-    // Convert to meters relative to surface
-    let result_meters = (result_bar - surface_pressure) * 10.0;
+/// Stop depth for an exact ceiling (m): the next 3 m stop at or below it, 0 without a ceiling
+pub fn round_to_stop(ceiling: f32) -> u32 {
+    if ceiling > 0.0 { ((ceiling + 2.999) / 3.0) as u32 * 3 } else { 0 }
+}
 
-    if result_meters < 0.0 {
-        return 0;
-    }
+/// Exact ceiling (m) as whole metres, rounded to a stop or truncated
+fn whole_metres(ceiling: f32, round: bool) -> u32 {
+    if round { round_to_stop(ceiling) } else { ceiling.max(0.0) as u32 }
+}
 
-    if !round {
-        return result_meters as u32;
-    }
+/// Unrounded GF ceiling of one compartment in metres relative to the surface, together with the
+/// gradient factor applied to it. GF low sits at the compartment's own deepest stop, as if it led
+/// the ascent. Negative values mean the compartment could tolerate a shallower ambient pressure
+/// than the surface.
+pub fn ceiling_with_gf_exact(
+    gf_low: f32,
+    gf_high: f32,
+    tissue: &Tissue,
+    tissue_index: usize,
+    surface_pressure: f32,
+) -> (f32, f32) {
+    let slope = GfSlope::new(gf_low, gf_high, surface_pressure);
+    let first_stop_pressure = slope.deepest_stop(core::iter::once((tissue_index, tissue)));
+    let (ceiling_bar, gf) = slope.compartment_ceiling(tissue, tissue_index, first_stop_pressure);
+    (pressure_to_depth(ceiling_bar, surface_pressure), gf)
+}
+
+/// Bühlmann a and b of one compartment, weighted by its N2 and He loads
+fn coefficients(tissue: &Tissue, tissue_index: usize) -> (f32, f32) {
+    let pn2 = tissue.load_n2;
+    let phe = tissue.load_he;
+    let p_total = pn2 + phe;
+    let a = ((ZhL16cGf::N2_A[tissue_index] * pn2) + (ZhL16cGf::HE_A[tissue_index] * phe)) / p_total;
+    let b = ((ZhL16cGf::N2_B[tissue_index] * pn2) + (ZhL16cGf::HE_B[tissue_index] * phe)) / p_total;
+    (a, b)
+}
 
-    // Round up to nearest multiple of 3 m
-    ((result_meters + 2.999) / 3.0) as u32 * 3
+/// Lowest ambient pressure a compartment with `p_total` tolerates at gradient factor `gf` (bar)
+pub(crate) fn tolerated_pressure(a: f32, b: f32, p_total: f32, gf: f32) -> f32 {
+    (b * p_total - gf * a * b) / ((1.0 - b) * gf + b)
 }
 
-/// Compute the deepest unmodified ceiling (first stop pressure) across all tissues.
-/// Returns pressure in bar (absolute).
-pub fn first_stop_pressure(tissues: &[Tissue], surface_pressure: f32) -> f32 {
-    let mut max_ceiling_bar = surface_pressure; // at least surface
+/// Gradient factors of one ascent: GF low at the deepest stop, GF high at the surface and
+/// interpolated by ambient pressure in between, as Baker defined them. The deepest stop is taken
+/// when the ascent starts and kept until it ends, so the slope does not move while the tissues
+/// off-gas. Before that every ceiling anchors the slope at the deepest stop of the tissues it is
+/// given.
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GfSlope {
+    pub gf_low: f32,
+    pub gf_high: f32,
+    pub surface_pressure: f32,              // bar
+    pub first_stop_pressure: Option<f32>,   // bar, set for the ascent by `anchor`
+}
+
+impl GfSlope {
+    pub fn new(gf_low: f32, gf_high: f32, surface_pressure: f32) -> Self {
+        GfSlope { gf_low, gf_high, surface_pressure, first_stop_pressure: None }
+    }
 
-    for (i, tissue) in tissues.iter().enumerate() {
-        let pn2 = tissue.load_n2;
-        let phe = tissue.load_he;
-        let p_total = pn2 + phe;
+    /// Deepest GF low ceiling of `compartments`, deepened to a 3 m stop (bar); the surface
+    /// when GF low allows a direct ascent.
+    fn deepest_stop<'a>(&self, compartments: impl Iterator<Item = (usize, &'a Tissue)>) -> f32 {
+        let deepest = compartments
+            .map(|(i, tissue)| ceiling_pressure(self.gf_low, tissue, i))
+            .fold(self.surface_pressure, f32::max);
+        self.surface_pressure + round_to_stop(pressure_to_depth(deepest, self.surface_pressure)) as f32 / 10.0
+    }
 
-        if p_total <= 0.0 {
-            continue;
+    /// Ambient pressure of the deepest stop of `tissues` (bar)
+    pub fn deepest_stop_pressure(&self, tissues: &[Tissue; 16]) -> f32 {
+        self.deepest_stop(tissues.iter().enumerate())
+    }
+
+    /// Fix GF low at the deepest stop of `tissues` for the rest of the ascent. Does nothing when
+    /// the slope is already anchored or GF low allows a direct ascent.
+    pub fn anchor(&mut self, tissues: &[Tissue; 16]) {
+        if self.first_stop_pressure.is_none() {
+            let deepest_stop = self.deepest_stop_pressure(tissues);
+            if deepest_stop > self.surface_pressure {
+                self.first_stop_pressure = Some(deepest_stop);
+            }
         }
+    }
 
-        let an2: f32 = ZhL16cGf::N2_A[i];
-        let bn2: f32 = ZhL16cGf::N2_B[i];
-        let ahe: f32 = ZhL16cGf::HE_A[i];
-        let bhe: f32 = ZhL16cGf::HE_B[i];
+    /// Forget the deepest stop, e.g. when the diver goes back down for another level.
+    pub fn release(&mut self) {
+        self.first_stop_pressure = None;
+    }
 
-        let a = ((an2 * pn2) + (ahe * phe)) / p_total;
-        let b = ((bn2 * pn2) + (bhe * phe)) / p_total;
+    /// Gradient factor at `amb_pressure` with GF low at `first_stop_pressure`
+    pub fn gf_at(&self, amb_pressure: f32, first_stop_pressure: f32) -> f32 {
+        if first_stop_pressure <= self.surface_pressure {
+            return self.gf_high;
+        }
+        interpolate_gf(self.gf_low, self.gf_high, amb_pressure, self.surface_pressure, first_stop_pressure)
+    }
 
-        // raw ceiling without GF
-        let denom = 1.0 - b;
-        if denom.abs() < 1e-10 {
-            continue; // skip invalid
+    /// Lowest ambient pressure one compartment tolerates (bar) with the gradient factor of that
+    /// pressure, and that gradient factor.
+    fn compartment_ceiling(&self, tissue: &Tissue, tissue_index: usize, first_stop_pressure: f32) -> (f32, f32) {
+        let p_total = tissue.load_n2 + tissue.load_he;
+        if p_total <= 0.0 {
+            return (0.0, self.gf_high);
+        }
+        // at or below the deepest stop GF low applies, at or above the surface GF high
+        let low = ceiling_pressure(self.gf_low, tissue, tissue_index);
+        if first_stop_pressure > self.surface_pressure && low >= first_stop_pressure {
+            return (low, self.gf_low);
+        }
+        let high = ceiling_pressure(self.gf_high, tissue, tissue_index);
+        if first_stop_pressure <= self.surface_pressure || high <= self.surface_pressure {
+            return (high, self.gf_high);
         }
 
-        let ceiling_bar = (b * p_total - a * b) / denom;
+        let (a, b) = coefficients(tissue, tissue_index);
+        // in between the GF is gf0 + slope * P; with it the tolerated load
+        // P * ((1 - b) * gf + b) / b + gf * a = p_total is a quadratic in P
+        let slope = -(self.gf_high - self.gf_low) / (first_stop_pressure - self.surface_pressure);
+        let gf0 = self.gf_low - slope * first_stop_pressure;
+        let qa = (1.0 - b) * slope / b;
+        let qb = ((1.0 - b) * gf0 + b) / b + a * slope;
+        let qc = gf0 * a - p_total;
+        // root form that stays accurate as qa goes to 0
+        let ceiling = -2.0 * qc / (qb + sqrtf((qb * qb - 4.0 * qa * qc).max(0.0)));
+        (ceiling, self.gf_at(ceiling, first_stop_pressure))
+    }
 
-        if ceiling_bar > max_ceiling_bar {
-            max_ceiling_bar = ceiling_bar;
+    /// Deepest ceiling across all tissues (bar, absolute and unclamped), the leading compartment
+    /// and the gradient factor applied to it. Without an anchor the slope starts at the deepest
+    /// stop of `tissues`.
+    pub fn max_ceiling_pressure(&self, tissues: &[Tissue; 16]) -> (f32, usize, f32) {
+        let first_stop_pressure = self.first_stop_pressure.unwrap_or_else(|| self.deepest_stop_pressure(tissues));
+        let mut max_ceiling = f32::NEG_INFINITY;
+        let mut tissue_index = 0;
+        let mut gf = self.gf_high;
+        for (i, tissue) in tissues.iter().enumerate() {
+            let (ceiling, tentative_gf) = self.compartment_ceiling(tissue, i, first_stop_pressure);
+            if ceiling > max_ceiling {
+                max_ceiling = ceiling;
+                tissue_index = i;
+                gf = tentative_gf;
+            }
         }
+        (max_ceiling, tissue_index, gf)
     }
 
-    max_ceiling_bar
+    /// `max_ceiling_pressure` as a depth (m, clamped at the surface)
+    pub fn max_ceiling(&self, tissues: &[Tissue; 16]) -> (f32, usize, f32) {
+        let (pressure, tissue_index, gf) = self.max_ceiling_pressure(tissues);
+        (pressure_to_depth(pressure, self.surface_pressure).max(0.0), tissue_index, gf)
+    }
 }
 
+/// Compute the deepest unmodified ceiling (first stop pressure) across all tissues.
+/// Returns pressure in bar (absolute), at least `surface_pressure`.
+pub fn first_stop_pressure(tissues: &[Tissue], surface_pressure: f32) -> f32 {
+    tissues
+        .iter()
+        .enumerate()
+        .map(|(i, tissue)| ceiling_pressure(1.0, tissue, i))
+        .fold(surface_pressure, f32::max)
+}
 
-
+/// Deepest GF ceiling across all tissues rounded up to a 3 m stop and the leading compartment,
+/// (0, 0) without a ceiling. The slope is anchored at the deepest stop of `tissues`.
 #[inline(never)]
 pub fn max_ceiling_with_gf(gf_low: f32, gf_high: f32, tissues: &[Tissue; 16]) -> (u32, usize) {
-    let mut max_ceiling = 0;
-    let mut tissue_index = 0;
-    for i in 0..16 {
-        let tentative_max_ceiling = ceiling_with_gf(gf_low, gf_high, &tissues[i], i, 1.0, true);
-        if tentative_max_ceiling > max_ceiling {
-            max_ceiling = tentative_max_ceiling;
-            tissue_index = i;
-        }
+    let (ceiling, tissue_index, _) = max_ceiling_with_gf_exact(gf_low, gf_high, tissues, 1.0);
+    if ceiling <= 0.0 {
+        return (0, 0);
     }
-    (max_ceiling, tissue_index)
+    (round_to_stop(ceiling), tissue_index)
+}
+
+/// Deepest unrounded GF ceiling across all tissues (m, clamped at the surface), the leading
+/// compartment and the gradient factor applied to it. The leading compartment is reported even
+/// when there is no ceiling, it is the one closest to its limit. The slope is anchored at the
+/// deepest stop of `tissues`; use `GfSlope` to keep it through an ascent.
+#[inline(never)]
+pub fn max_ceiling_with_gf_exact(gf_low: f32, gf_high: f32, tissues: &[Tissue; 16], surface_pressure: f32) -> (f32, usize, f32) {
+    GfSlope::new(gf_low, gf_high, surface_pressure).max_ceiling(tissues)
 }
 
 #[inline(never)]
 pub fn max_ceiling(dive_parameters: DiveParameters, tissues: &[Tissue; 16]) -> (u32, usize) {
-    let mut max_ceiling = 0;
-    let mut tissue_index = 0;
-    for i in 0..16 {
-        let tentative_max_ceiling = ceiling(dive_parameters, tissues[i], i, true);
-        if tentative_max_ceiling > max_ceiling {
-            max_ceiling = tentative_max_ceiling;
-            tissue_index = i;
-        }
-    }
-    (max_ceiling, tissue_index)
+    max_ceiling_with_gf(dive_parameters.gf_low, dive_parameters.gf_high, tissues)
 }
 
-/// Binary search implementation of ceiling calculation
-/// Uses binary search to find the shallowest depth where the tissue is oversaturated
+/// Ceiling of one compartment with GF low (m), whole metres, rounded up to a 3 m stop when
+/// `round`. Takes `binary_ceiling_depth`, the closed form `ceiling_pressure` as a depth.
 #[inline(never)]
 pub fn binary_ceiling(dive_parameters: DiveParameters, tissue: Tissue, tissue_index: usize, round: bool) -> u32 {
     binary_ceiling_with_gf(dive_parameters.gf_low, tissue, tissue_index, round)
 }
 
-/// Binary search implementation of ceiling calculation with custom gradient factor
+/// `binary_ceiling` with a custom gradient factor
 #[inline(never)]
 pub fn binary_ceiling_with_gf(gradient_factor: f32, tissue: Tissue, tissue_index: usize, round: bool) -> u32 {
-    let pn2 = tissue.load_n2;
-    let phe = tissue.load_he;
-    let p_total = pn2 + phe;
-    
-    // Handle edge case where tissue has no inert gas loading
-    if p_total <= 0.0 {
-        return 0;
-    }
-    
-    // Check if we're already oversaturated at surface (1 bar)
-    if !is_oversaturated_at_depth(gradient_factor, tissue, tissue_index, 0.0) {
-        return 0;
-    }
-    
-    // Binary search parameters
-    let mut low_depth = 0.0_f32;
-    let mut high_depth = 100.0_f32; // Start with a reasonable ceiling estimate
-    const PRECISION: f32 = 0.1; // Precision in meters
-    const MAX_ITERATIONS: u32 = 50; // Safety limit
-    let mut iterations = 0;
-    
-    // First, find an upper bound where we're not oversaturated
-    while is_oversaturated_at_depth(gradient_factor, tissue, tissue_index, high_depth) && iterations < MAX_ITERATIONS {
-        high_depth *= 2.0;
-        iterations += 1;
-        if iterations >= MAX_ITERATIONS {
-            break;
-        }
-    }
-    
-    // Reset iteration counter for binary search
-    iterations = 0;
-    
-    // Binary search for the exact ceiling
-    while (high_depth - low_depth) > PRECISION && iterations < MAX_ITERATIONS {
-        let mid_depth = (low_depth + high_depth) / 2.0;
-        
-        if is_oversaturated_at_depth(gradient_factor, tissue, tissue_index, mid_depth) {
-            // Still oversaturated at mid_depth, ceiling is deeper
-            low_depth = mid_depth;
-        } else {
-            // Not oversaturated at mid_depth, ceiling is shallower
-            high_depth = mid_depth;
-        }
-        
-        iterations += 1;
-    }
-    
-    let result_meters = high_depth;
-    
-    // Ensure we don't have negative ceilings
-    if result_meters < 0.0 {
-        return 0;
+    whole_metres(binary_ceiling_depth(gradient_factor, &tissue, tissue_index), round)
+}
+
+/// Ceiling of one compartment with gradient factor `gradient_factor` (m below a 1 bar surface),
+/// the `ceiling_pressure` as a depth; 0 without a ceiling.
+pub fn binary_ceiling_depth(gradient_factor: f32, tissue: &Tissue, tissue_index: usize) -> f32 {
+    pressure_to_depth(ceiling_pressure(gradient_factor, tissue, tissue_index), 1.0).max(0.0)
+}
+
+/// Gradient factors, tissue loads and surface pressure every fallible ceiling checks
+fn check_ceiling_inputs(gf_low: f32, gf_high: f32, tissues: &[Tissue], surface_pressure: f32) -> Result<(), DecoError> {
+    check_gradient_factors(gf_low, gf_high)?;
+    check_tissues(tissues)?;
+    check_input(Input::SurfacePressure, surface_pressure, |pressure| pressure > 0.0)?;
+    Ok(())
+}
+
+/// Gradient factors, surface pressure and anchor of `slope`
+pub(crate) fn check_slope(slope: &GfSlope) -> Result<(), DecoError> {
+    check_gradient_factors(slope.gf_low, slope.gf_high)?;
+    check_input(Input::SurfacePressure, slope.surface_pressure, |pressure| pressure > 0.0)?;
+    if let Some(first_stop_pressure) = slope.first_stop_pressure {
+        check_input(Input::AmbientPressure, first_stop_pressure, |pressure| pressure > 0.0)?;
     }
+    Ok(())
+}
+
+/// `ceiling` that returns an error instead of panicking or producing NaN on bad input.
+pub fn try_ceiling(dive_parameters: DiveParameters, tissue: Tissue, tissue_index: usize, round: bool) -> Result<u32, DecoError> {
+    try_ceiling_with_gf(dive_parameters.gf_low, dive_parameters.gf_high, &tissue, tissue_index, 1.0, round)
+}
 
-    // Round to multiples of 3 if requested
-    if !round {
-        return result_meters as u32;
+/// `ceiling_with_gf` that returns an error instead of panicking or producing NaN on bad input.
+pub fn try_ceiling_with_gf(gf_low: f32, gf_high: f32, tissue: &Tissue, tissue_index: usize, surface_pressure: f32, round: bool) -> Result<u32, DecoError> {
+    let (ceiling, _) = try_ceiling_with_gf_exact(gf_low, gf_high, tissue, tissue_index, surface_pressure)?;
+    Ok(whole_metres(ceiling, round))
+}
+
+/// `ceiling_with_gf_exact` that returns an error instead of panicking or producing NaN on bad input.
+pub fn try_ceiling_with_gf_exact(gf_low: f32, gf_high: f32, tissue: &Tissue, tissue_index: usize, surface_pressure: f32) -> Result<(f32, f32), DecoError> {
+    check_tissue_index(tissue_index)?;
+    check_ceiling_inputs(gf_low, gf_high, &[*tissue], surface_pressure)?;
+    match ceiling_with_gf_exact(gf_low, gf_high, tissue, tissue_index, surface_pressure) {
+        (ceiling, gf) if ceiling.is_finite() => Ok((ceiling, gf)),
+        _ => Err(DecoError::InvalidSolution),
     }
-    
-    ((result_meters + 2.999) / 3.0) as u32 * 3
 }
 
-/// Helper function to check if tissue is oversaturated at a given depth
-fn is_oversaturated_at_depth(gradient_factor: f32, tissue: Tissue, tissue_index: usize, depth_meters: f32) -> bool {
-    let pn2 = tissue.load_n2;
-    let phe = tissue.load_he;
-    let an2: f32 = ZhL16cGf::N2_A[tissue_index];
-    let bn2: f32 = ZhL16cGf::N2_B[tissue_index];
-    let ahe: f32 = ZhL16cGf::HE_A[tissue_index];
-    let bhe: f32 = ZhL16cGf::HE_B[tissue_index];
+/// `ceiling_pressure` that returns an error instead of panicking or producing NaN on bad input.
+pub fn try_ceiling_pressure(gf: f32, tissue: &Tissue, tissue_index: usize) -> Result<f32, DecoError> {
+    check_tissue_index(tissue_index)?;
+    check_ceiling_inputs(gf, gf, &[*tissue], 1.0)?;
+    Ok(ceiling_pressure(gf, tissue, tissue_index))
+}
 
-    let p_total = pn2 + phe;
-    
-    // Handle edge case where tissue has no inert gas loading
-    if p_total <= 0.0 {
-        return false;
+/// `max_ceiling_pressure` that returns an error instead of producing NaN on bad input.
+pub fn try_max_ceiling_pressure(gf: f32, tissues: &[Tissue; 16]) -> Result<(f32, usize), DecoError> {
+    check_ceiling_inputs(gf, gf, tissues, 1.0)?;
+    Ok(max_ceiling_pressure(gf, tissues))
+}
+
+/// `first_stop_pressure` for at most 16 tissues, returning an error instead of panicking or
+/// producing NaN on bad input.
+pub fn try_first_stop_pressure(tissues: &[Tissue], surface_pressure: f32) -> Result<f32, DecoError> {
+    if tissues.len() > 16 {
+        return Err(DecoError::InvalidTissueIndex(16));
     }
-    
-    let a = ((an2 * pn2) + (ahe * phe)) / p_total;
-    let b = ((bn2 * pn2) + (bhe * phe)) / p_total;
-
-    // Calculate ambient pressure at the given depth
-    let amb_pressure = depth_meters / 10.0 + 1.0;
-    
-    // Use the exact same Bühlmann equation as the analytical ceiling calculation:
-    // result_bar = (b * p_total - gradient_factor * a * b) / denominator
-    // where denominator = (1.0 - b) * gradient_factor + b
-    // Tissue is oversaturated if amb_pressure < result_bar
-    
-    let denominator = (1.0 - b) * gradient_factor + b;
-    
-    // Safety check for very small denominators
-    if fabsf(denominator) < 1e-10 {
-        return false;
+    check_tissues(tissues)?;
+    check_input(Input::SurfacePressure, surface_pressure, |pressure| pressure > 0.0)?;
+    Ok(first_stop_pressure(tissues, surface_pressure))
+}
+
+/// `max_ceiling_with_gf` that returns an error instead of producing NaN on bad input.
+pub fn try_max_ceiling_with_gf(gf_low: f32, gf_high: f32, tissues: &[Tissue; 16]) -> Result<(u32, usize), DecoError> {
+    check_ceiling_inputs(gf_low, gf_high, tissues, 1.0)?;
+    Ok(max_ceiling_with_gf(gf_low, gf_high, tissues))
+}
+
+/// `max_ceiling_with_gf_exact` that returns an error instead of producing NaN on bad input.
+pub fn try_max_ceiling_with_gf_exact(gf_low: f32, gf_high: f32, tissues: &[Tissue; 16], surface_pressure: f32) -> Result<(f32, usize, f32), DecoError> {
+    check_ceiling_inputs(gf_low, gf_high, tissues, surface_pressure)?;
+    match max_ceiling_with_gf_exact(gf_low, gf_high, tissues, surface_pressure) {
+        (ceiling, compartment, gf) if ceiling.is_finite() => Ok((ceiling, compartment, gf)),
+        _ => Err(DecoError::InvalidSolution),
     }
-    
-    let required_pressure_bar = (b * p_total - gradient_factor * a * b) / denominator;
-    
-    // Tissue is oversaturated if current ambient pressure is less than required
-    amb_pressure < required_pressure_bar
-}
\ No newline at end of file
+}
+
+/// `max_ceiling` that returns an error instead of producing NaN on bad input.
+pub fn try_max_ceiling(dive_parameters: DiveParameters, tissues: &[Tissue; 16]) -> Result<(u32, usize), DecoError> {
+    check_ceiling_inputs(dive_parameters.gf_low, dive_parameters.gf_high, tissues, 1.0)?;
+    Ok(max_ceiling(dive_parameters, tissues))
+}
+
+/// `binary_ceiling` that returns an error instead of panicking or producing NaN on bad input.
+pub fn try_binary_ceiling(dive_parameters: DiveParameters, tissue: Tissue, tissue_index: usize, round: bool) -> Result<u32, DecoError> {
+    try_binary_ceiling_with_gf(dive_parameters.gf_low, tissue, tissue_index, round)
+}
+
+/// `binary_ceiling_with_gf` that returns an error instead of panicking or producing NaN on bad input.
+pub fn try_binary_ceiling_with_gf(gradient_factor: f32, tissue: Tissue, tissue_index: usize, round: bool) -> Result<u32, DecoError> {
+    check_tissue_index(tissue_index)?;
+    check_ceiling_inputs(gradient_factor, gradient_factor, &[tissue], 1.0)?;
+    Ok(binary_ceiling_with_gf(gradient_factor, tissue, tissue_index, round))
+}
@@ -0,0 +1,64 @@
+//! Named conservatism levels for divers who do not think in gradient factors.
+//!
+//! Each level maps to the knobs of a decompression model; for the Bühlmann ZHL-16C model of this
+//! crate that is a gradient factor pair. `Custom` keeps the pair the diver entered. The presets
+//! follow the levels common on recreational and technical dive computers.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use defmt::Format;
+
+/// GF low and high of `Conservatism::Low`
+pub const LOW_GRADIENT_FACTORS: (f32, f32) = (0.45, 0.95);
+/// GF low and high of `Conservatism::Medium`
+pub const MEDIUM_GRADIENT_FACTORS: (f32, f32) = (0.40, 0.85);
+/// GF low and high of `Conservatism::High`
+pub const HIGH_GRADIENT_FACTORS: (f32, f32) = (0.35, 0.75);
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Conservatism {
+    /// Shortest decompression
+    Low,
+    Medium,
+    /// Longest decompression
+    High,
+    Custom { gf_low: f32, gf_high: f32 },
+}
+
+impl Conservatism {
+    /// The preset with exactly these gradient factors, `Custom` when none has them.
+    pub fn from_gradient_factors(gf_low: f32, gf_high: f32) -> Self {
+        [Conservatism::Low, Conservatism::Medium, Conservatism::High]
+            .into_iter()
+            .find(|preset| preset.gradient_factors() == (gf_low, gf_high))
+            .unwrap_or(Conservatism::Custom { gf_low, gf_high })
+    }
+
+    /// Parse `low`, `medium` or `high`, case insensitive.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Conservatism::Low, Conservatism::Medium, Conservatism::High]
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(name))
+    }
+
+    /// GF low and high of the Bühlmann model
+    pub fn gradient_factors(self) -> (f32, f32) {
+        match self {
+            Conservatism::Low => LOW_GRADIENT_FACTORS,
+            Conservatism::Medium => MEDIUM_GRADIENT_FACTORS,
+            Conservatism::High => HIGH_GRADIENT_FACTORS,
+            Conservatism::Custom { gf_low, gf_high } => (gf_low, gf_high),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Conservatism::Low => "low",
+            Conservatism::Medium => "medium",
+            Conservatism::High => "high",
+            Conservatism::Custom { .. } => "custom",
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Contingency schedules carried on slates next to the planned dive.
+//!
+//! A lost gas is replaced, segment by segment, with the richest gas still carried that is
+//! breathable at the segment depth; a lost cylinder only changes the plan when no other cylinder
+//! carries its gas. Stops, levels reached by ascending and followed by a shallower one, are held
+//! in whole minutes until the ceiling lets the diver move on, so the scripted deco stops grow with
+//! the slower off-gassing. Bottom time is never held, it only loads the tissues further.
+//!
+//! Deeper/longer variants move every segment at the maximum depth deeper and stay longer on the
+//! last of them, the usual +3 m / +3 min tables; `slate` prints them one line per variant.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use core::fmt::Write;
+
+use defmt::Format;
+use libm::ceilf;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::gas::{gas_usage, GasUsage};
+use crate::plan::{DivePlan, SegmentTime};
+use crate::simulate::{RuntimeEntry, RuntimeKind, SimulationOutputs, Simulator};
+use crate::tissue::Tissue;
+use crate::{DecoError, DiveParameters};
+
+/// Longest a segment is held waiting for the ceiling (s)
+const MAX_EXTENSION: f32 = 120.0 * 60.0;
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Loss {
+    /// Index into DivePlan::gases, every cylinder of the gas is lost
+    Gas(usize),
+    /// Index into DivePlan::cylinders
+    Cylinder(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Contingency {
+    pub loss: Loss,
+    pub plan: DivePlan,             // segments as durations, lost cylinders removed
+    pub runtime: Vec<RuntimeEntry>,
+    pub total_runtime: f32,         // s
+    pub extra_time: f32,            // s longer than the planned dive
+    pub usage: GasUsage,
+    pub enough: bool,               // no cylinder runs out and every gas breathed is carried
+}
+
+/// Contingency schedules for every deco gas (any gas of a segment or a cylinder but the one of the
+/// first segment) and for every cylinder of `plan`, starting from `tissues`. With `deco_ppo2` set
+/// the final ascent switches to the gases still carried, so computed stops grow with the loss too.
+///
+/// Replacement gases keep their ppO2 at or below `max_ppo2` at the segment depth; when none does,
+/// the segment stays on the gas of the segment before. Plans without cylinders are never `enough`.
+pub fn lost_gas_plans(plan: &DivePlan, params: &DiveParameters, tissues: &[Tissue; 16], temperature: f32, interval_in_seconds: f32, max_ppo2: f32) -> Result<Vec<Contingency>, DecoError> {
+    plan.validate()?;
+    let planned = plan.simulate(params, &mut { *tissues }, temperature, interval_in_seconds)?;
+    let planned_runtime = planned.times.last().copied().unwrap_or(0.0);
+
+    let bottom_gas = plan.segments[0].gas;
+    let mut losses: Vec<Loss> = Vec::new();
+    let gases = plan.segments.iter().map(|segment| segment.gas).chain(plan.cylinders.iter().map(|cylinder| cylinder.gas));
+    for gas in gases {
+        if gas != bottom_gas && !losses.contains(&Loss::Gas(gas)) {
+            losses.push(Loss::Gas(gas));
+        }
+    }
+    losses.extend((0..plan.cylinders.len()).map(Loss::Cylinder));
+
+    let mut contingencies = Vec::new();
+    for loss in losses {
+        let lost = without(plan, loss, max_ppo2);
+        let lost = hold_for_ceiling(&lost, params, tissues, temperature, interval_in_seconds);
+        let outputs = lost.simulate(params, &mut { *tissues }, temperature, interval_in_seconds)?;
+        contingencies.push(contingency(loss, lost, params, &outputs, planned_runtime));
+    }
+    Ok(contingencies)
+}
+
+fn contingency(loss: Loss, plan: DivePlan, params: &DiveParameters, outputs: &SimulationOutputs, planned_runtime: f32) -> Contingency {
+    let usage = gas_usage(&plan, params, outputs);
+    let total_runtime = outputs.times.last().copied().unwrap_or(0.0);
+    let enough = is_enough(&plan, &usage);
+    Contingency {
+        loss,
+        runtime: outputs.runtime_table(),
+        total_runtime,
+        extra_time: total_runtime - planned_runtime,
+        usage,
+        enough,
+        plan,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Variant {
+    pub deeper: f32,                // m added to the maximum depth
+    pub longer: f32,                // s added to the time at the maximum depth
+    pub plan: DivePlan,             // segments as durations
+    pub runtime: Vec<RuntimeEntry>,
+    pub total_runtime: f32,         // s
+    pub time_to_surface: f32,       // s from leaving the maximum depth
+    pub usage: GasUsage,
+    pub enough: bool,               // no cylinder runs out and every gas breathed is carried
+}
+
+impl Variant {
+    /// Levels and stops after the maximum depth as (depth, duration)
+    pub fn stops(&self) -> Vec<(f32, f32)> {
+        let leave_bottom = self.total_runtime - self.time_to_surface;
+        self.runtime.iter()
+            .filter(|entry| entry.runtime > leave_bottom + 0.5 && entry.kind != RuntimeKind::Surface)
+            .map(|entry| (entry.depth, entry.duration))
+            .collect()
+    }
+}
+
+/// Every combination of `deeper` (m) and `longer` (s) applied to `plan`, in rows of `longer` per
+/// `deeper` value. Include 0 in both to get the planned dive itself.
+pub fn deeper_longer_plans(plan: &DivePlan, params: &DiveParameters, tissues: &[Tissue; 16], temperature: f32, interval_in_seconds: f32, deeper: &[f32], longer: &[f32]) -> Result<Vec<Variant>, DecoError> {
+    plan.validate()?;
+    let max_depth = plan.segments.iter().fold(0.0, |a, segment| f32::max(a, segment.depth));
+    let last_bottom = plan.segments.iter().rposition(|segment| segment.depth == max_depth).unwrap_or(0);
+
+    let held = hold_for_ceiling(plan, params, tissues, temperature, interval_in_seconds);
+    let mut variants = Vec::new();
+    for &extra_depth in deeper {
+        for &extra_time in longer {
+            let mut variant = held.clone();
+            for segment in variant.segments.iter_mut().filter(|segment| segment.depth == max_depth) {
+                segment.depth += extra_depth;
+            }
+            if let SegmentTime::Duration(duration) = held.segments[last_bottom].time {
+                variant.segments[last_bottom].time = SegmentTime::Duration(duration + extra_time);
+            }
+            let variant = hold_for_ceiling(&variant, params, tissues, temperature, interval_in_seconds);
+            let outputs = variant.simulate(params, &mut { *tissues }, temperature, interval_in_seconds)?;
+
+            let runtime = outputs.runtime_table();
+            let total_runtime = outputs.times.last().copied().unwrap_or(0.0);
+            let leave_bottom = runtime.iter()
+                .rfind(|entry| entry.kind == RuntimeKind::Level && entry.depth == max_depth + extra_depth)
+                .map_or(0.0, |entry| entry.runtime);
+            let usage = gas_usage(&variant, params, &outputs);
+            variants.push(Variant {
+                deeper: extra_depth,
+                longer: extra_time,
+                enough: is_enough(&variant, &usage),
+                plan: variant,
+                runtime,
+                total_runtime,
+                time_to_surface: total_runtime - leave_bottom,
+                usage,
+            });
+        }
+    }
+    Ok(variants)
+}
+
+/// Slate lines for `variants`: depth and time, TTS, runtime, stops in minutes and gas per cylinder.
+pub fn slate(variants: &[Variant]) -> String {
+    let mut out = String::new();
+    for variant in variants {
+        let depth = variant.plan.segments.iter().fold(0.0, |a, segment| f32::max(a, segment.depth));
+        let time: f32 = variant.plan.segments.iter()
+            .filter(|segment| segment.depth == depth)
+            .map(|segment| match segment.time {
+                SegmentTime::Duration(duration) | SegmentTime::Runtime(duration) => duration,
+            })
+            .sum();
+        let _ = write!(out, "{:>3.0} m {:>3.0} min (+{:.0}/+{:.0})  TTS {:>3.0}  RT {:>3.0} |",
+            depth, time / 60.0, variant.deeper, variant.longer / 60.0, ceilf(variant.time_to_surface / 60.0), ceilf(variant.total_runtime / 60.0));
+        for (depth, duration) in variant.stops() {
+            let _ = write!(out, " {:.0}:{:.0}", depth, ceilf(duration / 60.0));
+        }
+        let _ = write!(out, " |");
+        for cylinder in variant.usage.cylinders.iter() {
+            let _ = write!(out, " {:.0}L", cylinder.litres);
+        }
+        if !variant.enough && !variant.plan.cylinders.is_empty() {
+            let _ = write!(out, " NOT ENOUGH GAS");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn is_enough(plan: &DivePlan, usage: &GasUsage) -> bool {
+    !plan.cylinders.is_empty() && usage.unassigned == 0.0
+        && usage.cylinders.iter().all(|cylinder| cylinder.end_pressure >= 0.0)
+}
+
+/// `plan` without what `loss` takes away, segments moved to the gases still carried.
+fn without(plan: &DivePlan, loss: Loss, max_ppo2: f32) -> DivePlan {
+    let mut lost = plan.clone();
+    lost.cylinders = plan.cylinders.iter()
+        .enumerate()
+        .filter(|(i, cylinder)| match loss {
+            Loss::Gas(gas) => cylinder.gas != gas,
+            Loss::Cylinder(index) => *i != index,
+        })
+        .map(|(_, cylinder)| *cylinder)
+        .collect();
+
+    let carried: Vec<bool> = (0..plan.gases.len())
+        .map(|gas| if plan.cylinders.is_empty() {
+            loss != Loss::Gas(gas)
+        } else {
+            lost.cylinders.iter().any(|cylinder| cylinder.gas == gas)
+        })
+        .collect();
+    let mut previous = None;
+    for segment in lost.segments.iter_mut() {
+        if !carried[segment.gas] {
+            let ambient = plan.surface_pressure + segment.depth / plan.water.metres_per_bar();
+            let richest = (0..plan.gases.len())
+                .filter(|gas| carried[*gas] && ambient * plan.gases[*gas].o2() <= max_ppo2)
+                .max_by(|a, b| plan.gases[*a].o2().total_cmp(&plan.gases[*b].o2()));
+            segment.gas = richest.or(previous).unwrap_or(segment.gas);
+        }
+        previous = Some(segment.gas);
+    }
+    lost
+}
+
+/// `plan` with every segment as a duration, each stop held until the ceiling allows the next
+/// shallower level.
+fn hold_for_ceiling(plan: &DivePlan, params: &DiveParameters, tissues: &[Tissue; 16], temperature: f32, interval_in_seconds: f32) -> DivePlan {
+    let mut held = plan.clone();
+    let mut simulator: Option<Simulator> = None;
+    for i in 0..plan.segments.len() {
+        let segment = plan.segments[i];
+        let gas = plan.gases[segment.gas];
+        let (depth, time) = simulator.as_ref().map_or((0.0, 0.0), |simulator| (simulator.depth(), simulator.time()));
+        let mut duration = segment.duration_from(depth, time, params);
+
+        let simulator = match simulator.as_mut() {
+            Some(simulator) => {
+                simulator.continue_to(segment.depth, duration, gas, segment.rate, false);
+                simulator
+            }
+            None => {
+                let mut first = Simulator::new(*params, *tissues, plan.surface_pressure, 0.0, segment.depth, temperature, interval_in_seconds, duration, false);
+                first.set_environment(plan.surface_pressure, plan.water, gas, segment.rate);
+                simulator.insert(first)
+            }
+        };
+        simulator.run_into(&mut ());
+
+        // only stops wait for the ceiling, staying at the bottom would keep on-gassing
+        let is_stop = i > 0 && segment.depth < plan.segments[i - 1].depth;
+        if let Some(next) = plan.segments.get(i + 1).filter(|next| is_stop && next.depth < segment.depth) {
+            let mut extension = 0.0;
+            while simulator.sample().ceiling as f32 > next.depth && extension < MAX_EXTENSION {
+                simulator.continue_to(segment.depth, 60.0, gas, None, false);
+                simulator.run_into(&mut ());
+                extension += 60.0;
+            }
+            duration += extension;
+        }
+        held.segments[i].time = SegmentTime::Duration(duration);
+    }
+    held
+}
@@ -0,0 +1,84 @@
+//! Catalogue of common scuba cylinders.
+//!
+//! Metric cylinders are sold by water volume and working pressure, imperial ones by the free gas
+//! they hold at their working pressure (rated capacity, cubic feet). Both are listed with the water
+//! volume so the real-gas contents can be worked out for any mix.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use defmt::Format;
+
+use crate::gas::Cylinder;
+use crate::Gas;
+
+/// Litres in a cubic foot
+pub const CUBIC_FOOT: f32 = 28.316_846;
+
+#[derive(Debug, Format, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Material {
+    Steel,
+    Aluminium,
+    Composite,
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+pub struct CylinderSpec {
+    pub name: &'static str,
+    pub volume: f32,                    // L water volume, both cylinders for doubles
+    pub working_pressure: f32,          // bar
+    pub material: Material,
+    pub rated_capacity: Option<f32>,    // cuft of air at the working pressure, imperial cylinders only
+}
+
+impl CylinderSpec {
+    /// A cylinder of this kind filled to `start_pressure` with the plan gas `gas`.
+    pub fn cylinder(&self, start_pressure: f32, gas: usize) -> Cylinder {
+        Cylinder::new(self.volume, self.working_pressure, start_pressure, gas)
+    }
+
+    /// Real-gas surface volume of `gas` held at the working pressure (L)
+    pub fn capacity(&self, gas: &Gas) -> f32 {
+        self.cylinder(self.working_pressure, 0).gas_volume(self.working_pressure, gas)
+    }
+}
+
+const fn spec(name: &'static str, volume: f32, working_pressure: f32, material: Material, rated_capacity: Option<f32>) -> CylinderSpec {
+    CylinderSpec { name, volume, working_pressure, material, rated_capacity }
+}
+
+pub const CATALOGUE: [CylinderSpec; 18] = [
+    // imperial aluminium, 3000 psi
+    spec("AL30", 4.3, 207.0, Material::Aluminium, Some(30.0)),
+    spec("AL40", 5.7, 207.0, Material::Aluminium, Some(40.0)),
+    spec("AL63", 8.7, 207.0, Material::Aluminium, Some(63.0)),
+    spec("AL80", 11.1, 207.0, Material::Aluminium, Some(77.4)),
+    // imperial steel, low pressure 2640 psi and high pressure 3442 psi
+    spec("LP85", 13.3, 182.0, Material::Steel, Some(85.0)),
+    spec("HP80", 10.1, 237.0, Material::Steel, Some(80.0)),
+    spec("HP100", 12.9, 237.0, Material::Steel, Some(100.0)),
+    spec("HP120", 15.3, 237.0, Material::Steel, Some(120.0)),
+    // metric steel
+    spec("S7 232", 7.0, 232.0, Material::Steel, None),
+    spec("S8 200", 8.0, 200.0, Material::Steel, None),
+    spec("S10 232", 10.0, 232.0, Material::Steel, None),
+    spec("S12 232", 12.0, 232.0, Material::Steel, None),
+    spec("S12 300", 12.0, 300.0, Material::Steel, None),
+    spec("S15 232", 15.0, 232.0, Material::Steel, None),
+    spec("S18 232", 18.0, 232.0, Material::Steel, None),
+    spec("D7 232", 14.0, 232.0, Material::Steel, None),
+    spec("D12 232", 24.0, 232.0, Material::Steel, None),
+    // composite
+    spec("C6.8 300", 6.8, 300.0, Material::Composite, None),
+];
+
+/// Catalogue entry called `name`, ignoring case.
+pub fn find(name: &str) -> Option<&'static CylinderSpec> {
+    CATALOGUE.iter().find(|spec| spec.name.eq_ignore_ascii_case(name))
+}
+
+/// Catalogue entry with this water volume and working pressure.
+pub fn find_by_size(volume: f32, working_pressure: f32) -> Option<&'static CylinderSpec> {
+    CATALOGUE.iter().find(|spec| spec.volume == volume && spec.working_pressure == working_pressure)
+}
@@ -0,0 +1,447 @@
+//! Open-circuit gas planning: how much gas a plan breathes and which cylinder it comes from.
+//!
+//! Consumption is the surface rate (SAC, L/min at 1 bar) times the ambient pressure. The bottom
+//! SAC applies until the last level is left, the deco SAC to the ascent and the stops.
+//!
+//! A plan may carry several cylinders of one gas; their roles decide which is breathed (stages
+//! first, bailout only once the rest are empty) and each one has a reserve it should keep.
+//!
+//! Cylinder contents are real-gas volumes: at 232-300 bar the compressibility of air, nitrox,
+//! trimix and helium makes a cylinder hold 5-10% less than volume x pressure.
+//!
+//! Minimum gas (rock bottom) is the reserve two divers sharing one supply need to get from the
+//! worst point of the dive to the surface: a problem-solving time at depth, then the ascent with
+//! every stop the tissues require at that moment, all at a stressed SAC.
+//!
+//! Turn pressures follow the penetration gas rules: only a fraction of the supply may be used on
+//! the way in. With mismatched buddy cylinders the fraction is taken of the smallest supply in
+//! the team, so the diver with more gas still keeps enough to get the other one out.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use defmt::Format;
+
+#[cfg(feature = "alloc")]
+use crate::plan::DivePlan;
+#[cfg(feature = "alloc")]
+use crate::simulate::{RuntimeEntry, RuntimeKind, SimulationOutputs, SimulationRecord, Simulator};
+#[cfg(feature = "alloc")]
+use crate::DiveParameters;
+use crate::Gas;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// Reserve a cylinder is planned to keep unless set otherwise (bar)
+pub const DEFAULT_RESERVE: f32 = 50.0;
+
+/// What a cylinder is carried for, which decides the order cylinders of one gas are drawn in.
+#[derive(Debug, Format, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CylinderRole {
+    /// Back-mounted or sidemount main supply
+    #[default]
+    BackGas,
+    /// Bottom-gas stage, breathed first so it can be dropped
+    Stage,
+    /// Decompression gas
+    Deco,
+    /// Only breathed once every other cylinder of the gas is empty
+    Bailout,
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cylinder {
+    pub volume: f32,            // L water volume
+    pub working_pressure: f32,  // bar
+    pub start_pressure: f32,    // bar
+    pub gas: usize,             // index into DivePlan::gases
+    pub role: CylinderRole,
+    pub reserve: f32,           // bar
+}
+
+impl Cylinder {
+    pub fn new(volume: f32, working_pressure: f32, start_pressure: f32, gas: usize) -> Self {
+        Cylinder {
+            volume,
+            working_pressure,
+            start_pressure,
+            gas,
+            role: CylinderRole::BackGas,
+            reserve: DEFAULT_RESERVE,
+        }
+    }
+
+    /// Surface volume of `gas` held at `pressure` (L)
+    pub fn gas_volume(&self, pressure: f32, gas: &Gas) -> f32 {
+        self.volume * pressure / compressibility(gas, pressure)
+    }
+
+    /// Pressure at which the cylinder holds `litres` of surface `gas` (bar)
+    pub fn pressure_for(&self, litres: f32, gas: &Gas) -> f32 {
+        if self.volume <= 0.0 {
+            return 0.0;
+        }
+        // Z changes slowly with pressure, a few fixed-point steps converge well below 0.01 bar
+        let mut pressure = litres / self.volume;
+        for _ in 0..8 {
+            pressure = litres * compressibility(gas, pressure) / self.volume;
+        }
+        pressure
+    }
+
+    /// Pressure drop from the start pressure for breathing `litres` of surface `gas` (bar).
+    /// Past empty the drop keeps growing at one bar per water volume litre.
+    pub fn pressure_drop(&self, litres: f32, gas: &Gas) -> f32 {
+        if self.volume <= 0.0 {
+            return 0.0;
+        }
+        let left = self.gas_volume(self.start_pressure, gas) - litres;
+        if left >= 0.0 {
+            self.start_pressure - self.pressure_for(left, gas)
+        } else {
+            self.start_pressure - left / self.volume
+        }
+    }
+}
+
+/// Compressibility factor Z of `gas` at `pressure` bar and room temperature.
+///
+/// Virial fit per component (O2, N2, He) mixed by fraction, valid up to 500 bar; Z is 1 at the
+/// surface and the ideal gas law holds where Z is 1.
+pub fn compressibility(gas: &Gas, pressure: f32) -> f32 {
+    const O2: [f32; 3] = [-7.180_921e-4, 2.818_525_7e-6, -1.502_906_2e-9];
+    const N2: [f32; 3] = [-2.192_603_5e-4, 2.928_448_4e-6, -2.076_134_8e-9];
+    const HE: [f32; 3] = [4.873_200_3e-4, -8.836_329e-8, 5.333_045_4e-11];
+
+    let p = pressure.clamp(0.0, 500.0);
+    let virial = |c: [f32; 3]| p * (c[0] + p * (c[1] + p * c[2]));
+    1.0 + gas.o2() * virial(O2) + gas.n2 * virial(N2) + gas.he * virial(HE)
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MinimumGasSettings {
+    pub stressed_sac_rate: f32,     // L/min per diver
+    pub divers: u32,                // divers breathing from the supply
+    pub problem_solving_time: f32,  // s at depth before the ascent starts
+    pub max_ppo2: f32,              // bar, out-of-gas points are only taken within the MOD of the gas
+}
+
+impl Default for MinimumGasSettings {
+    fn default() -> Self {
+        MinimumGasSettings {
+            stressed_sac_rate: 30.0,
+            divers: 2,
+            problem_solving_time: 60.0,
+            max_ppo2: 1.6,
+        }
+    }
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum GasRule {
+    Thirds,
+    Halves,
+    /// Fraction of the supply usable before turning, between 0 and 1
+    Fraction(f32),
+}
+
+impl GasRule {
+    /// Part of the supply that may be used before turning the dive
+    pub fn usable_fraction(self) -> f32 {
+        match self {
+            GasRule::Thirds => 1.0 / 3.0,
+            GasRule::Halves => 0.5,
+            GasRule::Fraction(fraction) => fraction.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// Pressure at which a diver breathing `gas` from `cylinder` turns the dive under `rule` (bar).
+///
+/// `buddies` are the team's cylinders carrying the same gas; the usable volume is the rule's
+/// fraction of the smallest supply among them and `cylinder`.
+pub fn turn_pressure(cylinder: &Cylinder, gas: &Gas, rule: GasRule, buddies: &[Cylinder]) -> f32 {
+    let supply = buddies.iter()
+        .map(|buddy| buddy.gas_volume(buddy.start_pressure, gas))
+        .fold(cylinder.gas_volume(cylinder.start_pressure, gas), f32::min);
+    cylinder.start_pressure - cylinder.pressure_drop(supply * rule.usable_fraction(), gas)
+}
+
+/// Surface litres breathed in `minutes` at `pressure` bar ambient with a SAC of `sac_rate` L/min
+pub fn gas_used(sac_rate: f32, pressure: f32, minutes: f32) -> f32 {
+    sac_rate * pressure * minutes
+}
+
+/// SAC rate (L/min) that explains `litres` breathed over a profile, given as sample times (s) and
+/// ambient pressures (bar). None if the profile has no duration.
+pub fn sac_rate_from_usage(litres: f32, times: &[f32], pressures: &[f32]) -> Option<f32> {
+    let pressure_minutes: f32 = times.windows(2)
+        .zip(pressures.windows(2))
+        .map(|(time, pressure)| gas_used(1.0, (pressure[0] + pressure[1]) / 2.0, (time[1] - time[0]) / 60.0))
+        .sum();
+    if pressure_minutes > 0.0 { Some(litres / pressure_minutes) } else { None }
+}
+
+/// Gas breathed between the previous runtime entry and the end of `entry`, travel included.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SegmentGas {
+    pub entry: RuntimeEntry,
+    pub cylinder: Option<usize>,  // index into DivePlan::cylinders that supplied most of the gas
+    pub litres: f32,              // L at the surface
+    pub bar: f32,                 // pressure drop in that cylinder
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Format, Copy, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CylinderGas {
+    pub litres: f32,                    // L at the surface
+    pub bar: f32,                       // pressure drop
+    pub end_pressure: f32,              // bar, negative if the cylinder runs out
+    pub below_reserve: Option<f32>,     // s when the pressure first drops below the reserve
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GasUsage {
+    pub segments: Vec<SegmentGas>,
+    pub cylinders: Vec<CylinderGas>,    // same order as DivePlan::cylinders
+    pub unassigned: f32,                // L breathed from gases without a cylinder
+    pub remaining: Vec<Vec<f32>>,       // bar per cylinder at every sample
+    pub sources: Vec<Option<usize>>,    // cylinder breathed up to every sample, None for the first
+}
+
+#[cfg(feature = "alloc")]
+impl GasUsage {
+    pub fn total_litres(&self) -> f32 {
+        self.segments.iter().map(|segment| segment.litres).sum()
+    }
+}
+
+/// Cylinder `gas` is drawn from when the cylinders are at `pressures`.
+///
+/// Empty cylinders come last, then bailout cylinders, then cylinders at their reserve; otherwise
+/// stages are breathed before back gas and deco cylinders, and among equals the fullest one,
+/// which keeps doubles and sidemount cylinders balanced.
+#[cfg(feature = "alloc")]
+fn draw_from(plan: &DivePlan, gas: &Gas, pressures: &[f32]) -> Option<usize> {
+    let order = |role: CylinderRole| match role {
+        CylinderRole::Stage => 0,
+        CylinderRole::BackGas => 1,
+        CylinderRole::Deco => 2,
+        CylinderRole::Bailout => 3,
+    };
+    plan.cylinders.iter()
+        .enumerate()
+        .filter(|(_, cylinder)| plan.gases.get(cylinder.gas) == Some(gas))
+        .min_by(|(a, first), (b, second)| {
+            let key = |i: usize, cylinder: &Cylinder| (pressures[i] <= 0.0, cylinder.role == CylinderRole::Bailout, pressures[i] <= cylinder.reserve, order(cylinder.role));
+            key(*a, first).cmp(&key(*b, second)).then(pressures[*b].total_cmp(&pressures[*a]))
+        })
+        .map(|(i, _)| i)
+}
+
+/// Works out the gas a simulated plan breathes, per runtime table entry and per cylinder.
+///
+/// `outputs` must come from `plan`. When several cylinders carry the breathed gas, the one to
+/// draw from is picked again at every sample, see [`CylinderRole`].
+#[cfg(feature = "alloc")]
+pub fn gas_usage(plan: &DivePlan, params: &DiveParameters, outputs: &SimulationOutputs) -> GasUsage {
+    let runtime = outputs.runtime_table();
+    let ascent_start = runtime.iter()
+        .rfind(|entry| entry.kind == RuntimeKind::Level)
+        .map_or(0.0, |entry| entry.runtime);
+
+    let mut usage = GasUsage {
+        segments: runtime.iter()
+            .map(|entry| SegmentGas { entry: *entry, cylinder: None, litres: 0.0, bar: 0.0 })
+            .collect(),
+        cylinders: plan.cylinders.iter()
+            .map(|cylinder| CylinderGas { end_pressure: cylinder.start_pressure, ..CylinderGas::default() })
+            .collect(),
+        unassigned: 0.0,
+        remaining: plan.cylinders.iter()
+            .map(|cylinder| Vec::from([cylinder.start_pressure]))
+            .collect(),
+        sources: Vec::from([None]),
+    };
+    // litres and bar each segment takes from each cylinder
+    let mut drawn = Vec::from_iter(runtime.iter().map(|_| Vec::from_iter(plan.cylinders.iter().map(|_| (0.0, 0.0)))));
+    let mut pressures: Vec<f32> = plan.cylinders.iter().map(|cylinder| cylinder.start_pressure).collect();
+
+    let mut segment = 0;
+    for i in 1..outputs.times.len() {
+        let (start, end) = (outputs.times[i - 1], outputs.times[i]);
+        let (start_pressure, end_pressure) = (outputs.pressures[i - 1], outputs.pressures[i]);
+        // gas switches take effect at the sample after the switch
+        let cylinder = draw_from(plan, &outputs.gases[i], &pressures);
+        usage.sources.push(cylinder);
+
+        // samples do not line up with the runtime table, split the interval at entry boundaries
+        let (mut from, mut from_pressure) = (start, start_pressure);
+        while from < end {
+            while segment + 1 < usage.segments.len() && usage.segments[segment].entry.runtime <= from {
+                segment += 1;
+            }
+            let boundary = usage.segments.get(segment).map_or(end, |entry| entry.entry.runtime);
+            let to = if boundary > from && boundary < end { boundary } else { end };
+            let to_pressure = start_pressure + (end_pressure - start_pressure) * (to - start) / (end - start);
+
+            let sac_rate = if from >= ascent_start { params.deco_sac_rate } else { params.sac_rate };
+            let litres = gas_used(sac_rate, (from_pressure + to_pressure) / 2.0, (to - from) / 60.0);
+            if let Some(entry) = usage.segments.get_mut(segment) {
+                entry.litres += litres;
+            }
+            match cylinder {
+                Some(index) => {
+                    // real-gas pressure drops depend on the fill, so segments add up the change in pressure
+                    let (used, gas) = (&mut usage.cylinders[index], &plan.gases[plan.cylinders[index].gas]);
+                    let before = plan.cylinders[index].pressure_drop(used.litres, gas);
+                    used.litres += litres;
+                    let bar = plan.cylinders[index].pressure_drop(used.litres, gas) - before;
+                    if let Some(drawn) = drawn.get_mut(segment) {
+                        drawn[index].0 += litres;
+                        drawn[index].1 += bar;
+                    }
+                }
+                None => usage.unassigned += litres,
+            }
+            (from, from_pressure) = (to, to_pressure);
+        }
+
+        for (j, cylinder) in plan.cylinders.iter().enumerate() {
+            let used = &mut usage.cylinders[j];
+            used.bar = cylinder.pressure_drop(used.litres, &plan.gases[cylinder.gas]);
+            used.end_pressure = cylinder.start_pressure - used.bar;
+            if used.below_reserve.is_none() && used.end_pressure < cylinder.reserve {
+                used.below_reserve = Some(end);
+            }
+            pressures[j] = used.end_pressure;
+            usage.remaining[j].push(used.end_pressure);
+        }
+    }
+
+    for (entry, drawn) in usage.segments.iter_mut().zip(drawn.iter()) {
+        let most = drawn.iter().enumerate().filter(|(_, (litres, _))| *litres > 0.0).max_by(|a, b| a.1.0.total_cmp(&b.1.0));
+        if let Some((index, (_, bar))) = most {
+            entry.cylinder = Some(index);
+            entry.bar = *bar;
+        }
+    }
+    usage
+}
+
+/// Rock bottom of one cylinder, taken at the point of the dive where it is hardest to meet.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Format, Copy, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MinimumGas {
+    pub depth: f32,             // m of the worst point
+    pub time: f32,              // s of the worst point
+    pub litres: f32,            // L at the surface needed from there
+    pub bar: f32,               // cylinder pressure holding the litres other cylinders of the gas do not
+    pub available: f32,         // bar left in the cylinder at that point
+    pub pass: bool,             // the cylinder still holds the reserve at that point
+}
+
+/// Where the profile crosses a cylinder's turn pressure.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TurnPoint {
+    pub turn_pressure: f32,     // bar
+    pub time: Option<f32>,      // s of the first sample at or below the turn pressure, None if never reached
+    pub depth: Option<f32>,     // m at that sample
+}
+
+/// Turn points of every cylinder of `plan` under `rule`, in the same order as `DivePlan::cylinders`.
+///
+/// `buddies` are the buddies' cylinders, their `gas` indexing into `plan.gases`; each plan
+/// cylinder is matched with the buddy cylinders carrying the same gas.
+#[cfg(feature = "alloc")]
+pub fn turn_points(plan: &DivePlan, usage: &GasUsage, outputs: &SimulationOutputs, rule: GasRule, buddies: &[Cylinder]) -> Vec<TurnPoint> {
+    plan.cylinders.iter()
+        .zip(usage.remaining.iter())
+        .map(|(cylinder, remaining)| {
+            let team: Vec<Cylinder> = buddies.iter().filter(|buddy| buddy.gas == cylinder.gas).copied().collect();
+            let turn_pressure = turn_pressure(cylinder, &plan.gases[cylinder.gas], rule, &team);
+            let reached = remaining.iter().position(|pressure| *pressure <= turn_pressure);
+            TurnPoint {
+                turn_pressure,
+                time: reached.and_then(|i| outputs.times.get(i).copied()),
+                depth: reached.and_then(|i| outputs.depths.get(i).copied()),
+            }
+        })
+        .collect()
+}
+
+/// Minimum gas for every cylinder of `plan`, in the same order as `DivePlan::cylinders`.
+///
+/// Every sample breathed from a cylinder within the MOD of its gas at `settings.max_ppo2` is a
+/// candidate out-of-gas point: the shared ascent is simulated from there on the same gas with the
+/// tissues of that moment. Other cylinders filled
+/// with that gas share the ascent, only what they cannot cover has to stay in the breathed one.
+/// Cylinders that are never breathed need no reserve and pass.
+#[cfg(feature = "alloc")]
+pub fn minimum_gas(plan: &DivePlan, params: &DiveParameters, outputs: &SimulationOutputs, settings: &MinimumGasSettings, temperature: f32, interval_in_seconds: f32) -> Vec<MinimumGas> {
+    let usage = gas_usage(plan, params, outputs);
+    let mut minimum: Vec<Option<MinimumGas>> = Vec::from_iter(plan.cylinders.iter().map(|_| None));
+    let shared_sac_rate = settings.stressed_sac_rate * settings.divers as f32;
+
+    for i in 0..outputs.times.len() {
+        let gas = outputs.gases[i];
+        let Some(index) = usage.sources[i] else {
+            continue;
+        };
+        let depth = outputs.depths[i];
+        if depth <= 0.0 || outputs.pressures[i] * gas.o2() > settings.max_ppo2 {
+            continue;
+        }
+
+        let mut ascent = Simulator::new(*params, outputs.tissues_per_interval[i], outputs.pressures[i], depth, depth, temperature, interval_in_seconds, settings.problem_solving_time, true);
+        ascent.set_environment(plan.surface_pressure, plan.water, gas, None);
+        let litres = shared_ascent_gas(ascent, shared_sac_rate);
+
+        let spare: f32 = plan.cylinders.iter()
+            .enumerate()
+            .filter(|(j, other)| *j != index && plan.gases.get(other.gas) == Some(&gas))
+            .map(|(j, other)| other.gas_volume(usage.remaining[j][i].max(0.0), &gas))
+            .sum();
+        let bar = plan.cylinders[index].pressure_for((litres - spare).max(0.0), &gas);
+        let available = usage.remaining[index][i];
+        let candidate = MinimumGas { depth, time: outputs.times[i], litres, bar, available, pass: available >= bar };
+
+        // worst is the point with the least margin between what is left and what is needed
+        let worst = &mut minimum[index];
+        if worst.is_none_or(|worst| candidate.available - candidate.bar < worst.available - worst.bar) {
+            *worst = Some(candidate);
+        }
+    }
+
+    minimum.into_iter()
+        .zip(plan.cylinders.iter())
+        .map(|(worst, cylinder)| worst.unwrap_or(MinimumGas { available: cylinder.start_pressure, pass: true, ..MinimumGas::default() }))
+        .collect()
+}
+
+#[cfg(feature = "alloc")]
+fn shared_ascent_gas(ascent: Simulator, sac_rate: f32) -> f32 {
+    let mut litres = 0.0;
+    let mut previous: Option<(f32, f32)> = None;
+    for record in ascent {
+        if let SimulationRecord::Sample(sample) = record {
+            if let Some((time, pressure)) = previous {
+                litres += gas_used(sac_rate, (pressure + sample.pressure) / 2.0, (sample.time - time) / 60.0);
+            }
+            previous = Some((sample.time, sample.pressure));
+        }
+    }
+    litres
+}
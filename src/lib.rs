@@ -4,23 +4,70 @@
 use std::println;
 
 use defmt::Format;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "std")]
 extern crate std;
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod alarm;
 pub mod ceiling;
+#[cfg(feature = "alloc")]
+pub mod contingency;
+pub mod conservatism;
+pub mod cylinder;
+pub mod gas;
 pub mod ndl;
+pub mod plan;
+#[cfg(all(feature = "serde", feature = "alloc"))]
+pub mod plan_file;
 pub mod simulate;
+pub mod stop;
+#[cfg(feature = "alloc")]
+pub mod table;
 pub mod m_value;
+pub mod mode;
+pub mod parameters;
 pub mod tissue;
+pub mod tissue_state;
 pub mod zh16c;
 
+/// Breathing gas, as inert gas fractions.
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Gas {
     pub n2: f32,
     pub he: f32,
 }
 
-#[derive(Debug, Format, Copy, Clone)]
+impl Gas {
+    pub fn new(o2: f32, he: f32) -> Self {
+        Gas {
+            n2: 1.0 - o2 - he,
+            he,
+        }
+    }
+
+    pub fn air() -> Self {
+        Gas { n2: FN2, he: FHE }
+    }
+
+    pub fn o2(&self) -> f32 {
+        1.0 - self.n2 - self.he
+    }
+}
+
+impl Default for Gas {
+    fn default() -> Self {
+        Gas::air()
+    }
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DiveParameters {
     pub descent_speed: f32,                 // m/s
     pub ascent_speed: f32,                  // m/s
@@ -29,7 +76,9 @@ pub struct DiveParameters {
     pub safety_stop_depth: f32,             // m
     pub gf_low: f32,                        // 0 < x <= 1
     pub gf_high: f32,                       // 0 < x <= 1
+    pub conservatism: Conservatism,         // level the gradient factors were chosen by
     pub sac_rate: f32,                      // litres per minute
+    pub deco_sac_rate: f32,                 // litres per minute on the ascent and at stops
 }
 
 impl DiveParameters {
@@ -42,9 +91,23 @@ impl DiveParameters {
             safety_stop_depth: 5.0,
             gf_low,
             gf_high,
-            sac_rate: 20.0
+            conservatism: Conservatism::from_gradient_factors(gf_low, gf_high),
+            sac_rate: 20.0,
+            deco_sac_rate: 20.0,
         }
     }
+
+    pub fn with_conservatism(conservatism: Conservatism) -> Self {
+        let mut params = DiveParameters::default();
+        params.set_conservatism(conservatism);
+        params
+    }
+
+    /// Switch to `conservatism` and its gradient factors.
+    pub fn set_conservatism(&mut self, conservatism: Conservatism) {
+        (self.gf_low, self.gf_high) = conservatism.gradient_factors();
+        self.conservatism = conservatism;
+    }
 }
 
 impl Default for DiveParameters {
@@ -57,7 +120,9 @@ impl Default for DiveParameters {
             safety_stop_depth: 5.0,
             gf_low: 1.0,
             gf_high: 1.0,
-            sac_rate: 20.0
+            conservatism: Conservatism::Custom { gf_low: 1.0, gf_high: 1.0 },
+            sac_rate: 20.0,
+            deco_sac_rate: 20.0,
         }
     }
 }
@@ -70,9 +135,12 @@ pub const FHE: f32 = 0.0;
 //     He,
 // }
 
-use crate::ceiling::max_ceiling;
+use crate::ceiling::{max_ceiling, GfSlope};
+use crate::conservatism::Conservatism;
+use crate::parameters::ParameterErrors;
 use crate::m_value::calculate_m_values;
-use crate::tissue::{calculate_tissue, Tissue};
+use crate::stop::time_to_ceiling;
+use crate::tissue::{calculate_tissue_with_gas, Tissue, TissueUpdater};
 
 
 
@@ -99,20 +167,130 @@ pub fn water_vapor_pressure(_temperature: f32) -> f32 {
 // }
 
 
-#[derive(Debug, Format)]
+/// Quantity a `DecoError::InvalidInput` refers to.
+#[derive(Debug, Format, Copy, Clone, PartialEq, Eq)]
+pub enum Input {
+    Depth,              // m
+    AmbientPressure,    // bar
+    SurfacePressure,    // bar
+    Time,               // s or min, whichever the function takes
+    Temperature,        // °C
+    TissueLoad,         // bar
+    GasFraction,
+    GradientFactor,
+}
+
+impl core::fmt::Display for Input {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name = match self {
+            Input::Depth => "depth",
+            Input::AmbientPressure => "ambient pressure",
+            Input::SurfacePressure => "surface pressure",
+            Input::Time => "time",
+            Input::Temperature => "temperature",
+            Input::TissueLoad => "tissue load",
+            Input::GasFraction => "gas fraction",
+            Input::GradientFactor => "gradient factor",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
 pub enum DecoError {
     Oversaturation,
     BurstCeiling,
     InvalidSolution,
+    InvalidSampleInterval,
+    /// The dive plan has no segments, or a segment or cylinder refers to a gas that is not in its gas list
+    InvalidPlan,
+    /// A value that is NaN, infinite or outside the range the calculation accepts
+    InvalidInput { input: Input, value: f32 },
+    /// Tissue compartments are numbered 0 to 15
+    InvalidTissueIndex(usize),
+    InvalidParameters(ParameterErrors),
+    /// An iterative calculation hit its iteration limit without a result
+    NoConvergence { iterations: u32 },
+    /// A decompression stop reached the 20 minute per-stop limit of the simulator
+    ExcessiveDeco { depth: f32 },   // m
+}
+
+impl core::fmt::Display for DecoError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DecoError::Oversaturation => write!(f, "a tissue is loaded beyond its M-value"),
+            DecoError::BurstCeiling => write!(f, "the ceiling was violated"),
+            DecoError::InvalidSolution => write!(f, "no decompression schedule was found"),
+            DecoError::InvalidSampleInterval => write!(f, "the sample interval is out of range"),
+            DecoError::InvalidPlan => write!(f, "the dive plan has no segments or refers to a missing gas"),
+            DecoError::InvalidInput { input, value } => write!(f, "invalid {} {}", input, value),
+            DecoError::InvalidTissueIndex(index) => write!(f, "tissue index {} is not between 0 and 15", index),
+            DecoError::InvalidParameters(errors) => write!(f, "invalid dive parameters: {}", errors),
+            DecoError::NoConvergence { iterations } => write!(f, "no result after {} iterations", iterations),
+            DecoError::ExcessiveDeco { depth } => write!(f, "the stop at {} m reached the per-stop time limit", depth),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecoError {}
+
+impl From<ParameterErrors> for DecoError {
+    fn from(errors: ParameterErrors) -> Self {
+        DecoError::InvalidParameters(errors)
+    }
+}
+
+/// `value` when it is finite and `valid` holds for it
+pub(crate) fn check_input(input: Input, value: f32, valid: impl Fn(f32) -> bool) -> Result<f32, DecoError> {
+    if value.is_finite() && valid(value) {
+        Ok(value)
+    } else {
+        Err(DecoError::InvalidInput { input, value })
+    }
+}
+
+/// Every tissue load finite and not negative
+pub(crate) fn check_tissues(tissues: &[Tissue]) -> Result<(), DecoError> {
+    for tissue in tissues {
+        check_input(Input::TissueLoad, tissue.load_n2, |load| load >= 0.0)?;
+        check_input(Input::TissueLoad, tissue.load_he, |load| load >= 0.0)?;
+    }
+    Ok(())
+}
+
+/// Both fractions between 0 and 1 and together at most 1
+pub(crate) fn check_gas(gas: Gas) -> Result<(), DecoError> {
+    check_input(Input::GasFraction, gas.n2, |fraction| (0.0..=1.0).contains(&fraction))?;
+    check_input(Input::GasFraction, gas.he, |fraction| (0.0..=1.0).contains(&fraction) && fraction + gas.n2 <= 1.0)?;
+    Ok(())
+}
+
+/// Both gradient factors above 0 and at most 1
+pub(crate) fn check_gradient_factors(gf_low: f32, gf_high: f32) -> Result<(), DecoError> {
+    check_input(Input::GradientFactor, gf_low, |gf| gf > 0.0 && gf <= 1.0)?;
+    check_input(Input::GradientFactor, gf_high, |gf| gf > 0.0 && gf <= 1.0)?;
+    Ok(())
+}
+
+pub(crate) fn check_tissue_index(tissue_index: usize) -> Result<(), DecoError> {
+    if tissue_index < 16 { Ok(()) } else { Err(DecoError::InvalidTissueIndex(tissue_index)) }
 }
 
 
+/// Loads `tissues` for one sample of `delta_t` minutes on air and checks them against the M-values.
+///
+/// Builds a `TissueUpdater` on every call; a sample loop keeps one and calls
+/// `run_no_deco_loop_with_updater` instead.
 pub fn run_no_deco_loop(_dive_parameters: &mut DiveParameters, tissues: &mut [Tissue; 16], amb_pressure: f32, temperature: f32, delta_t: f32) -> Result<(), DecoError> {
-    for i in 0..16 {
-        tissues[i] = calculate_tissue(tissues[i], i, amb_pressure, temperature, delta_t);
-        defmt::info!("{:?} - {:?}", i, tissues[i].load_n2);
+    run_no_deco_loop_with_updater(&TissueUpdater::new(delta_t), tissues, amb_pressure, temperature)
+}
 
-        if calculate_m_values(amb_pressure, i) < tissues[i].load_n2 {
+/// `run_no_deco_loop` with the decay factors of the sample interval computed once in `updater`.
+pub fn run_no_deco_loop_with_updater(updater: &TissueUpdater, tissues: &mut [Tissue; 16], amb_pressure: f32, temperature: f32) -> Result<(), DecoError> {
+    updater.update_all(tissues, amb_pressure, temperature, Gas::air());
+    for (i, tissue) in tissues.iter().enumerate() {
+        if calculate_m_values(amb_pressure, i) < tissue.load_n2 {
             return Err(DecoError::Oversaturation);
         }
     }
@@ -120,6 +298,15 @@ pub fn run_no_deco_loop(_dive_parameters: &mut DiveParameters, tissues: &mut [Ti
     Ok(())
 }
 
+/// `run_no_deco_loop` that returns an error instead of producing NaN on bad input.
+pub fn try_run_no_deco_loop(dive_parameters: &mut DiveParameters, tissues: &mut [Tissue; 16], amb_pressure: f32, temperature: f32, delta_t: f32) -> Result<(), DecoError> {
+    check_tissues(tissues)?;
+    check_input(Input::AmbientPressure, amb_pressure, |pressure| pressure > 0.0)?;
+    check_input(Input::Temperature, temperature, |_| true)?;
+    check_input(Input::Time, delta_t, |minutes| minutes >= 0.0)?;
+    run_no_deco_loop(dive_parameters, tissues, amb_pressure, temperature, delta_t)
+}
+
 pub fn calculate_deco_stops(dive_parameters: DiveParameters, tissues: &mut [Tissue; 16], _amb_pressure: f32, temperature: f32) -> Result<(), DecoError> {
     let first_stop = max_ceiling(dive_parameters, tissues);
     let last_stop: i32 = 3;
@@ -127,32 +314,24 @@ pub fn calculate_deco_stops(dive_parameters: DiveParameters, tissues: &mut [Tiss
     println!("Deco starting tissues {:?}", tissues);
     let mut current_stop_depth = first_stop.0;
     let mut base_tissues_clone = tissues.clone();
+    // GF low stays at the first stop for the whole ascent
+    let mut slope = GfSlope::new(dive_parameters.gf_low, dive_parameters.gf_high, 1.0);
+    slope.anchor(tissues);
     loop {
         if current_stop_depth >= last_stop as u32 {
-            let mut stop_length = 0;
-            // clone current stop depth tissues. first iteration will be the same as tissues, then it will be the previous stop tissues
-            let mut tissues_clone = base_tissues_clone.clone();
-            loop {
-                // assume that the ambient pressure keeps constant for the stop duration
-                stop_length += 1;
-                // 166 minutes of air is beyond what I can currently carry in my tanks
-                if stop_length > 10000 {
-                    return Err(DecoError::InvalidSolution);
-                }
-                let current_ceiling = max_ceiling(dive_parameters, &tissues_clone);
-                // if the currently calculated ceiling is less than the current stop depth, we may proceed to the next stop
-                // clone the tissues associated with this stop
-                if current_ceiling.0 < current_stop_depth as u32 {
-                    #[cfg(feature = "std")]
-                    println!("Deco stop at {:?} for {:?} seconds", current_stop_depth, stop_length);
-                    base_tissues_clone = tissues_clone.clone();
-                    break;
-                }
-
-                // otherwise, clone the tissues and let them desaturate for another second
-                for i in 0..16 {
-                    tissues_clone[i] = calculate_tissue(tissues_clone[i], i, current_stop_depth as f32 / 10.0 + 1.0, temperature, 1.0/60.0);
-                }
+            // assume that the ambient pressure keeps constant for the stop duration
+            let stop_pressure = current_stop_depth as f32 / 10.0 + 1.0;
+            // the rounded ceiling is below the stop once the exact one is less than 3 m shallower
+            let next_stop = current_stop_depth as f32 - 2.999;
+            // 166 minutes of air is beyond what I can currently carry in my tanks
+            let Some(seconds) = time_to_ceiling(&slope, &base_tissues_clone, stop_pressure, temperature, Gas::air(), next_stop, 9999) else {
+                return Err(DecoError::InvalidSolution);
+            };
+            #[cfg(feature = "std")]
+            println!("Deco stop at {:?} for {:?} seconds", current_stop_depth, seconds);
+            // the tissues at the end of this stop start the next one
+            for (i, tissue) in base_tissues_clone.iter_mut().enumerate() {
+                *tissue = calculate_tissue_with_gas(*tissue, i, stop_pressure, temperature, seconds as f32 / 60.0, Gas::air());
             }
             // deco stop complete, proceed to next stop
             current_stop_depth -= 3;
@@ -164,6 +343,15 @@ pub fn calculate_deco_stops(dive_parameters: DiveParameters, tissues: &mut [Tiss
     Ok(())
 }
 
+/// `calculate_deco_stops` that returns an error instead of panicking or producing NaN on bad input.
+pub fn try_calculate_deco_stops(dive_parameters: DiveParameters, tissues: &mut [Tissue; 16], amb_pressure: f32, temperature: f32) -> Result<(), DecoError> {
+    check_gradient_factors(dive_parameters.gf_low, dive_parameters.gf_high)?;
+    check_tissues(tissues)?;
+    check_input(Input::AmbientPressure, amb_pressure, |pressure| pressure > 0.0)?;
+    check_input(Input::Temperature, temperature, |_| true)?;
+    calculate_deco_stops(dive_parameters, tissues, amb_pressure, temperature)
+}
+
 #[cfg(feature = "std")]
 #[test]
 fn test_deco_stops() {
@@ -182,6 +370,37 @@ fn test_deco_stops() {
     let _result = calculate_deco_stops(DiveParameters::default(), &mut tissues, amb_pressure, temperature);
 }
 
+#[test]
+fn test_try_deco_loops_report_bad_input() {
+    let mut tissues = [Tissue { load_n2: default_tissue_load(20.0), load_he: 0.0 }; 16];
+    let mut params = DiveParameters::default();
+    assert_eq!(try_run_no_deco_loop(&mut params, &mut tissues, 2.0, 20.0, 1.0), Ok(()));
+    assert_eq!(try_run_no_deco_loop(&mut params, &mut tissues, 2.0, 20.0, -1.0),
+        Err(DecoError::InvalidInput { input: Input::Time, value: -1.0 }));
+    assert!(matches!(try_run_no_deco_loop(&mut params, &mut tissues, f32::NAN, 20.0, 1.0),
+        Err(DecoError::InvalidInput { input: Input::AmbientPressure, .. })));
+
+    assert_eq!(try_calculate_deco_stops(DiveParameters::new(1.5, 0.3), &mut tissues, 1.0, 20.0),
+        Err(DecoError::InvalidInput { input: Input::GradientFactor, value: 1.5 }));
+    tissues[4].load_n2 = f32::NAN;
+    assert!(matches!(try_calculate_deco_stops(DiveParameters::default(), &mut tissues, 1.0, 20.0),
+        Err(DecoError::InvalidInput { input: Input::TissueLoad, .. })));
+}
+
+#[test]
+fn test_no_deco_loop_with_a_kept_updater() {
+    let mut tissues = [Tissue { load_n2: default_tissue_load(20.0), load_he: 0.0 }; 16];
+    let mut kept = tissues;
+    let updater = TissueUpdater::new(1.0 / 60.0);
+    for _ in 0..600 {
+        assert_eq!(run_no_deco_loop(&mut DiveParameters::default(), &mut tissues, 3.0, 20.0, 1.0 / 60.0), Ok(()));
+        assert_eq!(run_no_deco_loop_with_updater(&updater, &mut kept, 3.0, 20.0), Ok(()));
+    }
+    for (kept, tissue) in kept.iter().zip(tissues.iter()) {
+        assert_eq!((kept.load_n2, kept.load_he), (tissue.load_n2, tissue.load_he));
+    }
+}
+
 // #[cfg(feature = "std")]
 // #[test]
 // fn test_deco_loop() {
@@ -1,5 +1,13 @@
 use crate::zh16c::ZhL16cGf;
+use crate::{check_input, check_tissue_index, DecoError, Input};
 
 pub fn calculate_m_values(amb_pressure: f32, tissue_index: usize) -> f32 {
     amb_pressure / ZhL16cGf::N2_B[tissue_index] + ZhL16cGf::N2_A[tissue_index]
-}
\ No newline at end of file
+}
+
+/// `calculate_m_values` that returns an error for a tissue index past 15 or a bad pressure.
+pub fn try_calculate_m_values(amb_pressure: f32, tissue_index: usize) -> Result<f32, DecoError> {
+    check_tissue_index(tissue_index)?;
+    check_input(Input::AmbientPressure, amb_pressure, |pressure| pressure > 0.0)?;
+    Ok(calculate_m_values(amb_pressure, tissue_index))
+}
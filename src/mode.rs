@@ -0,0 +1,305 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use defmt::Format;
+use crate::ceiling::GfSlope;
+use crate::tissue::{Tissue, TissueUpdater};
+use crate::{DecoError, DiveParameters, Gas};
+
+/// Depth below which the diver is considered to be underwater (m)
+pub const SUBMERGED_DEPTH: f32 = 1.2;
+/// Surface time after a gauge dive before the tissue model can be trusted again (s)
+pub const GAUGE_LOCKOUT_SECONDS: f32 = 48.0 * 60.0 * 60.0;
+/// Longest sample interval accepted in freedive mode (s)
+pub const FREEDIVE_MAX_SAMPLE_INTERVAL: f32 = 1.0;
+/// Depth change from the last turn that counts as an ascent or a re-descent (m), above depth noise
+pub const TURN_DEPTH_CHANGE: f32 = 1.0;
+
+#[derive(Debug, Format, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DiveMode {
+    /// Full Bühlmann decompression tracking
+    Decompression,
+    /// Depth, time and stopwatch only, no tissue model
+    Gauge,
+    /// Breath-hold diving with per-apnea tracking
+    Freedive,
+}
+
+#[derive(Debug, Format, Copy, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GaugeState {
+    pub depth: f32,                 // m
+    pub max_depth: f32,             // m
+    pub average_depth: f32,         // m, time weighted
+    pub dive_time: f32,             // s, time spent below SUBMERGED_DEPTH
+    pub stopwatch: f32,             // s
+    pub stopwatch_running: bool,
+}
+
+impl GaugeState {
+    pub fn start_stopwatch(&mut self) {
+        self.stopwatch_running = true;
+    }
+
+    pub fn stop_stopwatch(&mut self) {
+        self.stopwatch_running = false;
+    }
+
+    pub fn reset_stopwatch(&mut self) {
+        self.stopwatch = 0.0;
+    }
+
+    fn update(&mut self, depth: f32, delta_t_seconds: f32) {
+        self.depth = depth;
+        if self.stopwatch_running {
+            self.stopwatch += delta_t_seconds;
+        }
+        if depth < SUBMERGED_DEPTH {
+            return;
+        }
+
+        let total_time = self.dive_time + delta_t_seconds;
+        if total_time > 0.0 {
+            self.average_depth = (self.average_depth * self.dive_time + depth * delta_t_seconds) / total_time;
+        }
+        self.dive_time = total_time;
+        self.max_depth = self.max_depth.max(depth);
+    }
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DecoState {
+    /// Gradient factors of the dive. GF low is anchored at the deepest stop once the diver has
+    /// ascended `TURN_DEPTH_CHANGE` from the deepest depth, and released again at the surface or
+    /// after going back down `TURN_DEPTH_CHANGE` from the shallowest depth since.
+    pub gf_slope: GfSlope,
+    pub depth: f32,                 // m of the last sample
+    /// m, deepest depth while the slope is free, shallowest while it is anchored
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub turn_depth: f32,
+}
+
+impl Default for DecoState {
+    fn default() -> Self {
+        let params = DiveParameters::default();
+        DecoState {
+            gf_slope: GfSlope::new(params.gf_low, params.gf_high, 1.0),
+            depth: 0.0,
+            turn_depth: 0.0,
+        }
+    }
+}
+
+impl DecoState {
+    /// Deepest ceiling of `tissues` on the slope of the dive (m), the leading compartment and its GF
+    pub fn max_ceiling(&self, tissues: &[Tissue; 16]) -> (f32, usize, f32) {
+        self.gf_slope.max_ceiling(tissues)
+    }
+
+    fn update(&mut self, tissues: &[Tissue; 16], depth: f32, surface_pressure: f32) {
+        self.gf_slope.surface_pressure = surface_pressure;
+        if depth < SUBMERGED_DEPTH {
+            self.gf_slope.release();
+            self.turn_depth = depth;
+        } else if self.gf_slope.first_stop_pressure.is_none() {
+            self.turn_depth = self.turn_depth.max(depth);
+            if depth < self.turn_depth - TURN_DEPTH_CHANGE {
+                self.gf_slope.anchor(tissues);
+                if self.gf_slope.first_stop_pressure.is_some() {
+                    self.turn_depth = depth;
+                }
+            }
+        } else if depth > self.turn_depth + TURN_DEPTH_CHANGE {
+            // a new level, like the simulator the next ascent finds its own deepest stop
+            self.gf_slope.release();
+            self.turn_depth = depth;
+        } else {
+            self.turn_depth = self.turn_depth.min(depth);
+        }
+        self.depth = depth;
+    }
+}
+
+#[derive(Debug, Format, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FreediveSettings {
+    pub depth_alarm: f32,               // m, 0 disables
+    pub time_alarm: f32,                // s, 0 disables
+    pub min_surface_interval: f32,      // s
+    pub surface_interval_ratio: f32,    // required surface interval as a multiple of the last apnea
+}
+
+impl Default for FreediveSettings {
+    fn default() -> Self {
+        FreediveSettings {
+            depth_alarm: 0.0,
+            time_alarm: 0.0,
+            min_surface_interval: 60.0,
+            surface_interval_ratio: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Format, Copy, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Apnea {
+    pub max_depth: f32,     // m
+    pub duration: f32,      // s
+}
+
+#[derive(Debug, Format, Copy, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FreediveState {
+    pub settings: FreediveSettings,
+    pub apnea_count: u32,
+    pub current: Option<Apnea>,
+    pub last: Option<Apnea>,
+    pub surface_interval: f32,      // s since the last apnea ended
+}
+
+impl FreediveState {
+    /// Surface interval required before the next apnea, based on the last one (s)
+    pub fn required_surface_interval(&self) -> f32 {
+        match self.last {
+            Some(apnea) => self.settings.min_surface_interval.max(apnea.duration * self.settings.surface_interval_ratio),
+            None => 0.0,
+        }
+    }
+
+    fn update(&mut self, depth: f32, delta_t_seconds: f32) -> FreediveAlarms {
+        let mut alarms = FreediveAlarms::default();
+
+        match (self.current.as_mut(), depth >= SUBMERGED_DEPTH) {
+            (Some(apnea), true) => {
+                apnea.duration += delta_t_seconds;
+                apnea.max_depth = apnea.max_depth.max(depth);
+            }
+            (Some(apnea), false) => {
+                self.last = Some(*apnea);
+                self.current = None;
+                self.surface_interval = 0.0;
+            }
+            (None, true) => {
+                alarms.surface_interval = self.last.is_some() && self.surface_interval < self.required_surface_interval();
+                self.apnea_count += 1;
+                self.current = Some(Apnea { max_depth: depth, duration: delta_t_seconds });
+            }
+            (None, false) => {
+                self.surface_interval += delta_t_seconds;
+            }
+        }
+
+        if let Some(apnea) = self.current {
+            alarms.depth = self.settings.depth_alarm > 0.0 && apnea.max_depth >= self.settings.depth_alarm;
+            alarms.time = self.settings.time_alarm > 0.0 && apnea.duration >= self.settings.time_alarm;
+        }
+
+        alarms
+    }
+}
+
+#[derive(Debug, Format, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FreediveAlarms {
+    pub depth: bool,
+    pub time: bool,
+    /// A new apnea started before the required surface interval elapsed
+    pub surface_interval: bool,
+}
+
+#[derive(Debug, Format, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ModeState {
+    pub mode: DiveMode,
+    pub surface_pressure: f32,      // bar
+    /// Set once a gauge dive is logged, survives mode changes and is only cleared after
+    /// GAUGE_LOCKOUT_SECONDS at the surface or an explicit tissue reset.
+    pub tissue_state_unknown: bool,
+    pub lockout_elapsed: f32,       // s at the surface since the flag was set
+    pub gauge: GaugeState,
+    pub deco: DecoState,
+    pub freedive: FreediveState,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    updater: TissueUpdater,         // decay factors of the last sample interval
+}
+
+impl ModeState {
+    pub fn new(mode: DiveMode) -> Self {
+        ModeState {
+            mode,
+            surface_pressure: 1.0,
+            tissue_state_unknown: false,
+            lockout_elapsed: 0.0,
+            gauge: GaugeState::default(),
+            deco: DecoState::default(),
+            freedive: FreediveState::default(),
+            updater: TissueUpdater::default(),
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: DiveMode) {
+        if mode != self.mode {
+            self.gauge = GaugeState::default();
+            self.deco = DecoState { gf_slope: GfSlope { first_stop_pressure: None, ..self.deco.gf_slope }, ..DecoState::default() };
+            self.freedive = FreediveState { settings: self.freedive.settings, ..FreediveState::default() };
+        }
+        self.mode = mode;
+    }
+
+    /// Call after re-initialising the tissues to surface saturation, e.g. after a manual desat reset.
+    pub fn reset_tissue_state(&mut self) {
+        self.tissue_state_unknown = false;
+        self.lockout_elapsed = 0.0;
+    }
+
+    /// Advance the active mode by one sample. Tissues are only updated in decompression and freedive mode.
+    pub fn update(&mut self, tissues: &mut [Tissue; 16], depth: f32, temperature: f32, delta_t_seconds: f32) -> Result<FreediveAlarms, DecoError> {
+        if delta_t_seconds < 0.0 || (self.mode == DiveMode::Freedive && delta_t_seconds > FREEDIVE_MAX_SAMPLE_INTERVAL) {
+            return Err(DecoError::InvalidSampleInterval);
+        }
+
+        self.gauge.update(depth, delta_t_seconds);
+        self.update_lockout(depth, delta_t_seconds);
+
+        match self.mode {
+            DiveMode::Decompression => {
+                self.update_tissues(tissues, depth, temperature, delta_t_seconds);
+                self.deco.update(tissues, depth, self.surface_pressure);
+                Ok(FreediveAlarms::default())
+            }
+            DiveMode::Gauge => {
+                if depth >= SUBMERGED_DEPTH {
+                    self.tissue_state_unknown = true;
+                    self.lockout_elapsed = 0.0;
+                }
+                Ok(FreediveAlarms::default())
+            }
+            DiveMode::Freedive => {
+                // breath-hold dives still load nitrogen, repeated apneas add up
+                self.update_tissues(tissues, depth, temperature, delta_t_seconds);
+                Ok(self.freedive.update(depth, delta_t_seconds))
+            }
+        }
+    }
+
+    fn update_lockout(&mut self, depth: f32, delta_t_seconds: f32) {
+        if !self.tissue_state_unknown || (self.mode == DiveMode::Gauge && depth >= SUBMERGED_DEPTH) {
+            return;
+        }
+        if depth < SUBMERGED_DEPTH {
+            self.lockout_elapsed += delta_t_seconds;
+        }
+        if self.lockout_elapsed >= GAUGE_LOCKOUT_SECONDS {
+            self.reset_tissue_state();
+        }
+    }
+
+    fn update_tissues(&mut self, tissues: &mut [Tissue; 16], depth: f32, temperature: f32, delta_t_seconds: f32) {
+        let amb_pressure = self.surface_pressure + depth.max(0.0) / 10.0;
+        if self.updater.minutes() != delta_t_seconds / 60.0 {
+            self.updater = TissueUpdater::new(delta_t_seconds / 60.0);
+        }
+        self.updater.update_all(tissues, amb_pressure, temperature, Gas::air());
+    }
+}
@@ -1,29 +1,55 @@
-use crate::ceiling::{binary_ceiling_with_gf, ceiling};
-use crate::tissue::{calculate_tissue, Tissue};
-use crate::DiveParameters;
-use core::panic;
+use crate::ceiling::{binary_ceiling_with_gf, ceiling, check_slope, GfSlope};
+use crate::tissue::{Tissue, TissueUpdater};
+use crate::{check_gas, check_gradient_factors, check_input, check_tissues, DecoError, DiveParameters, Gas, Input};
 
+/// Maximum whole minutes `ndl` and `binary_ndl` step through, a guard against GFs that never
+/// produce a ceiling
+const MAX_ITERATIONS: u32 = 10000;
+
+/// NDL on air at `amb_pressure` in whole minutes, stepping `tissues` a minute at a time.
+/// Saturates at 10,000 minutes when no ceiling appears, e.g. at the surface.
 pub fn ndl(
     dive_parameters: DiveParameters,
     tissues: &mut [Tissue; 16],
     amb_pressure: f32,
     temperature: f32,
 ) -> f32 {
+    step_ndl(dive_parameters, tissues, amb_pressure, temperature).unwrap_or(MAX_ITERATIONS as f32)
+}
+
+/// `ndl` that returns an error on bad input, and instead of saturating when no ceiling appears
+/// within 10,000 minutes.
+pub fn try_ndl(
+    dive_parameters: DiveParameters,
+    tissues: &mut [Tissue; 16],
+    amb_pressure: f32,
+    temperature: f32,
+) -> Result<f32, DecoError> {
+    check_ndl_inputs(dive_parameters.gf_low, dive_parameters.gf_high, tissues, amb_pressure, temperature)?;
+    step_ndl(dive_parameters, tissues, amb_pressure, temperature)
+}
+
+fn step_ndl(
+    dive_parameters: DiveParameters,
+    tissues: &mut [Tissue; 16],
+    amb_pressure: f32,
+    temperature: f32,
+) -> Result<f32, DecoError> {
     // while ceiling is 0 keep looping
     let mut bottom_time = 0.0;
     let mut max_ceiling: u32;
-    const MAX_ITERATIONS: u32 = 10000; // Prevent infinite loops with extreme GF values
     let mut iterations = 0;
+    let minute = TissueUpdater::new(1.0);
 
     loop {
         max_ceiling = 0; // Reset max_ceiling at the start of each iteration
         for i in 0..16 {
-            tissues[i] = calculate_tissue(tissues[i], i, amb_pressure, temperature, 1.0);
+            tissues[i] = minute.update(tissues[i], i, amb_pressure, temperature, Gas::air());
             max_ceiling = u32::max(max_ceiling, ceiling(dive_parameters, tissues[i], i, true));
         }
 
         if max_ceiling != 0 {
-            return bottom_time;
+            return Ok(bottom_time);
         }
 
         bottom_time += 1.0;
@@ -31,32 +57,53 @@ pub fn ndl(
 
         // Safety check to prevent infinite loops with extreme gradient factors
         if iterations >= MAX_ITERATIONS {
-            panic!("NDL calculation exceeded maximum iterations");
+            return Err(DecoError::NoConvergence { iterations });
         }
     }
 }
 
-/// Binary search implementation of NDL (No Decompression Limit) calculation
-/// Uses binary search to find the maximum bottom time where ceiling remains 0
+/// `ndl` against the `binary_ceiling_with_gf` of GF low. Saturates at 10,000 minutes when no
+/// ceiling appears.
 pub fn binary_ndl(
     dive_parameters: DiveParameters,
     tissues: &mut [Tissue; 16],
     amb_pressure: f32,
     temperature: f32,
 ) -> f32 {
+    step_binary_ndl(dive_parameters, tissues, amb_pressure, temperature).unwrap_or(MAX_ITERATIONS as f32)
+}
+
+/// `binary_ndl` that returns an error on bad input, and instead of saturating when no ceiling
+/// appears within 10,000 minutes.
+pub fn try_binary_ndl(
+    dive_parameters: DiveParameters,
+    tissues: &mut [Tissue; 16],
+    amb_pressure: f32,
+    temperature: f32,
+) -> Result<f32, DecoError> {
+    check_ndl_inputs(dive_parameters.gf_low, dive_parameters.gf_high, tissues, amb_pressure, temperature)?;
+    step_binary_ndl(dive_parameters, tissues, amb_pressure, temperature)
+}
+
+fn step_binary_ndl(
+    dive_parameters: DiveParameters,
+    tissues: &mut [Tissue; 16],
+    amb_pressure: f32,
+    temperature: f32,
+) -> Result<f32, DecoError> {
     let mut bottom_time = 0.0;
     let mut max_ceiling: u32;
-    const MAX_ITERATIONS: u32 = 10000; // Prevent infinite loops with extreme GF values
     let mut iterations = 0;
+    let minute = TissueUpdater::new(1.0);
     loop {
         max_ceiling = 0; // Reset max_ceiling at the start of each iteration
         for i in 0..16 {
-            tissues[i] = calculate_tissue(tissues[i], i, amb_pressure, temperature, 1.0);
+            tissues[i] = minute.update(tissues[i], i, amb_pressure, temperature, Gas::air());
             max_ceiling = u32::max(max_ceiling, binary_ceiling_with_gf(dive_parameters.gf_low, tissues[i], i, true));
         }
 
         if max_ceiling != 0 {
-            return bottom_time;
+            return Ok(bottom_time);
         }
 
         bottom_time += 1.0;
@@ -64,7 +111,70 @@ pub fn binary_ndl(
 
         // Safety check to prevent infinite loops with extreme gradient factors
         if iterations >= MAX_ITERATIONS {
-            panic!("NDL calculation exceeded maximum iterations");
+            return Err(DecoError::NoConvergence { iterations });
         }
     }
-}
\ No newline at end of file
+}
+
+/// Gradient factors, tissue loads, ambient pressure and temperature every fallible NDL checks
+fn check_ndl_inputs(gf_low: f32, gf_high: f32, tissues: &[Tissue; 16], amb_pressure: f32, temperature: f32) -> Result<(), DecoError> {
+    check_gradient_factors(gf_low, gf_high)?;
+    check_tissues(tissues)?;
+    check_input(Input::AmbientPressure, amb_pressure, |pressure| pressure > 0.0)?;
+    check_input(Input::Temperature, temperature, |_| true)?;
+    Ok(())
+}
+
+/// NDL at a constant ambient pressure breathing `gas`, in whole minutes, capped at `limit_minutes`.
+/// Returns `None` when the tissues already carry a decompression obligation under `slope`.
+/// Tissues are projected with the closed-form tissue equation and the minute found by bisection,
+/// so the cost does not grow with the NDL and the caller's tissues are left untouched.
+pub fn ndl_with_limit(
+    slope: &GfSlope,
+    tissues: &[Tissue; 16],
+    amb_pressure: f32,
+    temperature: f32,
+    gas: Gas,
+    limit_minutes: u32,
+) -> Option<f32> {
+    let in_deco_after = |minutes: u32| {
+        let mut projected = *tissues;
+        TissueUpdater::new(minutes as f32).update_all(&mut projected, amb_pressure, temperature, gas);
+        slope.max_ceiling(&projected).0 > 0.0
+    };
+
+    if in_deco_after(0) {
+        return None;
+    }
+    if !in_deco_after(limit_minutes) {
+        return Some(limit_minutes as f32);
+    }
+
+    // invariant: no deco after `low` minutes, deco after `high` minutes
+    let mut low = 0;
+    let mut high = limit_minutes;
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        if in_deco_after(mid) {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    Some(low as f32)
+}
+
+/// `ndl_with_limit` that returns an error instead of producing NaN on bad input.
+pub fn try_ndl_with_limit(
+    slope: &GfSlope,
+    tissues: &[Tissue; 16],
+    amb_pressure: f32,
+    temperature: f32,
+    gas: Gas,
+    limit_minutes: u32,
+) -> Result<Option<f32>, DecoError> {
+    check_slope(slope)?;
+    check_ndl_inputs(slope.gf_low, slope.gf_high, tissues, amb_pressure, temperature)?;
+    check_gas(gas)?;
+    Ok(ndl_with_limit(slope, tissues, amb_pressure, temperature, gas, limit_minutes))
+}
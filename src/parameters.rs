@@ -0,0 +1,205 @@
+//! Validated construction of `DiveParameters`.
+//!
+//! The model accepts any number in `DiveParameters`, but a GF of 0 or a speed of 0 never
+//! converges and a GF above 1 allows more than the M-value. `DiveParametersBuilder` checks every
+//! field when it builds and reports all problems at once, so a settings screen can flag each one.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use core::fmt;
+
+use defmt::Format;
+
+use crate::conservatism::Conservatism;
+use crate::DiveParameters;
+
+/// Number of checks `DiveParameters::validate` runs, one per `ParameterProblem` variant
+pub const PARAMETER_CHECKS: usize = 10;
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ParameterProblem {
+    /// Not above 0 and at most 1
+    GfLow(f32),
+    /// Not above 0 and at most 1
+    GfHigh(f32),
+    /// GF low above GF high
+    GfOrder { gf_low: f32, gf_high: f32 },
+    DescentSpeed(f32),              // m/s, not above 0
+    AscentSpeed(f32),               // m/s, not above 0
+    SafetyStopAscentSpeed(f32),     // m/s, not above 0
+    SafetyStopDuration(f32),        // s, negative
+    SafetyStopDepth(f32),           // m, negative
+    SacRate(f32),                   // L/min, not above 0
+    DecoSacRate(f32),               // L/min, not above 0
+}
+
+impl fmt::Display for ParameterProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ParameterProblem::GfLow(gf) => write!(f, "GF low {} is not above 0 and at most 1", gf),
+            ParameterProblem::GfHigh(gf) => write!(f, "GF high {} is not above 0 and at most 1", gf),
+            ParameterProblem::GfOrder { gf_low, gf_high } => write!(f, "GF low {} is above GF high {}", gf_low, gf_high),
+            ParameterProblem::DescentSpeed(speed) => write!(f, "descent speed {} m/s is not a finite number above 0", speed),
+            ParameterProblem::AscentSpeed(speed) => write!(f, "ascent speed {} m/s is not a finite number above 0", speed),
+            ParameterProblem::SafetyStopAscentSpeed(speed) => write!(f, "safety stop ascent speed {} m/s is not a finite number above 0", speed),
+            ParameterProblem::SafetyStopDuration(duration) => write!(f, "safety stop duration {} s is negative or not finite", duration),
+            ParameterProblem::SafetyStopDepth(depth) => write!(f, "safety stop depth {} m is negative or not finite", depth),
+            ParameterProblem::SacRate(rate) => write!(f, "SAC rate {} L/min is not a finite number above 0", rate),
+            ParameterProblem::DecoSacRate(rate) => write!(f, "deco SAC rate {} L/min is not a finite number above 0", rate),
+        }
+    }
+}
+
+/// Every problem found in a set of dive parameters, in the order of `ParameterProblem`.
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+pub struct ParameterErrors {
+    params: DiveParameters,
+    failed: u16,        // one bit per check, in the order of ParameterProblem
+}
+
+impl ParameterErrors {
+    /// The problem of check `check`, carrying the offending values
+    fn problem(&self, check: usize) -> ParameterProblem {
+        let params = &self.params;
+        match check {
+            0 => ParameterProblem::GfLow(params.gf_low),
+            1 => ParameterProblem::GfHigh(params.gf_high),
+            2 => ParameterProblem::GfOrder { gf_low: params.gf_low, gf_high: params.gf_high },
+            3 => ParameterProblem::DescentSpeed(params.descent_speed),
+            4 => ParameterProblem::AscentSpeed(params.ascent_speed),
+            5 => ParameterProblem::SafetyStopAscentSpeed(params.safety_stop_ascent_speed),
+            6 => ParameterProblem::SafetyStopDuration(params.safety_stop_duration),
+            7 => ParameterProblem::SafetyStopDepth(params.safety_stop_depth),
+            8 => ParameterProblem::SacRate(params.sac_rate),
+            _ => ParameterProblem::DecoSacRate(params.deco_sac_rate),
+        }
+    }
+
+    pub fn problems(&self) -> impl Iterator<Item = ParameterProblem> + '_ {
+        (0..PARAMETER_CHECKS)
+            .filter(|check| self.failed & (1 << check) != 0)
+            .map(|check| self.problem(check))
+    }
+
+    pub fn len(&self) -> usize {
+        self.failed.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+impl fmt::Display for ParameterErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, problem) in self.problems().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParameterErrors {}
+
+/// Positive and finite, NaN fails
+fn positive(value: f32) -> bool {
+    value > 0.0 && value.is_finite()
+}
+
+/// Zero or more and finite, NaN fails
+fn not_negative(value: f32) -> bool {
+    value >= 0.0 && value.is_finite()
+}
+
+impl DiveParameters {
+    pub fn builder() -> DiveParametersBuilder {
+        DiveParametersBuilder { params: DiveParameters::default() }
+    }
+
+    /// Check every field, listing each problem.
+    pub fn validate(&self) -> Result<(), ParameterErrors> {
+        let gf_in_range = |gf: f32| gf > 0.0 && gf <= 1.0;
+        let checks: [bool; PARAMETER_CHECKS] = [
+            gf_in_range(self.gf_low),
+            gf_in_range(self.gf_high),
+            self.gf_low <= self.gf_high || self.gf_low.is_nan() || self.gf_high.is_nan(),
+            positive(self.descent_speed),
+            positive(self.ascent_speed),
+            positive(self.safety_stop_ascent_speed),
+            not_negative(self.safety_stop_duration),
+            not_negative(self.safety_stop_depth),
+            positive(self.sac_rate),
+            positive(self.deco_sac_rate),
+        ];
+        let failed = checks.iter()
+            .enumerate()
+            .filter(|(_, passed)| !**passed)
+            .fold(0, |failed, (check, _)| failed | 1 << check);
+
+        if failed == 0 { Ok(()) } else { Err(ParameterErrors { params: *self, failed }) }
+    }
+}
+
+/// Starts from `DiveParameters::default()`; `build` validates the result.
+#[derive(Debug, Format, Copy, Clone)]
+pub struct DiveParametersBuilder {
+    params: DiveParameters,
+}
+
+impl DiveParametersBuilder {
+    pub fn gradient_factors(mut self, gf_low: f32, gf_high: f32) -> Self {
+        self.params.gf_low = gf_low;
+        self.params.gf_high = gf_high;
+        self.params.conservatism = Conservatism::from_gradient_factors(gf_low, gf_high);
+        self
+    }
+
+    pub fn conservatism(mut self, conservatism: Conservatism) -> Self {
+        self.params.set_conservatism(conservatism);
+        self
+    }
+
+    /// m/s
+    pub fn descent_speed(mut self, speed: f32) -> Self {
+        self.params.descent_speed = speed;
+        self
+    }
+
+    /// m/s
+    pub fn ascent_speed(mut self, speed: f32) -> Self {
+        self.params.ascent_speed = speed;
+        self
+    }
+
+    /// Stop depth (m), duration (s) and the ascent speed to it (m/s)
+    pub fn safety_stop(mut self, depth: f32, duration: f32, ascent_speed: f32) -> Self {
+        self.params.safety_stop_depth = depth;
+        self.params.safety_stop_duration = duration;
+        self.params.safety_stop_ascent_speed = ascent_speed;
+        self
+    }
+
+    /// L/min on the bottom, also on the ascent unless `deco_sac_rate` is set after it
+    pub fn sac_rate(mut self, rate: f32) -> Self {
+        self.params.sac_rate = rate;
+        self.params.deco_sac_rate = rate;
+        self
+    }
+
+    /// L/min on the ascent and at stops
+    pub fn deco_sac_rate(mut self, rate: f32) -> Self {
+        self.params.deco_sac_rate = rate;
+        self
+    }
+
+    pub fn build(self) -> Result<DiveParameters, ParameterErrors> {
+        self.params.validate()?;
+        Ok(self.params)
+    }
+}
@@ -0,0 +1,203 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use defmt::Format;
+use libm::powf;
+
+#[cfg(feature = "alloc")]
+use crate::gas::{Cylinder, CylinderRole};
+#[cfg(feature = "alloc")]
+use crate::simulate::{OutputSink, SimulationOutputs, Simulator};
+#[cfg(feature = "alloc")]
+use crate::tissue::Tissue;
+#[cfg(feature = "alloc")]
+use crate::{DecoError, Gas};
+use crate::DiveParameters;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[derive(Debug, Format, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum WaterType {
+    #[default]
+    Salt,
+    Fresh,
+}
+
+impl WaterType {
+    /// Depth of water that adds one bar of pressure (m)
+    pub fn metres_per_bar(self) -> f32 {
+        match self {
+            WaterType::Salt => 10.0,
+            WaterType::Fresh => 10.3,
+        }
+    }
+}
+
+/// Surface pressure used at sea level throughout the crate (bar)
+pub const SEA_LEVEL_PRESSURE: f32 = 1.0;
+
+/// Surface pressure at `altitude` metres above sea level, from the standard barometric formula (bar)
+pub fn surface_pressure_at_altitude(altitude: f32) -> f32 {
+    SEA_LEVEL_PRESSURE * powf(1.0 - 2.25577e-5 * altitude, 5.25588)
+}
+
+/// How long a segment lasts once its depth has been reached.
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SegmentTime {
+    /// Time spent at depth, excluding the transition (s)
+    Duration(f32),
+    /// Runtime since the start of the dive at which the segment is left (s)
+    Runtime(f32),
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Segment {
+    pub depth: f32,             // m
+    pub time: SegmentTime,
+    pub gas: usize,             // index into DivePlan::gases
+    pub rate: Option<f32>,      // m/s to reach the depth, None uses the dive parameters
+}
+
+impl Segment {
+    pub fn new(depth: f32, time: SegmentTime, gas: usize) -> Self {
+        Segment {
+            depth,
+            time,
+            gas,
+            rate: None,
+        }
+    }
+
+    /// Seconds to stay at the depth after travelling there from `depth` (m) at `time` (s): the
+    /// duration itself, or what is left of the runtime, at least 0.
+    pub fn duration_from(&self, depth: f32, time: f32, params: &DiveParameters) -> f32 {
+        match self.time {
+            SegmentTime::Duration(duration) => duration,
+            SegmentTime::Runtime(runtime) => {
+                let speed = match self.rate {
+                    Some(rate) => rate,
+                    None if self.depth > depth => params.descent_speed,
+                    None => params.ascent_speed,
+                };
+                (runtime - time - (self.depth - depth).abs() / speed).max(0.0)
+            }
+        }
+    }
+}
+
+/// Multi-level dive plan, simulated as one continuous profile that ends with an ascent from the
+/// last segment including the required decompression.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DivePlan {
+    pub surface_pressure: f32,  // bar
+    pub water: WaterType,
+    pub gases: Vec<Gas>,
+    pub segments: Vec<Segment>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cylinders: Vec<Cylinder>,
+    /// Highest ppO2 the final ascent switches to a carried gas at (bar); None stays on the gas of
+    /// the last segment
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub deco_ppo2: Option<f32>,
+}
+
+#[cfg(feature = "alloc")]
+impl DivePlan {
+    pub fn new(surface_pressure: f32, water: WaterType) -> Self {
+        DivePlan {
+            surface_pressure,
+            water,
+            gases: Vec::new(),
+            segments: Vec::new(),
+            cylinders: Vec::new(),
+            deco_ppo2: None,
+        }
+    }
+
+    /// Adds a gas and returns the index segments use to refer to it.
+    pub fn add_gas(&mut self, gas: Gas) -> usize {
+        self.gases.push(gas);
+        self.gases.len() - 1
+    }
+
+    pub fn add_segment(&mut self, segment: Segment) {
+        self.segments.push(segment);
+    }
+
+    /// Adds a cylinder and returns its index.
+    pub fn add_cylinder(&mut self, cylinder: Cylinder) -> usize {
+        self.cylinders.push(cylinder);
+        self.cylinders.len() - 1
+    }
+
+    /// Gases of the cylinders the final ascent may switch to, bailout cylinders left out
+    pub fn deco_gases(&self) -> Vec<Gas> {
+        let mut gases: Vec<Gas> = Vec::new();
+        for cylinder in self.cylinders.iter().filter(|cylinder| cylinder.role != CylinderRole::Bailout) {
+            let gas = self.gases[cylinder.gas];
+            if !gases.contains(&gas) {
+                gases.push(gas);
+            }
+        }
+        gases
+    }
+
+    pub fn validate(&self) -> Result<(), DecoError> {
+        if self.segments.is_empty() || self.segments.iter().any(|segment| segment.gas >= self.gases.len())
+            || self.cylinders.iter().any(|cylinder| cylinder.gas >= self.gases.len()) {
+            return Err(DecoError::InvalidPlan);
+        }
+        Ok(())
+    }
+
+    pub fn simulate(&self, params: &DiveParameters, tissues: &mut [Tissue; 16], temperature: f32, interval_in_seconds: f32) -> Result<SimulationOutputs, DecoError> {
+        let mut outputs = SimulationOutputs::new();
+        self.simulate_into(params, tissues, temperature, interval_in_seconds, &mut outputs)?;
+        Ok(outputs)
+    }
+
+    /// Simulates every segment in order from the surface, handing samples and events to `sink`.
+    /// Fails before running when the plan, the parameters or the sample interval are invalid.
+    pub fn simulate_into<S: OutputSink>(&self, params: &DiveParameters, tissues: &mut [Tissue; 16], temperature: f32, interval_in_seconds: f32, sink: &mut S) -> Result<(), DecoError> {
+        self.validate()?;
+        params.validate()?;
+        if !(interval_in_seconds > 0.0 && interval_in_seconds.is_finite()) {
+            return Err(DecoError::InvalidSampleInterval);
+        }
+
+        let mut simulator: Option<Simulator> = None;
+        for (i, segment) in self.segments.iter().enumerate() {
+            let include_ascent = i == self.segments.len() - 1;
+            let gas = self.gases[segment.gas];
+            let (depth, time) = simulator.as_ref().map_or((0.0, 0.0), |simulator| (simulator.depth(), simulator.time()));
+
+            let bottom_time_seconds = segment.duration_from(depth, time, params);
+
+            match simulator.as_mut() {
+                Some(simulator) => simulator.continue_to(segment.depth, bottom_time_seconds, gas, segment.rate, include_ascent),
+                None => {
+                    let mut first = Simulator::new(*params, *tissues, self.surface_pressure, 0.0, segment.depth, temperature, interval_in_seconds, bottom_time_seconds, include_ascent);
+                    first.set_environment(self.surface_pressure, self.water, gas, segment.rate);
+                    if let Some(max_ppo2) = self.deco_ppo2 {
+                        first.set_deco_gases(&self.deco_gases(), max_ppo2);
+                    }
+                    simulator = Some(first);
+                }
+            }
+
+            if let Some(simulator) = simulator.as_mut() {
+                simulator.run_into(sink);
+            }
+        }
+
+        if let Some(simulator) = simulator {
+            *tissues = simulator.tissues;
+        }
+        Ok(())
+    }
+}
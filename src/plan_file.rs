@@ -0,0 +1,288 @@
+//! Versioned JSON dive plan file.
+//!
+//! Files are written in human units so they can be edited by hand: depths in metres, times in
+//! minutes, rates in m/min, pressures in bar and gas fractions between 0 and 1.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "gf_low": 0.3,
+//!   "gf_high": 0.85,
+//!   "conservatism": "Medium",
+//!   "descent_rate": 20.0,
+//!   "ascent_rate": 10.0,
+//!   "sac_rate": 20.0,
+//!   "deco_sac_rate": 15.0,
+//!   "altitude": 0.0,
+//!   "water": "Salt",
+//!   "gases": [{ "o2": 0.21, "he": 0.0 }, { "o2": 0.5, "he": 0.0 }],
+//!   "cylinders": [
+//!     { "volume": 12.0, "working_pressure": 232.0, "start_pressure": 200.0, "gas": 0 },
+//!     { "volume": 7.0, "working_pressure": 232.0, "start_pressure": 200.0, "gas": 1, "role": "Deco" }
+//!   ],
+//!   "segments": [
+//!     { "depth": 30.0, "duration": 20.0, "gas": 0 },
+//!     { "depth": 21.0, "runtime": 35.0, "gas": 1, "rate": 5.0 }
+//!   ]
+//! }
+//! ```
+//!
+//! * `version`: schema version, files without one are treated as version 0, the format the GUI
+//!   planner wrote before this schema existed
+//! * `conservatism`: optional `Low`, `Medium` or `High`, replaces `gf_low` and `gf_high` with the
+//!   gradient factors of the preset
+//! * `deco_sac_rate`: optional SAC on the ascent and at stops, defaults to `sac_rate`
+//! * `cylinders[].role`: optional `BackGas`, `Stage`, `Deco` or `Bailout`, defaults to `BackGas`
+//! * `cylinders[].reserve`: optional pressure to keep in the cylinder, defaults to 50 bar
+//! * `surface_pressure`: optional, overrides the pressure derived from `altitude`
+//! * `segments[].duration` is the time at depth, `segments[].runtime` the dive time at which the
+//!   segment is left; exactly one of them is expected, `duration` wins if both are present
+//! * `segments[].rate`: optional speed to reach the segment depth, defaults to the descent or
+//!   ascent rate
+//! * `gas` fields index into `gases`
+//!
+//! Older versions are migrated on load, files are always written in the current version.
+
+use core::fmt;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::conservatism::Conservatism;
+use crate::gas::{Cylinder, CylinderRole, DEFAULT_RESERVE};
+use crate::plan::{surface_pressure_at_altitude, DivePlan, Segment, SegmentTime, WaterType};
+use crate::{DecoError, DiveParameters, Gas};
+
+pub const PLAN_FILE_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum PlanFileError {
+    Json(serde_json::Error),
+    /// The file was written by a newer version of the library
+    UnsupportedVersion(u32),
+}
+
+impl From<serde_json::Error> for PlanFileError {
+    fn from(error: serde_json::Error) -> Self {
+        PlanFileError::Json(error)
+    }
+}
+
+impl fmt::Display for PlanFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanFileError::Json(error) => write!(f, "invalid plan file: {}", error),
+            PlanFileError::UnsupportedVersion(version) => write!(f, "unsupported plan file version {} (newest supported is {})", version, PLAN_FILE_VERSION),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlanGas {
+    pub o2: f32,
+    #[serde(default)]
+    pub he: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlanCylinder {
+    pub volume: f32,            // L water volume
+    pub working_pressure: f32,  // bar
+    pub start_pressure: f32,    // bar
+    pub gas: usize,
+    #[serde(default)]
+    pub role: CylinderRole,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reserve: Option<f32>,   // bar
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlanSegment {
+    pub depth: f32,             // m
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f32>,  // min at depth
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<f32>,   // min since the start of the dive
+    #[serde(default)]
+    pub gas: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<f32>,      // m/min
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanFile {
+    pub version: u32,
+    pub gf_low: f32,
+    pub gf_high: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conservatism: Option<Conservatism>,
+    #[serde(default = "default_descent_rate")]
+    pub descent_rate: f32,      // m/min
+    #[serde(default = "default_ascent_rate")]
+    pub ascent_rate: f32,       // m/min
+    #[serde(default = "default_sac_rate")]
+    pub sac_rate: f32,          // L/min
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deco_sac_rate: Option<f32>,     // L/min
+    #[serde(default)]
+    pub altitude: f32,          // m above sea level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub surface_pressure: Option<f32>,  // bar
+    #[serde(default)]
+    pub water: WaterType,
+    pub gases: Vec<PlanGas>,
+    #[serde(default)]
+    pub cylinders: Vec<PlanCylinder>,
+    pub segments: Vec<PlanSegment>,
+}
+
+fn default_descent_rate() -> f32 {
+    DiveParameters::default().descent_speed * 60.0
+}
+
+fn default_ascent_rate() -> f32 {
+    DiveParameters::default().ascent_speed * 60.0
+}
+
+fn default_sac_rate() -> f32 {
+    DiveParameters::default().sac_rate
+}
+
+/// Version 0, written by the GUI planner: air only, no version field
+#[derive(Deserialize)]
+struct PlanFileV0 {
+    gf_low: f32,
+    gf_high: f32,
+    surface_pressure: f32,
+    descent_speed: f32,         // m/min
+    ascent_speed: f32,          // m/min
+    dive_steps: Vec<DiveStepV0>,
+}
+
+#[derive(Deserialize)]
+struct DiveStepV0 {
+    depth: f32,
+    duration: f32,              // min
+}
+
+impl From<PlanFileV0> for PlanFile {
+    fn from(old: PlanFileV0) -> Self {
+        PlanFile {
+            version: PLAN_FILE_VERSION,
+            gf_low: old.gf_low,
+            gf_high: old.gf_high,
+            conservatism: None,
+            descent_rate: old.descent_speed,
+            ascent_rate: old.ascent_speed,
+            sac_rate: default_sac_rate(),
+            deco_sac_rate: None,
+            altitude: 0.0,
+            surface_pressure: Some(old.surface_pressure),
+            water: WaterType::Salt,
+            gases: Vec::from([PlanGas { o2: Gas::air().o2(), he: Gas::air().he }]),
+            cylinders: Vec::new(),
+            segments: old.dive_steps.iter()
+                .map(|step| PlanSegment { depth: step.depth, duration: Some(step.duration), runtime: None, gas: 0, rate: None })
+                .collect(),
+        }
+    }
+}
+
+impl PlanFile {
+    /// Parse a plan file of any supported version, migrating it to the current one.
+    pub fn from_json(json: &str) -> Result<Self, PlanFileError> {
+        let value: Value = serde_json::from_str(json)?;
+        let version = value.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+
+        match version {
+            0 => Ok(PlanFile::from(serde_json::from_value::<PlanFileV0>(value)?)),
+            PLAN_FILE_VERSION => Ok(serde_json::from_value(value)?),
+            _ => Err(PlanFileError::UnsupportedVersion(version)),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, PlanFileError> {
+        let current = PlanFile { version: PLAN_FILE_VERSION, ..self.clone() };
+        Ok(serde_json::to_string_pretty(&current)?)
+    }
+
+    /// Describe an existing plan.
+    pub fn from_plan(plan: &DivePlan, params: &DiveParameters) -> Self {
+        PlanFile {
+            version: PLAN_FILE_VERSION,
+            gf_low: params.gf_low,
+            gf_high: params.gf_high,
+            conservatism: Some(params.conservatism).filter(|conservatism| !matches!(conservatism, Conservatism::Custom { .. })),
+            descent_rate: params.descent_speed * 60.0,
+            ascent_rate: params.ascent_speed * 60.0,
+            sac_rate: params.sac_rate,
+            deco_sac_rate: Some(params.deco_sac_rate),
+            altitude: 0.0,
+            surface_pressure: Some(plan.surface_pressure),
+            water: plan.water,
+            gases: plan.gases.iter().map(|gas| PlanGas { o2: gas.o2(), he: gas.he }).collect(),
+            cylinders: plan.cylinders.iter()
+                .map(|cylinder| PlanCylinder {
+                    volume: cylinder.volume,
+                    working_pressure: cylinder.working_pressure,
+                    start_pressure: cylinder.start_pressure,
+                    gas: cylinder.gas,
+                    role: cylinder.role,
+                    reserve: Some(cylinder.reserve),
+                })
+                .collect(),
+            segments: plan.segments.iter()
+                .map(|segment| {
+                    let (duration, runtime) = match segment.time {
+                        SegmentTime::Duration(seconds) => (Some(seconds / 60.0), None),
+                        SegmentTime::Runtime(seconds) => (None, Some(seconds / 60.0)),
+                    };
+                    PlanSegment { depth: segment.depth, duration, runtime, gas: segment.gas, rate: segment.rate.map(|rate| rate * 60.0) }
+                })
+                .collect(),
+        }
+    }
+
+    /// Surface pressure from the explicit value or the altitude (bar)
+    pub fn surface_pressure(&self) -> f32 {
+        self.surface_pressure.unwrap_or_else(|| surface_pressure_at_altitude(self.altitude))
+    }
+
+    pub fn dive_parameters(&self) -> DiveParameters {
+        let mut params = DiveParameters::new(self.gf_high, self.gf_low);
+        if let Some(conservatism) = self.conservatism {
+            params.set_conservatism(conservatism);
+        }
+        params.descent_speed = self.descent_rate / 60.0;
+        params.ascent_speed = self.ascent_rate / 60.0;
+        params.sac_rate = self.sac_rate;
+        params.deco_sac_rate = self.deco_sac_rate.unwrap_or(self.sac_rate);
+        params
+    }
+
+    pub fn dive_plan(&self) -> Result<DivePlan, DecoError> {
+        let mut plan = DivePlan::new(self.surface_pressure(), self.water);
+        for gas in self.gases.iter() {
+            plan.add_gas(Gas::new(gas.o2, gas.he));
+        }
+        for cylinder in self.cylinders.iter() {
+            plan.add_cylinder(Cylinder {
+                role: cylinder.role,
+                reserve: cylinder.reserve.unwrap_or(DEFAULT_RESERVE),
+                ..Cylinder::new(cylinder.volume, cylinder.working_pressure, cylinder.start_pressure, cylinder.gas)
+            });
+        }
+        for segment in self.segments.iter() {
+            let time = match (segment.duration, segment.runtime) {
+                (Some(duration), _) => SegmentTime::Duration(duration * 60.0),
+                (None, Some(runtime)) => SegmentTime::Runtime(runtime * 60.0),
+                (None, None) => return Err(DecoError::InvalidPlan),
+            };
+            plan.add_segment(Segment { rate: segment.rate.map(|rate| rate / 60.0), ..Segment::new(segment.depth, time, segment.gas) });
+        }
+        plan.validate()?;
+        Ok(plan)
+    }
+}
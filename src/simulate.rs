@@ -1,48 +1,1022 @@
-#[cfg(feature="std")]
-use std::println;
-use crate::DiveParameters;
+#[cfg(feature = "trace")]
+use std::eprintln;
+use crate::alarm::AlarmKind;
+use crate::plan::WaterType;
+use crate::{check_input, check_tissues, DecoError, DiveParameters, Gas, Input};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use crate::tissue::{calculate_tissue, Tissue};
+use crate::tissue::{Tissue, TissueUpdater};
 
-#[cfg(all(feature = "serde", feature = "std"))]
-use std::vec::Vec;
-#[cfg(all(feature = "serde", not(feature = "std")))]
+use crate::ceiling::{round_to_stop, GfSlope};
+use crate::ndl::ndl_with_limit;
+use crate::stop::time_to_ceiling;
+#[cfg(feature = "alloc")]
+use crate::alarm::{AlarmInputs, AlarmMonitor};
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 
-#[cfg(feature = "serde")]
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use defmt::Format;
+
+#[derive(Debug, Format, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DivePhase {
+    Descent,
+    Bottom,
+    Ascent,
+    Stop,
+    Surface,
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SimulationEventKind {
+    PhaseChange(DivePhase),
+    StopArrival,
+    StopDeparture { duration: f32 },        // s spent at the stop
+    /// Emitted by multi-gas profiles when the breathing gas changes
+    GasSwitch { o2: f32, he: f32 },
+    Alarm(AlarmKind),
+    /// The diver went shallower than the ceiling
+    CeilingViolation { ceiling: f32 },      // m
+    /// A stop was cut short by the 20 minute per-stop safety limit
+    StopTimeLimit,
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SimulationEvent {
+    pub time: f32,      // s since the start of the simulation
+    pub depth: f32,     // m
+    pub kind: SimulationEventKind,
+}
+
+#[derive(Debug, Format, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RuntimeKind {
+    /// Planned time at a level
+    Level,
+    /// Decompression stop
+    Stop,
+    Surface,
+}
+
+/// One line of a runtime table.
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RuntimeEntry {
+    pub kind: RuntimeKind,
+    pub depth: f32,     // m
+    pub duration: f32,  // s at depth
+    pub runtime: f32,   // s since the start of the dive when the depth is left
+    pub gas: Gas,
+}
+
+/// One recorded point of a simulated profile.
+#[derive(Debug, Format, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SimulationSample {
+    pub time: f32,                      // s since the start of the simulation
+    pub depth: f32,                     // m
+    pub pressure: f32,                  // bar
+    pub tissues: [Tissue; 16],
+    pub ceiling: u32,                   // m, GF ceiling rounded to stop depths
+    pub exact_ceiling: f32,             // m, unrounded GF ceiling
+    pub controlling_compartment: usize,
+    pub ndl: Option<f32>,               // min, None while in deco
+    pub gradient_factor: f32,           // GF applied to the controlling compartment
+    pub gas: Gas,                       // breathing gas
+}
+
+/// Receives samples and events while a simulation runs. Implemented by [`SimulationOutputs`]
+/// with the `alloc` feature, by [`BufferSink`] and [`CallbackSink`] for allocation-free builds,
+/// and by `()` to discard everything and only advance the tissues.
+pub trait OutputSink {
+    fn record(&mut self, sample: &SimulationSample);
+    fn event(&mut self, event: &SimulationEvent);
+}
+
+impl OutputSink for () {
+    fn record(&mut self, _sample: &SimulationSample) {}
+    fn event(&mut self, _event: &SimulationEvent) {}
+}
+
+/// Writes into caller-provided fixed-capacity buffers. Items that do not fit are counted, not stored.
+pub struct BufferSink<'a> {
+    samples: &'a mut [SimulationSample],
+    events: &'a mut [SimulationEvent],
+    sample_count: usize,
+    event_count: usize,
+    pub dropped_samples: usize,
+    pub dropped_events: usize,
+}
+
+impl<'a> BufferSink<'a> {
+    pub fn new(samples: &'a mut [SimulationSample], events: &'a mut [SimulationEvent]) -> Self {
+        BufferSink {
+            samples,
+            events,
+            sample_count: 0,
+            event_count: 0,
+            dropped_samples: 0,
+            dropped_events: 0,
+        }
+    }
+
+    pub fn samples(&self) -> &[SimulationSample] {
+        &self.samples[..self.sample_count]
+    }
+
+    pub fn events(&self) -> &[SimulationEvent] {
+        &self.events[..self.event_count]
+    }
+}
+
+impl OutputSink for BufferSink<'_> {
+    fn record(&mut self, sample: &SimulationSample) {
+        match self.samples.get_mut(self.sample_count) {
+            Some(slot) => {
+                *slot = *sample;
+                self.sample_count += 1;
+            }
+            None => self.dropped_samples += 1,
+        }
+    }
+
+    fn event(&mut self, event: &SimulationEvent) {
+        match self.events.get_mut(self.event_count) {
+            Some(slot) => {
+                *slot = *event;
+                self.event_count += 1;
+            }
+            None => self.dropped_events += 1,
+        }
+    }
+}
+
+/// Forwards samples and events to two closures, e.g. to stream them to flash or a display.
+pub struct CallbackSink<S, E> {
+    pub on_sample: S,
+    pub on_event: E,
+}
+
+impl<S: FnMut(&SimulationSample), E: FnMut(&SimulationEvent)> OutputSink for CallbackSink<S, E> {
+    fn record(&mut self, sample: &SimulationSample) {
+        (self.on_sample)(sample);
+    }
+
+    fn event(&mut self, event: &SimulationEvent) {
+        (self.on_event)(event);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SimulationOutputs {
+    pub times: Vec<f32>,        // s since the start of the simulation, one per sample
     pub depths: Vec<f32>,
     pub pressures: Vec<f32>,
     pub tissues_per_interval: Vec<[Tissue; 16]>,
+    pub ceilings: Vec<u32>,                     // m, GF ceiling rounded to stop depths
+    pub exact_ceilings: Vec<f32>,               // m, unrounded GF ceiling
+    pub controlling_compartments: Vec<usize>,
+    pub ndls: Vec<Option<f32>>,                 // min, None while in deco
+    pub gradient_factors: Vec<f32>,             // GF applied to the controlling compartment
+    pub gases: Vec<Gas>,
+    pub events: Vec<SimulationEvent>,
 }
 
-#[cfg(not(feature = "serde"))]
-use defmt::Format;
-#[cfg(not(feature = "serde"))]
-#[derive(Debug, Format, Clone, Copy)]
-pub struct SimulationOutputs {}
-
-#[cfg(feature = "serde")]
+#[cfg(feature = "alloc")]
 impl SimulationOutputs {
     pub fn new() -> Self {
         Self {
+            times: Vec::new(),
             depths: Vec::new(),
             pressures: Vec::new(),
             tissues_per_interval: Vec::new(),
+            ceilings: Vec::new(),
+            exact_ceilings: Vec::new(),
+            controlling_compartments: Vec::new(),
+            ndls: Vec::new(),
+            gradient_factors: Vec::new(),
+            gases: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Decompression stops as (depth, arrival time, duration), taken from the event log.
+    pub fn stops(&self) -> Vec<(f32, f32, f32)> {
+        let mut stops = Vec::new();
+        let mut arrival = None;
+        for event in self.events.iter() {
+            match event.kind {
+                SimulationEventKind::StopArrival => arrival = Some(event.time),
+                SimulationEventKind::StopDeparture { duration } => {
+                    if let Some(time) = arrival.take() {
+                        stops.push((event.depth, time, duration));
+                    }
+                }
+                _ => {}
+            }
         }
+        stops
+    }
+
+    /// Levels, stops and the surfacing time in order, taken from the event log.
+    pub fn runtime_table(&self) -> Vec<RuntimeEntry> {
+        let mut table = Vec::new();
+        let mut gas = self.gases.first().copied().unwrap_or_default();
+        let mut open: Option<(RuntimeKind, f32, f32)> = None; // kind, depth, arrival
+
+        for event in self.events.iter() {
+            match event.kind {
+                SimulationEventKind::PhaseChange(DivePhase::Bottom) => open = Some((RuntimeKind::Level, event.depth, event.time)),
+                SimulationEventKind::StopArrival => open = Some((RuntimeKind::Stop, event.depth, event.time)),
+                SimulationEventKind::PhaseChange(DivePhase::Descent | DivePhase::Ascent) | SimulationEventKind::StopDeparture { .. } => {
+                    if let Some((kind, depth, arrival)) = open.take() {
+                        table.push(RuntimeEntry { kind, depth, duration: event.time - arrival, runtime: event.time, gas });
+                    }
+                }
+                SimulationEventKind::PhaseChange(DivePhase::Surface) => {
+                    table.push(RuntimeEntry { kind: RuntimeKind::Surface, depth: 0.0, duration: 0.0, runtime: event.time, gas });
+                }
+                SimulationEventKind::GasSwitch { o2, he } => gas = Gas::new(o2, he),
+                _ => {}
+            }
+        }
+        table
+    }
+
+    /// Append another simulation that continues this one, shifting its time axis.
+    pub fn append(&mut self, mut other: SimulationOutputs) {
+        let offset = self.times.last().copied().unwrap_or(0.0);
+        self.times.extend(other.times.iter().map(|time| time + offset));
+        self.depths.append(&mut other.depths);
+        self.pressures.append(&mut other.pressures);
+        self.tissues_per_interval.append(&mut other.tissues_per_interval);
+        self.ceilings.append(&mut other.ceilings);
+        self.exact_ceilings.append(&mut other.exact_ceilings);
+        self.controlling_compartments.append(&mut other.controlling_compartments);
+        self.ndls.append(&mut other.ndls);
+        self.gradient_factors.append(&mut other.gradient_factors);
+        self.gases.append(&mut other.gases);
+        self.events.extend(other.events.iter().map(|event| SimulationEvent { time: event.time + offset, ..*event }));
+    }
+
+    /// Replay the recorded samples through an alarm monitor and merge the raised alarms into the event log.
+    pub fn record_alarms(&mut self, monitor: &mut AlarmMonitor) {
+        let mut alarms = Vec::new();
+        for i in 0..self.times.len() {
+            let ascent_rate = if i > 0 && self.times[i] > self.times[i - 1] {
+                (self.depths[i - 1] - self.depths[i]) / (self.times[i] - self.times[i - 1]) * 60.0
+            } else {
+                0.0
+            };
+            let inputs = AlarmInputs {
+                depth: self.depths[i],
+                dive_time: self.times[i],
+                ndl: self.ndls[i],
+                ceiling: self.exact_ceilings[i],
+                ascent_rate,
+                ppo2: self.pressures[i] * self.gases[i].o2(),
+                cns: 0.0,
+                gas_pressure: None,
+            };
+            for kind in monitor.evaluate(&inputs).iter() {
+                alarms.push(SimulationEvent { time: self.times[i], depth: self.depths[i], kind: SimulationEventKind::Alarm(kind) });
+            }
+        }
+        self.events.extend(alarms);
+        self.events.sort_by(|a, b| a.time.total_cmp(&b.time));
     }
 }
 
-#[cfg(not(feature = "serde"))]
-impl SimulationOutputs {
-    pub fn new() -> Self {
-        Self {}
+#[cfg(feature = "alloc")]
+impl OutputSink for SimulationOutputs {
+    fn record(&mut self, sample: &SimulationSample) {
+        self.times.push(sample.time);
+        self.depths.push(sample.depth);
+        self.pressures.push(sample.pressure);
+        self.tissues_per_interval.push(sample.tissues);
+        self.ceilings.push(sample.ceiling);
+        self.exact_ceilings.push(sample.exact_ceiling);
+        self.controlling_compartments.push(sample.controlling_compartment);
+        self.ndls.push(sample.ndl);
+        self.gradient_factors.push(sample.gradient_factor);
+        self.gases.push(sample.gas);
+    }
+
+    fn event(&mut self, event: &SimulationEvent) {
+        self.events.push(*event);
+    }
+}
+
+/// Recorded NDLs are capped here, like a dive computer display
+pub const NDL_LIMIT_MINUTES: u32 = 99;
+
+/// Internal time step of the simulator (s)
+const INTERNAL_STEP: f32 = 1.0;
+
+/// Bottom time left over below this is done; late in a dive it is below the resolution of the
+/// dive time and adding it would not move the clock (s)
+const MIN_STEP: f32 = 1e-3;
+
+/// Longest time at one stop before the ascent continues regardless of the ceiling (s)
+const STOP_TIME_LIMIT: f32 = 20.0 * 60.0;
+
+/// Safety counter to prevent infinite loops
+const MAX_ITERATIONS: u32 = 50000000; // Increased limit for ascent phase
+
+/// Most records one `advance` produces: at a stop a gas switch, a ceiling violation, a sample,
+/// the stop time limit and the stop departure with its phase change
+const MAX_ADVANCE_RECORDS: usize = 6;
+
+/// Most gases the final ascent can switch to, see `Simulator::set_deco_gases`
+pub const MAX_DECO_GASES: usize = 8;
+
+/// Records of `start`: the first sample and phase
+const MAX_START_RECORDS: usize = 2;
+
+/// Records of `continue_to`: the phase change and a gas switch. A `continue_to` the simulator has
+/// not run yet is replaced by the next one, records included.
+const MAX_CONTINUE_RECORDS: usize = 2;
+
+/// `advance` and `start` only run on an empty queue, `continue_to` can add its records to what is
+/// left of them
+const QUEUE_CAPACITY: usize = 8;
+
+const _: () = assert!(QUEUE_CAPACITY >= MAX_ADVANCE_RECORDS + MAX_CONTINUE_RECORDS);
+const _: () = assert!(QUEUE_CAPACITY >= MAX_START_RECORDS + MAX_CONTINUE_RECORDS);
+
+/// Either a sample or an event, in the order the simulator produced them.
+#[derive(Debug, Format, Copy, Clone)]
+pub enum SimulationRecord {
+    Sample(SimulationSample),
+    Event(SimulationEvent),
+}
+
+#[derive(Debug, Copy, Clone)]
+struct RecordQueue {
+    items: [Option<SimulationRecord>; QUEUE_CAPACITY],
+    head: usize,
+    len: usize,
+}
+
+impl RecordQueue {
+    fn new() -> Self {
+        RecordQueue {
+            items: [None; QUEUE_CAPACITY],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Never full, the record counts above bound what one call can queue
+    fn push(&mut self, record: SimulationRecord) {
+        debug_assert!(self.len < QUEUE_CAPACITY, "simulator step produced too many records");
+        self.items[(self.head + self.len) % QUEUE_CAPACITY] = Some(record);
+        self.len += 1;
+    }
+
+    /// Forget the `count` most recently pushed records
+    fn drop_newest(&mut self, count: usize) {
+        for _ in 0..count.min(self.len) {
+            self.len -= 1;
+            self.items[(self.head + self.len) % QUEUE_CAPACITY] = None;
+        }
+    }
+
+    fn pop(&mut self) -> Option<SimulationRecord> {
+        if self.len == 0 {
+            return None;
+        }
+        let record = self.items[self.head].take();
+        self.head = (self.head + 1) % QUEUE_CAPACITY;
+        self.len -= 1;
+        record
+    }
+}
+
+/// Step-by-step dive simulation: descent or transition to the target depth, bottom time, then an
+/// optional ascent with decompression stops. Records are produced lazily through the `Iterator`
+/// implementation, so callers can stop early, downsample or stream them without collecting.
+/// Further levels can be chained with `continue_to` once a segment without ascent has finished.
+#[derive(Debug, Copy, Clone)]
+pub struct Simulator {
+    pub params: DiveParameters,
+    pub tissues: [Tissue; 16],
+    surface_pressure: f32,
+    water: WaterType,
+    gas: Gas,
+    transition_speed: Option<f32>,  // m/s, overrides the dive parameters to reach the target depth
+    temperature: f32,
+    interval_in_seconds: f32,
+    target_depth: f32,
+    bottom_time_seconds: f32,
+    include_ascent: bool,
+
+    depth: f32,
+    amb_pressure: f32,
+    dive_time: f32,
+    descending: bool,
+    bottom: bool,
+    bottom_start: f32,
+    ascending: bool,
+    transitioning: bool, // going from deeper to shallower
+    at_deco_stop: bool,
+    current_deco_depth: f32,
+    deco_stop_time: f32,
+    stop_clear_in: f32,             // s at the stop until the ceiling is predicted to clear
+    first_stop_pressure: Option<f32>,   // bar, GF low is anchored here for the ascent
+    accumulated_short_stop_time: f32,
+    in_violation: bool,
+    output_accumulator: f32,
+    iteration_count: u32,
+    updater: TissueUpdater,         // decay factors of the last step
+    started: bool,
+    finished: bool,
+    queue: RecordQueue,
+    continued: usize,               // records of the last `continue_to` still queued
+    gas_before_continue: Gas,
+    pending_gas: Option<Gas>,       // switched to on arriving at the target of an ascent
+    deco_gases: [Gas; MAX_DECO_GASES],
+    deco_gas_count: usize,
+    deco_ppo2: f32,                 // bar, highest ppO2 a deco gas is switched to at
+}
+
+impl Simulator {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        params: DiveParameters,
+        tissues: [Tissue; 16],
+        starting_ambient_pressure: f32,
+        starting_depth: f32,
+        target_depth: f32,
+        temperature: f32,
+        interval_in_seconds: f32,
+        bottom_time_seconds: f32,
+        include_ascent: bool,
+    ) -> Self {
+        Simulator {
+            params,
+            tissues,
+            surface_pressure: 1.0,  // starting_ambient_pressure only describes the first sample
+            water: WaterType::Salt,
+            gas: Gas::air(),
+            transition_speed: None,
+            temperature,
+            interval_in_seconds,
+            target_depth,
+            bottom_time_seconds,
+            include_ascent,
+            depth: starting_depth,
+            amb_pressure: starting_depth / 10.0 + starting_ambient_pressure,
+            dive_time: 0.0,
+            descending: starting_depth < target_depth,
+            bottom: starting_depth == target_depth,
+            bottom_start: 0.0,
+            ascending: false,
+            transitioning: starting_depth > target_depth,
+            at_deco_stop: false,
+            current_deco_depth: 0.0,
+            deco_stop_time: 0.0,
+            stop_clear_in: 0.0,
+            first_stop_pressure: None,
+            accumulated_short_stop_time: 0.0,
+            in_violation: false,
+            output_accumulator: 0.0,
+            iteration_count: 0,
+            updater: TissueUpdater::default(),
+            started: false,
+            finished: false,
+            queue: RecordQueue::new(),
+            continued: 0,
+            gas_before_continue: Gas::air(),
+            pending_gas: None,
+            deco_gases: [Gas::air(); MAX_DECO_GASES],
+            deco_gas_count: 0,
+            deco_ppo2: 0.0,
+        }
+    }
+
+    /// Surface pressure, water type, breathing gas and transition speed for the first segment.
+    /// Only takes effect before the first record has been taken.
+    pub fn set_environment(&mut self, surface_pressure: f32, water: WaterType, gas: Gas, transition_speed: Option<f32>) {
+        if self.started {
+            return;
+        }
+        self.surface_pressure = surface_pressure;
+        self.gas = gas;
+        self.water = water;
+        self.transition_speed = transition_speed;
+        self.amb_pressure = self.pressure_at(self.depth);
+    }
+
+    /// Chain another level to a finished segment: move to `target_depth` at `transition_speed`
+    /// (or the dive parameters' speeds), stay for `bottom_time_seconds`, then optionally ascend.
+    /// Tissues, time and recording interval carry on from the previous segment. Going down the
+    /// diver switches to `gas` straight away, going up only on arriving at `target_depth`.
+    pub fn continue_to(&mut self, target_depth: f32, bottom_time_seconds: f32, gas: Gas, transition_speed: Option<f32>, include_ascent: bool) {
+        self.target_depth = target_depth;
+        self.bottom_time_seconds = bottom_time_seconds;
+        self.transition_speed = transition_speed;
+        self.include_ascent = include_ascent;
+        self.descending = self.depth < target_depth;
+        self.transitioning = self.depth > target_depth;
+        self.bottom = self.depth == target_depth;
+        self.bottom_start = self.dive_time;
+        self.ascending = false;
+        self.at_deco_stop = false;
+        if self.descending {
+            // a new bottom, the next ascent finds its own deepest stop
+            self.first_stop_pressure = None;
+        }
+        self.finished = false;
+
+        if !self.started {
+            self.gas = gas;
+            return;
+        }
+        // replacing a continue_to that never ran replaces its records too
+        if self.continued == 0 {
+            self.gas_before_continue = self.gas;
+        } else {
+            self.queue.drop_newest(self.continued);
+            self.gas = self.gas_before_continue;
+        }
+        self.pending_gas = None;
+        let queued = self.queue.len;
+        self.record_phase();
+        if self.transitioning {
+            // the new gas may be too rich for the depth the ascent starts from
+            self.pending_gas = Some(gas).filter(|&gas| gas != self.gas);
+        } else {
+            self.switch_gas(gas);
+        }
+        self.continued = self.queue.len - queued;
+    }
+
+    /// Gases the final ascent switches to by their MOD: whenever one of them is richer than the
+    /// gas breathed and its ppO2 at the current depth is at most `max_ppo2`, the richest such gas
+    /// is switched to. Only the first `MAX_DECO_GASES` are kept, none switches off the automatic
+    /// switching.
+    pub fn set_deco_gases(&mut self, gases: &[Gas], max_ppo2: f32) {
+        self.deco_gas_count = gases.len().min(MAX_DECO_GASES);
+        self.deco_gases[..self.deco_gas_count].copy_from_slice(&gases[..self.deco_gas_count]);
+        self.deco_ppo2 = max_ppo2;
+    }
+
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+
+    fn switch_gas(&mut self, gas: Gas) {
+        if gas != self.gas {
+            self.record_event(SimulationEventKind::GasSwitch { o2: gas.o2(), he: gas.he });
+        }
+        self.gas = gas;
+    }
+
+    /// Seconds since the start of the simulation
+    pub fn time(&self) -> f32 {
+        self.dive_time
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished && self.queue.len == 0
+    }
+
+    /// The run was stopped at the iteration limit rather than at the surface or the target
+    pub(crate) fn hit_iteration_limit(&self) -> bool {
+        self.iteration_count >= MAX_ITERATIONS
+    }
+
+    pub fn gas(&self) -> Gas {
+        self.gas
+    }
+
+    /// Snapshot of the current state, independent of the recording interval.
+    pub fn sample(&self) -> SimulationSample {
+        // same ceiling the simulator uses for its stop decisions
+        let (exact_ceiling, controlling_compartment, gf) = self.exact_ceiling();
+        let ceiling = round_to_stop(exact_ceiling);
+        let ndl = if ceiling == 0 {
+            ndl_with_limit(&self.gf_slope(), &self.tissues, self.amb_pressure, self.temperature, self.gas, NDL_LIMIT_MINUTES)
+        } else {
+            None
+        };
+
+        SimulationSample {
+            time: self.dive_time,
+            depth: self.depth,
+            pressure: self.amb_pressure,
+            tissues: self.tissues,
+            ceiling,
+            exact_ceiling,
+            controlling_compartment,
+            ndl,
+            gradient_factor: gf,
+            gas: self.gas,
+        }
+    }
+
+    fn pressure_at(&self, depth: f32) -> f32 {
+        self.surface_pressure + depth / self.water.metres_per_bar()
+    }
+
+    /// Gradient factors of the dive, anchored at the deepest stop once the ascent has started
+    pub fn gf_slope(&self) -> GfSlope {
+        GfSlope { first_stop_pressure: self.first_stop_pressure, ..GfSlope::new(self.params.gf_low, self.params.gf_high, self.surface_pressure) }
+    }
+
+    /// Keep GF low at the deepest stop from the first ascent with a stop on
+    fn anchor_gf_slope(&mut self) {
+        if self.first_stop_pressure.is_none() {
+            let mut slope = self.gf_slope();
+            slope.anchor(&self.tissues);
+            self.first_stop_pressure = slope.first_stop_pressure;
+        }
+    }
+
+    /// GF ceiling in metres of the simulated water, with the leading compartment and its GF
+    fn exact_ceiling(&self) -> (f32, usize, f32) {
+        let (ceiling, compartment, gf) = self.gf_slope().max_ceiling(&self.tissues);
+        // the ceiling functions work in 10 m per bar
+        (ceiling * self.water.metres_per_bar() / 10.0, compartment, gf)
+    }
+
+    /// Ceiling rounded to stop depths
+    fn ceiling(&self) -> u32 {
+        round_to_stop(self.exact_ceiling().0)
+    }
+
+    /// Advance the simulation by `dt` seconds, forwarding every record produced on the way to
+    /// `sink`. Returns false once the simulation has finished.
+    pub fn step<S: OutputSink>(&mut self, dt: f32, sink: &mut S) -> bool {
+        self.start();
+        let end_time = self.dive_time + dt;
+        loop {
+            while let Some(record) = self.pop_record() {
+                forward(sink, &record);
+            }
+            if self.finished || self.dive_time >= end_time {
+                return !self.finished;
+            }
+            self.advance();
+        }
+    }
+
+    /// Run to completion, forwarding every record to `sink`.
+    pub fn run_into<S: OutputSink>(&mut self, sink: &mut S) {
+        for record in self.by_ref() {
+            forward(sink, &record);
+        }
+    }
+
+    fn pop_record(&mut self) -> Option<SimulationRecord> {
+        let record = self.queue.pop();
+        // the records of a continue_to are the newest, popping reaches them last
+        self.continued = self.continued.min(self.queue.len);
+        record
+    }
+
+    /// Record the initial state at the starting depth
+    fn start(&mut self) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+
+        #[cfg(feature = "trace")]
+        eprintln!("Starting dive simulation: descent -> bottom -> ascent with decompression");
+
+        self.record_output();
+        self.record_phase();
+    }
+
+    fn record_phase(&mut self) {
+        let phase = if self.descending {
+            DivePhase::Descent
+        } else if self.transitioning {
+            DivePhase::Ascent
+        } else {
+            DivePhase::Bottom
+        };
+        self.record_event(SimulationEventKind::PhaseChange(phase));
+    }
+
+    fn record_output(&mut self) {
+        let sample = self.sample();
+        self.queue.push(SimulationRecord::Sample(sample));
+    }
+
+    fn record_event(&mut self, kind: SimulationEventKind) {
+        self.record_event_at(self.dive_time, self.depth, kind);
+    }
+
+    fn record_event_at(&mut self, time: f32, depth: f32, kind: SimulationEventKind) {
+        self.queue.push(SimulationRecord::Event(SimulationEvent { time, depth, kind }));
+    }
+
+    fn record_stop_arrival(&mut self, time: f32) {
+        self.record_event_at(time, self.depth, SimulationEventKind::PhaseChange(DivePhase::Stop));
+        self.record_event_at(time, self.depth, SimulationEventKind::StopArrival);
+    }
+
+    fn record_stop_departure(&mut self) {
+        let duration = self.deco_stop_time;
+        self.record_event_at(self.dive_time, self.current_deco_depth, SimulationEventKind::StopDeparture { duration });
+        self.record_event_at(self.dive_time, self.current_deco_depth, SimulationEventKind::PhaseChange(DivePhase::Ascent));
+    }
+
+    fn check_violation(&mut self, ceiling: u32) {
+        let violated = ceiling as f32 > self.depth;
+        if violated && !self.in_violation {
+            self.record_event(SimulationEventKind::CeilingViolation { ceiling: ceiling as f32 });
+        }
+        self.in_violation = violated;
+    }
+
+    /// Update the tissues for `step` seconds at the current depth and record a sample when the
+    /// output interval has elapsed.
+    fn spend(&mut self, step: f32) {
+        self.amb_pressure = self.pressure_at(self.depth);
+        if self.updater.minutes() != step / 60.0 {
+            self.updater = TissueUpdater::new(step / 60.0);
+        }
+        self.updater.update_all(&mut self.tissues, self.amb_pressure, self.temperature, self.gas);
+
+        self.dive_time += step;
+        self.output_accumulator += step;
+
+        if self.output_accumulator >= self.interval_in_seconds {
+            self.record_output();
+            self.output_accumulator -= self.interval_in_seconds;
+        }
+    }
+
+    /// One iteration of the simulation state machine.
+    fn advance(&mut self) {
+        self.iteration_count += 1;
+
+        // Safety check to prevent infinite loops
+        if self.iteration_count >= MAX_ITERATIONS {
+            #[cfg(feature = "trace")]
+            eprintln!("⚠️  Warning: Simulation reached maximum iterations ({}). Stopping simulation.", MAX_ITERATIONS);
+            self.finish();
+            return;
+        }
+
+        if self.descending {
+            // DESCENT PHASE
+            let speed = self.transition_speed.unwrap_or(self.params.descent_speed);
+            let remaining_depth = self.target_depth - self.depth;
+            let time_to_target = remaining_depth / speed;
+            let step = INTERNAL_STEP.min(time_to_target);
+
+            self.depth += speed * step;
+            self.spend(step);
+
+            if self.depth >= self.target_depth {
+                #[cfg(feature = "trace")]
+                eprintln!("Reached target depth: {}m after {} seconds", self.target_depth, self.dive_time);
+                self.descending = false;
+                self.bottom = true;
+                self.bottom_start = self.dive_time;
+                self.record_event(SimulationEventKind::PhaseChange(DivePhase::Bottom));
+            }
+        } else if self.transitioning {
+            // TRANSITION PHASE - going from deeper to shallower depth
+            self.anchor_gf_slope();
+            let speed = self.transition_speed.unwrap_or(self.params.ascent_speed);
+            let remaining_depth = self.depth - self.target_depth;
+            let time_to_target = remaining_depth / speed;
+            let step = INTERNAL_STEP.min(time_to_target);
+
+            self.depth -= speed * step;
+            self.spend(step);
+
+            // the transition ignores the ceiling, flag it when the diver goes above it
+            let current_ceiling = self.ceiling();
+            self.check_violation(current_ceiling);
+
+            if self.depth <= self.target_depth {
+                #[cfg(feature = "trace")]
+                eprintln!("Reached target depth: {}m after {} seconds (transitioning)", self.target_depth, self.dive_time);
+                self.transitioning = false;
+                self.bottom = true;
+                self.bottom_start = self.dive_time;
+                self.record_event(SimulationEventKind::PhaseChange(DivePhase::Bottom));
+                if let Some(gas) = self.pending_gas.take() {
+                    self.switch_gas(gas);
+                }
+            }
+        } else if self.bottom {
+            // BOTTOM PHASE
+            let remaining_bottom_time = self.bottom_time_seconds - (self.dive_time - self.bottom_start);
+
+            if remaining_bottom_time < MIN_STEP {
+                #[cfg(feature = "trace")]
+                eprintln!("Bottom time completed. Starting ascent...");
+                self.bottom = false;
+                self.ascending = true;
+                if self.include_ascent {
+                    self.record_event(SimulationEventKind::PhaseChange(DivePhase::Ascent));
+                }
+                return;
+            }
+
+            let step = INTERNAL_STEP.min(remaining_bottom_time);
+            self.depth = self.target_depth;
+            self.spend(step);
+        } else if self.ascending && self.include_ascent {
+            self.advance_ascent();
+        } else {
+            // End of simulation (no ascent requested)
+            self.finish();
+        }
+    }
+
+    /// Switch to the richest deco gas within its MOD when it is richer than the gas breathed
+    fn switch_to_deco_gas(&mut self) {
+        let amb_pressure = self.pressure_at(self.depth);
+        let richest = self.deco_gases[..self.deco_gas_count]
+            .iter()
+            .filter(|gas| amb_pressure * gas.o2() <= self.deco_ppo2)
+            .max_by(|a, b| a.o2().total_cmp(&b.o2()));
+        if let Some(&gas) = richest.filter(|gas| gas.o2() > self.gas.o2()) {
+            self.switch_gas(gas);
+            // the stop clears sooner on the richer gas
+            self.stop_clear_in = 0.0;
+        }
+    }
+
+    /// ASCENT PHASE WITH DECOMPRESSION STOPS
+    fn advance_ascent(&mut self) {
+        self.anchor_gf_slope();
+        self.switch_to_deco_gas();
+        let current_ceiling = self.ceiling();
+        self.check_violation(current_ceiling);
+
+        // Debug output every 10 iterations to avoid spam
+        #[cfg(feature = "trace")]
+        if self.iteration_count % 10 == 0 {
+            eprintln!("Ascent: depth {:.1}m, ceiling {:.1}m, at_deco_stop: {}", self.depth, current_ceiling, self.at_deco_stop);
+        }
+
+        if self.at_deco_stop {
+            // AT DECOMPRESSION STOP
+            self.depth = self.current_deco_depth;
+            if self.stop_clear_in <= 0.0 {
+                self.stop_clear_in = self.predict_stop_clear().max(INTERNAL_STEP);
+            }
+            // jump to the predicted clearing, stopping for every output sample and the stop time limit
+            let step = self.stop_clear_in
+                .min(self.interval_in_seconds - self.output_accumulator)
+                .min(STOP_TIME_LIMIT - self.deco_stop_time)
+                .max(MIN_STEP);
+            self.stop_clear_in -= step;
+            self.deco_stop_time += step;
+            self.spend(step);
+
+            // Check if we can leave the deco stop (ceiling has cleared)
+            let new_ceiling = self.ceiling();
+            let cleared = new_ceiling == 0 || new_ceiling as f32 + 0.5 < self.current_deco_depth;
+
+            if self.deco_stop_time >= 60.0 && cleared {
+                #[cfg(feature = "trace")]
+                eprintln!("Completed deco stop at {}m after {:.1} minutes", self.current_deco_depth, self.deco_stop_time / 60.0);
+                self.record_stop_departure();
+                self.at_deco_stop = false;
+                self.deco_stop_time = 0.0;
+            } else if self.deco_stop_time < 60.0 && cleared {
+                // This stop would be less than 1 minute - accumulate the time and move to next stop
+                self.accumulated_short_stop_time += self.deco_stop_time;
+                #[cfg(feature = "trace")]
+                eprintln!("Skipping short deco stop at {}m ({:.1}s) - adding to next stop", self.current_deco_depth, self.deco_stop_time);
+                self.record_stop_departure();
+                self.at_deco_stop = false;
+                self.deco_stop_time = 0.0;
+            }
+
+            // Safety check - max deco stop time of 20 minutes
+            if self.deco_stop_time >= STOP_TIME_LIMIT {
+                #[cfg(feature = "trace")]
+                eprintln!("⚠️  Maximum deco stop time reached at {}m. Continuing ascent.", self.current_deco_depth);
+                self.record_event(SimulationEventKind::StopTimeLimit);
+                self.record_stop_departure();
+                self.at_deco_stop = false;
+                self.deco_stop_time = 0.0;
+            }
+            return;
+        }
+
+        if current_ceiling > 0 && self.depth > current_ceiling as f32 {
+            // We need to make a deco stop
+            let deco_depth = calculate_deco_stop_depth(current_ceiling);
+            self.current_deco_depth = deco_depth;
+
+            #[cfg(feature = "trace")]
+            eprintln!("Deco stop required at {}m (ceiling: {}m)", deco_depth, current_ceiling);
+
+            if self.depth > deco_depth {
+                // Ascend to deco stop depth if we're deeper
+                let depth_to_ascend = self.depth - deco_depth;
+                let time_to_deco_depth = depth_to_ascend / self.params.ascent_speed;
+                let step = INTERNAL_STEP.min(time_to_deco_depth);
+
+                self.depth -= self.params.ascent_speed * step;
+                if self.depth <= deco_depth {
+                    self.depth = deco_depth;
+                    // Only now do we officially start the deco stop
+                    self.start_stop(self.dive_time + step);
+                }
+                self.spend(step);
+            } else {
+                // We're already at or above the deco stop depth
+                self.depth = deco_depth;
+                self.start_stop(self.dive_time);
+            }
+        } else if self.depth > 0.0 {
+            if current_ceiling == 0 {
+                // No decompression obligation - ascend directly to surface
+                let time_to_surface = self.depth / self.params.ascent_speed;
+                let step = INTERNAL_STEP.min(time_to_surface);
+
+                self.depth -= self.params.ascent_speed * step;
+                if self.depth <= 0.0 {
+                    self.depth = 0.0;
+                    #[cfg(feature = "trace")]
+                    eprintln!("Reached surface - simulation complete");
+                    self.record_event_at(self.dive_time + step, 0.0, SimulationEventKind::PhaseChange(DivePhase::Surface));
+                    self.finish();
+                    return;
+                }
+                self.spend(step);
+            } else {
+                // Ceiling constrains us - wait at current depth
+                self.spend(INTERNAL_STEP);
+            }
+        } else {
+            // We're at the surface
+            self.finish();
+        }
+    }
+
+    /// Seconds at the current stop until the rounded ceiling is above it, solved in closed form;
+    /// the time left to the stop time limit when it does not clear before it.
+    fn predict_stop_clear(&self) -> f32 {
+        let limit = STOP_TIME_LIMIT - self.deco_stop_time;
+        // the rounded ceiling is above the stop once the exact one is less than 3 m shallower,
+        // converted to the 10 m per bar of the ceiling functions
+        let next_stop = (self.current_deco_depth - 2.999) * 10.0 / self.water.metres_per_bar();
+        time_to_ceiling(&self.gf_slope(), &self.tissues, self.pressure_at(self.current_deco_depth), self.temperature, self.gas, next_stop, limit.max(0.0) as u32)
+            .map_or(limit, |seconds| seconds as f32)
+    }
+
+    fn start_stop(&mut self, time: f32) {
+        self.at_deco_stop = true;
+        self.stop_clear_in = 0.0;
+        self.deco_stop_time = self.accumulated_short_stop_time; // Start with accumulated time from skipped stops
+        self.accumulated_short_stop_time = 0.0;
+        self.record_stop_arrival(time);
+    }
+
+    fn finish(&mut self) {
+        if !self.finished {
+            #[cfg(feature = "trace")]
+            eprintln!("Simulation completed after {} iterations", self.iteration_count);
+        }
+        self.finished = true;
+    }
+}
+
+impl Iterator for Simulator {
+    type Item = SimulationRecord;
+
+    fn next(&mut self) -> Option<SimulationRecord> {
+        self.start();
+        loop {
+            if let Some(record) = self.pop_record() {
+                return Some(record);
+            }
+            if self.finished {
+                return None;
+            }
+            self.advance();
+        }
     }
 }
 
+fn forward<S: OutputSink>(sink: &mut S, record: &SimulationRecord) {
+    match record {
+        SimulationRecord::Sample(sample) => sink.record(sample),
+        SimulationRecord::Event(event) => sink.event(event),
+    }
+}
+
+#[cfg(feature = "alloc")]
 #[inline(never)]
 pub fn simulate(
     params: &mut DiveParameters,
@@ -56,6 +1030,7 @@ pub fn simulate(
     simulate_with_ascent(params, tissues, starting_ambient_pressure, target_depth, temperature, interval_in_seconds, bottom_time_seconds, true)
 }
 
+#[cfg(feature = "alloc")]
 #[inline(never)]
 pub fn simulate_with_ascent(
     params: &mut DiveParameters,
@@ -70,6 +1045,7 @@ pub fn simulate_with_ascent(
     simulate_with_ascent_from_depth(params, tissues, starting_ambient_pressure, 0.0, target_depth, temperature, interval_in_seconds, bottom_time_seconds, include_ascent)
 }
 
+#[cfg(feature = "alloc")]
 #[inline(never)]
 pub fn simulate_with_ascent_from_depth(
     params: &mut DiveParameters,
@@ -82,302 +1058,144 @@ pub fn simulate_with_ascent_from_depth(
     bottom_time_seconds: f32,
     include_ascent: bool,
 ) -> SimulationOutputs {
-    use crate::ceiling::max_ceiling_with_gf;
-    
     let mut outputs = SimulationOutputs::new();
-    let mut depth = starting_depth;
-    let mut amb_pressure: f32; // Convert depth to absolute pressure
-    let mut dive_time = 0.0;
-    let mut descending = if starting_depth < target_depth { true } else { false };
-    let mut bottom = if starting_depth == target_depth { true } else { false };
-    let mut ascending = false;
-    let mut transitioning = if starting_depth > target_depth { true } else { false }; // New state for going from deeper to shallower
-    let mut at_deco_stop = false;
-    let mut first_stop_depth: Option<f32> = None;
-    let mut current_deco_depth = 0.0;
-    let mut deco_stop_time = 0.0;
-    let mut accumulated_short_stop_time = 0.0;
-
-    // Define a fixed internal time step (e.g., 1 second) for consistent simulation
-    let internal_step = 1.0_f32;
-
-    // Accumulator for output recording
-    let mut output_accumulator = 0.0;
-    
-    // Safety counter to prevent infinite loops
-    let mut iteration_count = 0;
-    const MAX_ITERATIONS: u32 = 50000000; // Increased limit for ascent phase
-
-    #[cfg(feature = "std")]
-    println!("Starting dive simulation: descent -> bottom -> ascent with decompression");
-
-    // Record initial state at the starting depth
-    amb_pressure = starting_depth / 10.0 + starting_ambient_pressure;
-    record_output(&mut outputs, depth, amb_pressure, tissues);
-
-    loop {
-        iteration_count += 1;
-        
-        // Safety check to prevent infinite loops
-        if iteration_count >= MAX_ITERATIONS {
-            #[cfg(feature = "std")]
-            println!("⚠️  Warning: Simulation reached maximum iterations ({}). Stopping simulation.", MAX_ITERATIONS);
-            break;
-        }
-        
-        if descending {
-            // DESCENT PHASE
-            let remaining_depth = target_depth - depth;
-            let time_to_target = remaining_depth / params.descent_speed;
-            let step = internal_step.min(time_to_target);
-
-            depth += params.descent_speed * step;
-            amb_pressure = depth / 10.0 + 1.0;
-
-            for i in 0..16 {
-                tissues[i] = calculate_tissue(tissues[i], i, amb_pressure, temperature, step / 60.0);
-            }
-
-            dive_time += step;
-            output_accumulator += step;
-
-            if output_accumulator >= interval_in_seconds {
-                record_output(&mut outputs, depth, amb_pressure, tissues);
-                output_accumulator -= interval_in_seconds;
-            }
-
-            if depth >= target_depth {
-                #[cfg(feature = "std")]
-                println!("Reached target depth: {}m after {} seconds", target_depth, dive_time);
-                descending = false;
-                bottom = true;
-                continue;
-            }
-        } else if transitioning {
-            // TRANSITION PHASE - going from deeper to shallower depth
-            let remaining_depth = depth - target_depth;
-            let time_to_target = remaining_depth / params.ascent_speed;
-            let step = internal_step.min(time_to_target);
-
-            depth -= params.ascent_speed * step;
-            amb_pressure = depth / 10.0 + 1.0;
-
-            for i in 0..16 {
-                tissues[i] = calculate_tissue(tissues[i], i, amb_pressure, temperature, step / 60.0);
-            }
+    simulate_with_ascent_from_depth_into(params, tissues, starting_ambient_pressure, starting_depth, target_depth, temperature, interval_in_seconds, bottom_time_seconds, include_ascent, &mut outputs);
+    outputs
+}
 
-            dive_time += step;
-            output_accumulator += step;
+/// Same as [`simulate_with_ascent_from_depth`], but hands every sample and event to `sink`
+/// instead of collecting them, so it works without an allocator.
+#[allow(clippy::too_many_arguments)]
+#[inline(never)]
+pub fn simulate_with_ascent_from_depth_into<S: OutputSink>(
+    params: &mut DiveParameters,
+    tissues: &mut [Tissue; 16],
+    starting_ambient_pressure: f32,
+    starting_depth: f32,
+    target_depth: f32,
+    temperature: f32,
+    interval_in_seconds: f32,
+    bottom_time_seconds: f32,
+    include_ascent: bool,
+    sink: &mut S,
+) {
+    let mut simulator = Simulator::new(*params, *tissues, starting_ambient_pressure, starting_depth, target_depth, temperature, interval_in_seconds, bottom_time_seconds, include_ascent);
+    simulator.run_into(sink);
+    *tissues = simulator.tissues;
+}
 
-            if output_accumulator >= interval_in_seconds {
-                record_output(&mut outputs, depth, amb_pressure, tissues);
-                output_accumulator -= interval_in_seconds;
-            }
+/// Passes everything on to `sink` and remembers the depth of the first stop cut at the per-stop
+/// time limit.
+struct StopLimitWatch<'a, S> {
+    sink: &'a mut S,
+    limited_at: Option<f32>,    // m
+}
 
-            if depth <= target_depth {
-                #[cfg(feature = "std")]
-                println!("Reached target depth: {}m after {} seconds (transitioning)", target_depth, dive_time);
-                transitioning = false;
-                bottom = true;
-                continue;
-            }
-        } else if bottom {
-            // BOTTOM PHASE
-            let descent_time = target_depth / params.descent_speed;
-            let remaining_bottom_time = bottom_time_seconds - (dive_time - descent_time);
-            
-            if remaining_bottom_time <= 0.0 {
-                #[cfg(feature = "std")]
-                println!("Bottom time completed. Starting ascent...");
-                bottom = false;
-                ascending = true;
-                continue;
-            }
+impl<S: OutputSink> OutputSink for StopLimitWatch<'_, S> {
+    fn record(&mut self, sample: &SimulationSample) {
+        self.sink.record(sample);
+    }
 
-            let step = internal_step.min(remaining_bottom_time);
-            depth = target_depth;
-            amb_pressure = depth / 10.0 + 1.0;
+    fn event(&mut self, event: &SimulationEvent) {
+        if event.kind == SimulationEventKind::StopTimeLimit && self.limited_at.is_none() {
+            self.limited_at = Some(event.depth);
+        }
+        self.sink.event(event);
+    }
+}
 
-            for i in 0..16 {
-                tissues[i] = calculate_tissue(tissues[i], i, amb_pressure, temperature, step / 60.0);
-            }
+/// [`simulate`] that checks its inputs and reports a run it could not finish as an error.
+#[cfg(feature = "alloc")]
+#[allow(clippy::too_many_arguments)]
+pub fn try_simulate(
+    params: &mut DiveParameters,
+    tissues: &mut [Tissue; 16],
+    starting_ambient_pressure: f32,
+    target_depth: f32,
+    temperature: f32,
+    interval_in_seconds: f32,
+    bottom_time_seconds: f32,
+) -> Result<SimulationOutputs, DecoError> {
+    try_simulate_with_ascent(params, tissues, starting_ambient_pressure, target_depth, temperature, interval_in_seconds, bottom_time_seconds, true)
+}
 
-            dive_time += step;
-            output_accumulator += step;
+/// [`simulate_with_ascent`] that checks its inputs and reports a run it could not finish as an
+/// error.
+#[cfg(feature = "alloc")]
+#[allow(clippy::too_many_arguments)]
+pub fn try_simulate_with_ascent(
+    params: &mut DiveParameters,
+    tissues: &mut [Tissue; 16],
+    starting_ambient_pressure: f32,
+    target_depth: f32,
+    temperature: f32,
+    interval_in_seconds: f32,
+    bottom_time_seconds: f32,
+    include_ascent: bool,
+) -> Result<SimulationOutputs, DecoError> {
+    try_simulate_with_ascent_from_depth(params, tissues, starting_ambient_pressure, 0.0, target_depth, temperature, interval_in_seconds, bottom_time_seconds, include_ascent)
+}
 
-            if output_accumulator >= interval_in_seconds {
-                record_output(&mut outputs, depth, amb_pressure, tissues);
-                output_accumulator -= interval_in_seconds;
-            }
-        } else if ascending && include_ascent {
-            // ASCENT PHASE WITH DECOMPRESSION STOPS
-            // First, check with GF Low to determine if we need any decompression
-            let (ceiling_with_gf_low, _) = max_ceiling_with_gf(params.gf_low, params.gf_high, tissues);
-            
-            // Set first stop depth if not set
-            if first_stop_depth.is_none() && ceiling_with_gf_low > 0 {
-                first_stop_depth = Some(ceiling_with_gf_low as f32);
-            }
+/// [`simulate_with_ascent_from_depth`] that checks its inputs and reports a run it could not
+/// finish as an error.
+#[cfg(feature = "alloc")]
+#[allow(clippy::too_many_arguments)]
+pub fn try_simulate_with_ascent_from_depth(
+    params: &mut DiveParameters,
+    tissues: &mut [Tissue; 16],
+    starting_ambient_pressure: f32,
+    starting_depth: f32,
+    target_depth: f32,
+    temperature: f32,
+    interval_in_seconds: f32,
+    bottom_time_seconds: f32,
+    include_ascent: bool,
+) -> Result<SimulationOutputs, DecoError> {
+    let mut outputs = SimulationOutputs::new();
+    try_simulate_with_ascent_from_depth_into(params, tissues, starting_ambient_pressure, starting_depth, target_depth, temperature, interval_in_seconds, bottom_time_seconds, include_ascent, &mut outputs)?;
+    Ok(outputs)
+}
 
-            let (current_ceiling, _controlling_tissue) = max_ceiling_with_gf(params.gf_low, params.gf_high, tissues);
-            
-            // Debug output every 10 iterations to avoid spam
-            #[cfg(feature = "std")]
-            if iteration_count % 10 == 0 {
-                println!("Ascent: depth {:.1}m, ceiling {:.1}m, at_deco_stop: {}", depth, current_ceiling, at_deco_stop);
-            }
-            
-            if !at_deco_stop {
-                // Check if we need a decompression stop
-                if current_ceiling > 0 && depth > current_ceiling as f32 {
-                    // We need to make a deco stop
-                    let deco_depth = calculate_deco_stop_depth(current_ceiling);
-                    current_deco_depth = deco_depth;
-                    
-                    #[cfg(feature = "std")]
-                    println!("Deco stop required at {}m (ceiling: {}m, controlling tissue: {})", 
-                            deco_depth, current_ceiling, _controlling_tissue);
-                    
-                    // Ascend to deco stop depth if we're deeper
-                    if depth > deco_depth {
-                        let depth_to_ascend = depth - deco_depth;
-                        let time_to_deco_depth = depth_to_ascend / params.ascent_speed;
-                        let step = internal_step.min(time_to_deco_depth);
-                        
-                        depth -= params.ascent_speed * step;
-                        if depth <= deco_depth {
-                            depth = deco_depth;
-                            // Only now do we officially start the deco stop
-                            at_deco_stop = true;
-                            deco_stop_time = accumulated_short_stop_time; // Start with accumulated time from skipped stops
-                            accumulated_short_stop_time = 0.0; // Reset accumulator
-                        }
-                        amb_pressure = depth / 10.0 + 1.0;
-                        
-                        for i in 0..16 {
-                            tissues[i] = calculate_tissue(tissues[i], i, amb_pressure, temperature, step / 60.0);
-                        }
-                        
-                        dive_time += step;
-                        output_accumulator += step;
-                        
-                        if output_accumulator >= interval_in_seconds {
-                            record_output(&mut outputs, depth, amb_pressure, tissues);
-                            output_accumulator -= interval_in_seconds;
-                        }
-                        
-                        // Continue ascending to deco stop
-                        continue;
-                    } else {
-                        // We're already at or above the deco stop depth
-                        depth = deco_depth;
-                        at_deco_stop = true;
-                        deco_stop_time = accumulated_short_stop_time;
-                        accumulated_short_stop_time = 0.0;
-                    }
-                } else if depth > 0.0 {
-                    // No decompression obligation - ascend directly to surface
-                    if current_ceiling == 0 {
-                        // Clear to ascend to surface
-                        let depth_to_surface = depth;
-                        let time_to_surface = depth_to_surface / params.ascent_speed;
-                        let step = internal_step.min(time_to_surface);
-                        
-                        depth -= params.ascent_speed * step;
-                        if depth <= 0.0 {
-                            depth = 0.0;
-                            #[cfg(feature = "std")]
-                            println!("Reached surface - simulation complete");
-                            break;
-                        }
-                        amb_pressure = depth / 10.0 + 1.0;
-                        
-                        for i in 0..16 {
-                            tissues[i] = calculate_tissue(tissues[i], i, amb_pressure, temperature, step / 60.0);
-                        }
-                        
-                        dive_time += step;
-                        output_accumulator += step;
-                        
-                        if output_accumulator >= interval_in_seconds {
-                            record_output(&mut outputs, depth, amb_pressure, tissues);
-                            output_accumulator -= interval_in_seconds;
-                        }
-                    } else {
-                        // Ceiling constrains us - wait at current depth
-                        amb_pressure = depth / 10.0 + 1.0;
-                        
-                        for i in 0..16 {
-                            tissues[i] = calculate_tissue(tissues[i], i, amb_pressure, temperature, internal_step / 60.0);
-                        }
-                        
-                        dive_time += internal_step;
-                        output_accumulator += internal_step;
-                        
-                        if output_accumulator >= interval_in_seconds {
-                            record_output(&mut outputs, depth, amb_pressure, tissues);
-                            output_accumulator -= interval_in_seconds;
-                        }
-                    }
-                } else {
-                    // We're at the surface
-                    break;
-                }
-            } else {
-                // AT DECOMPRESSION STOP
-                depth = current_deco_depth;
-                amb_pressure = depth / 10.0 + 1.0;
-                
-                // Update tissues while at deco stop
-                for i in 0..16 {
-                    tissues[i] = calculate_tissue(tissues[i], i, amb_pressure, temperature, internal_step / 60.0);
-                }
-                
-                dive_time += internal_step;
-                deco_stop_time += internal_step;
-                output_accumulator += internal_step;
-                
-                if output_accumulator >= interval_in_seconds {
-                    record_output(&mut outputs, depth, amb_pressure, tissues);
-                    output_accumulator -= interval_in_seconds;
-                }
-                
-                // Check if we can leave the deco stop (ceiling has cleared)
-                let (new_ceiling, _) = max_ceiling_with_gf(params.gf_low, params.gf_high, tissues);
-                
-                // Check if we can leave this deco stop
-                if deco_stop_time >= 60.0 && (new_ceiling == 0 || new_ceiling as f32 + 0.5 < current_deco_depth) {
-                    #[cfg(feature = "std")]
-                    println!("Completed deco stop at {}m after {:.1} minutes", current_deco_depth, deco_stop_time / 60.0);
-                    at_deco_stop = false;
-                    deco_stop_time = 0.0;
-                } else if deco_stop_time < 60.0 && (new_ceiling == 0 || new_ceiling as f32 + 0.5 < current_deco_depth) {
-                    // This stop would be less than 1 minute - accumulate the time and move to next stop
-                    accumulated_short_stop_time += deco_stop_time;
-                    #[cfg(feature = "std")]
-                    println!("Skipping short deco stop at {}m ({:.1}s) - adding to next stop", current_deco_depth, deco_stop_time);
-                    at_deco_stop = false;
-                    deco_stop_time = 0.0;
-                }
-                
-                // Safety check - max deco stop time of 20 minutes
-                if deco_stop_time >= 20.0 * 60.0 {
-                    #[cfg(feature = "std")]
-                    println!("⚠️  Maximum deco stop time reached at {}m. Continuing ascent.", current_deco_depth);
-                    at_deco_stop = false;
-                    deco_stop_time = 0.0;
-                }
-            }
-        } else {
-            // End of simulation (no ascent requested)
-            break;
-        }
+/// [`simulate_with_ascent_from_depth_into`] that checks the parameters, tissues and dive before
+/// running. A stop cut at the 20 minute limit gives `ExcessiveDeco` and a run stopped at the
+/// iteration limit gives `NoConvergence`; the tissues are updated either way, the sink holds
+/// everything up to the end of the run.
+#[allow(clippy::too_many_arguments)]
+pub fn try_simulate_with_ascent_from_depth_into<S: OutputSink>(
+    params: &mut DiveParameters,
+    tissues: &mut [Tissue; 16],
+    starting_ambient_pressure: f32,
+    starting_depth: f32,
+    target_depth: f32,
+    temperature: f32,
+    interval_in_seconds: f32,
+    bottom_time_seconds: f32,
+    include_ascent: bool,
+    sink: &mut S,
+) -> Result<(), DecoError> {
+    params.validate()?;
+    check_tissues(tissues)?;
+    check_input(Input::SurfacePressure, starting_ambient_pressure, |pressure| pressure > 0.0)?;
+    check_input(Input::Depth, starting_depth, |depth| depth >= 0.0)?;
+    check_input(Input::Depth, target_depth, |depth| depth >= 0.0)?;
+    check_input(Input::Temperature, temperature, |_| true)?;
+    check_input(Input::Time, bottom_time_seconds, |time| time >= 0.0)?;
+    if !(interval_in_seconds > 0.0 && interval_in_seconds.is_finite()) {
+        return Err(DecoError::InvalidSampleInterval);
     }
 
-    #[cfg(feature = "std")]
-    println!("Simulation completed after {} iterations", iteration_count);
+    let mut watch = StopLimitWatch { sink, limited_at: None };
+    let mut simulator = Simulator::new(*params, *tissues, starting_ambient_pressure, starting_depth, target_depth, temperature, interval_in_seconds, bottom_time_seconds, include_ascent);
+    simulator.run_into(&mut watch);
+    *tissues = simulator.tissues;
 
-    outputs
+    if simulator.hit_iteration_limit() {
+        return Err(DecoError::NoConvergence { iterations: MAX_ITERATIONS });
+    }
+    match watch.limited_at {
+        Some(depth) => Err(DecoError::ExcessiveDeco { depth }),
+        None => Ok(()),
+    }
 }
 
 fn calculate_deco_stop_depth(ceiling: u32) -> f32 {
@@ -385,15 +1203,3 @@ fn calculate_deco_stop_depth(ceiling: u32) -> f32 {
     let deco_depth = ((ceiling as f32 + 2.999) / 3.0) as u32 as f32 * 3.0;
     deco_depth.max(3.0)
 }
-
-#[cfg(feature = "serde")]
-fn record_output(outputs: &mut SimulationOutputs, depth: f32, pressure: f32, tissues: &[Tissue; 16]) {
-    outputs.depths.push(depth);
-    outputs.pressures.push(pressure);
-    outputs.tissues_per_interval.push(*tissues);
-}
-
-#[cfg(not(feature = "serde"))]
-fn record_output(_outputs: &mut SimulationOutputs, _depth: f32, _pressure: f32, _tissues: &[Tissue; 16]) {
-    // No-op for non-serde builds
-}
\ No newline at end of file
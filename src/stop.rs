@@ -0,0 +1,148 @@
+//! Length of a decompression stop without stepping through it second by second.
+//!
+//! At a stop the ambient pressure is constant, so every compartment follows the Haldane equation
+//! p(t) = pi + (p0 - pi) * e^(-kt). The compartment leading the ceiling is solved for the time its
+//! load falls to what the next stop tolerates, which is exact for a compartment holding one gas
+//! and a close estimate otherwise. The estimate is then refined to the whole second across all
+//! compartments, projecting the tissues with the closed-form equation.
+
+use libm::{expf, logf};
+
+use crate::ceiling::{check_slope, GfSlope};
+use crate::tissue::{calculate_tissue_with_gas, Tissue};
+use crate::zh16c::ZhL16cGf;
+use crate::{check_gas, check_input, check_tissues, water_vapor_pressure, DecoError, Gas, Input};
+
+/// Newton steps for a compartment holding N2 and He
+const NEWTON_STEPS: u32 = 8;
+
+/// Whole seconds at `amb_pressure` breathing `gas` until the ceiling of `slope`, as reported by
+/// `GfSlope::max_ceiling` in metres at 10 m per bar, is shallower than `ceiling`. `None` when it
+/// takes longer than `limit_seconds` or the ceiling can never be reached at this pressure. An
+/// unanchored slope is anchored at the deepest stop of `tissues` for the whole stop.
+pub fn time_to_ceiling(
+    slope: &GfSlope,
+    tissues: &[Tissue; 16],
+    amb_pressure: f32,
+    temperature: f32,
+    gas: Gas,
+    ceiling: f32,
+    limit_seconds: u32,
+) -> Option<u32> {
+    let mut slope = *slope;
+    slope.anchor(tissues);
+    let cleared_after = |seconds: u32| {
+        let mut projected = *tissues;
+        for (i, tissue) in projected.iter_mut().enumerate() {
+            *tissue = calculate_tissue_with_gas(*tissue, i, amb_pressure, temperature, seconds as f32 / 60.0, gas);
+        }
+        slope.max_ceiling(&projected).0 < ceiling
+    };
+
+    if cleared_after(0) {
+        return Some(0);
+    }
+    if !cleared_after(limit_seconds) {
+        return None;
+    }
+
+    let (_, compartment, gf) = slope.max_ceiling(tissues);
+    let estimate = estimate_seconds(&tissues[compartment], compartment, gf, amb_pressure, temperature, gas, slope.surface_pressure + ceiling / 10.0)
+        .map_or(limit_seconds, |seconds| (seconds as u32).clamp(1, limit_seconds));
+
+    // widen a bracket around the estimate, then bisect;
+    // invariant: not cleared after `low` seconds, cleared after `high` seconds
+    let (mut low, mut high) = if cleared_after(estimate) {
+        let mut high = estimate;
+        let mut step = 1;
+        loop {
+            let low = high.saturating_sub(step);
+            if !cleared_after(low) {
+                break (low, high);
+            }
+            high = low;
+            step *= 2;
+        }
+    } else {
+        let mut low = estimate;
+        let mut step = 1;
+        loop {
+            let high = (low + step).min(limit_seconds);
+            if cleared_after(high) {
+                break (low, high);
+            }
+            low = high;
+            step *= 2;
+        }
+    };
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        if cleared_after(mid) {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    Some(high)
+}
+
+/// `time_to_ceiling` that returns an error instead of producing NaN on bad input.
+pub fn try_time_to_ceiling(
+    slope: &GfSlope,
+    tissues: &[Tissue; 16],
+    amb_pressure: f32,
+    temperature: f32,
+    gas: Gas,
+    ceiling: f32,
+    limit_seconds: u32,
+) -> Result<Option<u32>, DecoError> {
+    check_slope(slope)?;
+    check_tissues(tissues)?;
+    check_input(Input::AmbientPressure, amb_pressure, |pressure| pressure > 0.0)?;
+    check_input(Input::Temperature, temperature, |_| true)?;
+    check_gas(gas)?;
+    check_input(Input::Depth, ceiling, |_| true)?;
+    Ok(time_to_ceiling(slope, tissues, amb_pressure, temperature, gas, ceiling, limit_seconds))
+}
+
+/// Seconds until one compartment is down to the load tolerated at `target_pressure` with `gf`,
+/// keeping its Bühlmann a and b at today's gas mix. `None` when the load never gets there.
+fn estimate_seconds(tissue: &Tissue, tissue_index: usize, gf: f32, amb_pressure: f32, temperature: f32, gas: Gas, target_pressure: f32) -> Option<f32> {
+    let p_total = tissue.load_n2 + tissue.load_he;
+    if p_total <= 0.0 {
+        return None;
+    }
+    let a = (ZhL16cGf::N2_A[tissue_index] * tissue.load_n2 + ZhL16cGf::HE_A[tissue_index] * tissue.load_he) / p_total;
+    let b = (ZhL16cGf::N2_B[tissue_index] * tissue.load_n2 + ZhL16cGf::HE_B[tissue_index] * tissue.load_he) / p_total;
+    // load at which the compartment ceiling reaches the target, inverted from the ceiling equation
+    let tolerated = target_pressure * ((1.0 - b) * gf + b) / b + gf * a;
+
+    let inspired = amb_pressure - water_vapor_pressure(temperature);
+    let (pi_n2, pi_he) = (inspired * gas.n2, inspired * gas.he);
+    let (d_n2, d_he) = (tissue.load_n2 - pi_n2, tissue.load_he - pi_he);
+    let k_n2 = logf(2.0) / ZhL16cGf::N2_HALF_LIFE[tissue_index];
+    let k_he = logf(2.0) / ZhL16cGf::HE_HALF_LIFE[tissue_index];
+    let excess = tolerated - pi_n2 - pi_he;
+    if excess <= 0.0 {
+        return None;
+    }
+
+    // Haldane equation solved for t when one gas carries the whole difference
+    if d_he == 0.0 || d_n2 == 0.0 {
+        let (difference, k) = if d_he == 0.0 { (d_n2, k_n2) } else { (d_he, k_he) };
+        return Some(if difference > excess { logf(difference / excess) / k * 60.0 } else { 0.0 });
+    }
+
+    // otherwise Newton's method from the slower gas, which bounds the time from above when both off-gas
+    let mut minutes = logf(((d_n2 + d_he) / excess).max(1.0)) / k_n2.min(k_he);
+    for _ in 0..NEWTON_STEPS {
+        let e_n2 = d_n2 * expf(-k_n2 * minutes);
+        let e_he = d_he * expf(-k_he * minutes);
+        let slope = -k_n2 * e_n2 - k_he * e_he;
+        if slope >= 0.0 {
+            break;
+        }
+        minutes = (minutes - (e_n2 + e_he - excess) / slope).max(0.0);
+    }
+    Some(minutes * 60.0)
+}
@@ -0,0 +1,207 @@
+//! Decompression tables: a sweep of depth × bottom time for one gas, set of gradient factors and
+//! rates, laid out like the published tables printed for training and as backups.
+//!
+//! As in published tables, bottom time runs from leaving the surface to leaving the bottom and
+//! every dive starts from tissues saturated at the surface. The NDL of a depth is the time left at
+//! the depth on arrival, after the descent, capped at `simulate::NDL_LIMIT_MINUTES`.
+//!
+//! Tables print in Markdown or CSV with depths in metres and times in minutes, stop times rounded
+//! up to whole minutes; JSON (with the `serde` feature) keeps the seconds of `DecoTable`.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use core::fmt::Write;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use libm::ceilf;
+
+use crate::plan::{DivePlan, Segment, SegmentTime, WaterType};
+use crate::simulate::{RuntimeKind, SimulationEventKind, Simulator};
+use crate::tissue::Tissue;
+use crate::{water_vapor_pressure, DecoError, DiveParameters, Gas, FN2};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TableRow {
+    pub depth: f32,                 // m
+    pub bottom_time: f32,           // s from leaving the surface to leaving the bottom
+    pub ndl: Option<f32>,           // min at the depth on arrival, None when the descent alone needs stops
+    pub first_stop: Option<f32>,    // m, None for a no-stop dive
+    pub stops: Vec<(f32, f32)>,     // m, s
+    pub time_to_surface: f32,       // s from leaving the bottom
+    pub stop_time_limit: bool,      // a stop was cut at the 20 min per-stop limit, the wait after it only shows in time_to_surface
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DecoTable {
+    pub gas: Gas,
+    pub gf_low: f32,
+    pub gf_high: f32,
+    pub descent_speed: f32,         // m/s
+    pub ascent_speed: f32,          // m/s
+    pub surface_pressure: f32,      // bar
+    pub water: WaterType,
+    pub rows: Vec<TableRow>,        // bottom times of each depth in order, depths in order
+}
+
+/// Table of every `depths` (m) × `bottom_times` (s) dive on `gas` with the gradient factors and
+/// rates of `params`.
+#[allow(clippy::too_many_arguments)]
+pub fn deco_table(params: &DiveParameters, gas: Gas, surface_pressure: f32, water: WaterType, temperature: f32, interval_in_seconds: f32, depths: &[f32], bottom_times: &[f32]) -> Result<DecoTable, DecoError> {
+    let mut tissues = [Tissue::default(); 16];
+    for tissue in tissues.iter_mut() {
+        tissue.load_n2 = (surface_pressure - water_vapor_pressure(temperature)) * FN2;
+        tissue.load_he = 0.0;
+    }
+
+    let mut rows = Vec::new();
+    for &depth in depths {
+        let mut descent = Simulator::new(*params, tissues, surface_pressure, 0.0, depth, temperature, interval_in_seconds, 0.0, false);
+        descent.set_environment(surface_pressure, water, gas, None);
+        descent.run_into(&mut ());
+        let ndl = descent.sample().ndl;
+
+        for &bottom_time in bottom_times {
+            let mut plan = DivePlan::new(surface_pressure, water);
+            let index = plan.add_gas(gas);
+            plan.add_segment(Segment::new(depth, SegmentTime::Runtime(bottom_time), index));
+            let outputs = plan.simulate(params, &mut { tissues }, temperature, interval_in_seconds)?;
+
+            let runtime = outputs.runtime_table();
+            let leave_bottom = runtime.iter()
+                .find(|entry| entry.kind == RuntimeKind::Level)
+                .map_or(0.0, |entry| entry.runtime);
+            let stops: Vec<(f32, f32)> = runtime.iter()
+                .filter(|entry| entry.kind == RuntimeKind::Stop)
+                .map(|entry| (entry.depth, entry.duration))
+                .collect();
+            rows.push(TableRow {
+                depth,
+                bottom_time,
+                ndl,
+                first_stop: stops.first().map(|stop| stop.0),
+                stops,
+                time_to_surface: runtime.last().map_or(0.0, |entry| entry.runtime) - leave_bottom,
+                stop_time_limit: outputs.events.iter().any(|event| event.kind == SimulationEventKind::StopTimeLimit),
+            });
+        }
+    }
+
+    Ok(DecoTable {
+        gas,
+        gf_low: params.gf_low,
+        gf_high: params.gf_high,
+        descent_speed: params.descent_speed,
+        ascent_speed: params.ascent_speed,
+        surface_pressure,
+        water,
+        rows,
+    })
+}
+
+impl DecoTable {
+    /// Deepest stop of any row, where the stop columns of the printed tables start (m)
+    fn deepest_stop(&self) -> f32 {
+        self.rows.iter().filter_map(|row| row.first_stop).fold(0.0, f32::max)
+    }
+
+    /// Stop depths from the deepest, 3 m apart
+    fn stop_depths(&self) -> Vec<f32> {
+        let mut depths = Vec::new();
+        let mut depth = self.deepest_stop();
+        while depth > 0.0 {
+            depths.push(depth);
+            depth -= 3.0;
+        }
+        depths
+    }
+
+    /// Whole minutes at `depth`, empty when the row does not stop there
+    fn stop_minutes(row: &TableRow, depth: f32) -> String {
+        let mut out = String::new();
+        let duration: f32 = row.stops.iter().filter(|stop| stop.0 == depth).map(|stop| stop.1).sum();
+        if duration > 0.0 {
+            let _ = write!(out, "{:.0}", ceilf(duration / 60.0));
+        }
+        out
+    }
+
+    /// Header line with the gas, gradient factors and rates
+    fn title(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(out, "O2 {:.0}% He {:.0}%, GF {:.0}/{:.0}, descent {:.0} m/min, ascent {:.0} m/min",
+            self.gas.o2() * 100.0, self.gas.he * 100.0, self.gf_low * 100.0, self.gf_high * 100.0,
+            self.descent_speed * 60.0, self.ascent_speed * 60.0);
+        out
+    }
+
+    /// One line per row: depth, bottom time, NDL, first stop, a column per stop depth and the
+    /// total ascent time; "LIMIT" in the last column when a stop was cut short.
+    pub fn to_csv(&self) -> String {
+        let stop_depths = self.stop_depths();
+        let mut out = String::from("depth_m,bottom_time_min,ndl_min,first_stop_m");
+        for depth in stop_depths.iter() {
+            let _ = write!(out, ",stop_{:.0}m_min", depth);
+        }
+        out.push_str(",time_to_surface_min,stop_time_limit");
+        for row in self.rows.iter() {
+            let _ = write!(out, "\n{:.0},{:.0},", row.depth, row.bottom_time / 60.0);
+            if let Some(ndl) = row.ndl {
+                let _ = write!(out, "{:.0}", ndl);
+            }
+            out.push(',');
+            if let Some(first_stop) = row.first_stop {
+                let _ = write!(out, "{:.0}", first_stop);
+            }
+            for depth in stop_depths.iter() {
+                let _ = write!(out, ",{}", Self::stop_minutes(row, *depth));
+            }
+            let _ = write!(out, ",{:.0},{}", ceilf(row.time_to_surface / 60.0), if row.stop_time_limit { "LIMIT" } else { "" });
+        }
+        out
+    }
+
+    /// A Markdown table per depth with its NDL, rows past the NDL show their stops.
+    pub fn to_markdown(&self) -> String {
+        let stop_depths = self.stop_depths();
+        let mut out = self.title();
+        let mut depth = None;
+        for row in self.rows.iter() {
+            if depth != Some(row.depth) {
+                depth = Some(row.depth);
+                let _ = write!(out, "\n\n### {:.0} m, NDL ", row.depth);
+                match row.ndl {
+                    Some(ndl) => { let _ = write!(out, "{:.0} min", ndl); }
+                    None => out.push_str("none"),
+                }
+                out.push_str("\n\n| Bottom time | First stop |");
+                for stop in stop_depths.iter() {
+                    let _ = write!(out, " {:.0} m |", stop);
+                }
+                out.push_str(" Ascent |\n|---:|---:|");
+                for _ in stop_depths.iter() {
+                    out.push_str("---:|");
+                }
+                out.push_str("---:|");
+            }
+            let _ = write!(out, "\n| {:.0} | ", row.bottom_time / 60.0);
+            match row.first_stop {
+                Some(first_stop) => { let _ = write!(out, "{:.0} m |", first_stop); }
+                None => out.push_str("no stop |"),
+            }
+            for stop in stop_depths.iter() {
+                let _ = write!(out, " {} |", Self::stop_minutes(row, *stop));
+            }
+            let _ = write!(out, " {:.0}{} |", ceilf(row.time_to_surface / 60.0), if row.stop_time_limit { " LIMIT" } else { "" });
+        }
+        out
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
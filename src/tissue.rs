@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use defmt::{Format, Formatter};
 use libm::{logf, powf};
-use crate::{water_vapor_pressure, FHE, FN2};
+use crate::{check_gas, check_input, check_tissue_index, check_tissues, water_vapor_pressure, DecoError, Gas, Input};
 use crate::zh16c::ZhL16cGf;
 
 #[cfg(feature = "serde")]
@@ -43,38 +43,163 @@ impl Format for Tissue {
 // R -> rate of change of the partial inert gass pressure in the breathing mix in the alveoli (bar/min)
 //      R = QRamb in which Q is the fraction of the inert gas and Ramb is the rate of change of the ambient pressure
 // t -> time
+/// `tissue` after `minutes_since_last_check` on air at `amb_pressure`. Panics when the time is
+/// negative; `try_calculate_tissue` returns an error instead.
 pub fn calculate_tissue(
+    tissue: Tissue,
+    tissue_index: usize,
+    amb_pressure: f32,
+    temperature: f32,
+    minutes_since_last_check: f32,
+) -> Tissue {
+    calculate_tissue_with_gas(tissue, tissue_index, amb_pressure, temperature, minutes_since_last_check, Gas::air())
+}
+
+/// `calculate_tissue` that returns an error instead of panicking or producing NaN on bad input.
+pub fn try_calculate_tissue(
+    tissue: Tissue,
+    tissue_index: usize,
+    amb_pressure: f32,
+    temperature: f32,
+    minutes_since_last_check: f32,
+) -> Result<Tissue, DecoError> {
+    try_calculate_tissue_with_gas(tissue, tissue_index, amb_pressure, temperature, minutes_since_last_check, Gas::air())
+}
+
+/// `calculate_tissue_with_gas` that returns an error instead of panicking or producing NaN on bad input.
+pub fn try_calculate_tissue_with_gas(
+    tissue: Tissue,
+    tissue_index: usize,
+    amb_pressure: f32,
+    temperature: f32,
+    minutes_since_last_check: f32,
+    gas: Gas,
+) -> Result<Tissue, DecoError> {
+    check_tissue_index(tissue_index)?;
+    check_tissues(&[tissue])?;
+    check_input(Input::AmbientPressure, amb_pressure, |pressure| pressure > 0.0)?;
+    check_input(Input::Temperature, temperature, |_| true)?;
+    check_input(Input::Time, minutes_since_last_check, |minutes| minutes >= 0.0)?;
+    check_gas(gas)?;
+    Ok(calculate_tissue_with_gas(tissue, tissue_index, amb_pressure, temperature, minutes_since_last_check, gas))
+}
+
+/// Same as `calculate_tissue`, breathing `gas` instead of air. Panics when the time is negative;
+/// `try_calculate_tissue_with_gas` returns an error instead.
+pub fn calculate_tissue_with_gas(
     mut tissue: Tissue,
     tissue_index: usize,
     amb_pressure: f32,
     temperature: f32,
     minutes_since_last_check: f32,
+    gas: Gas,
 ) -> Tissue {
 
     assert!(minutes_since_last_check >= 0.0, "minutes_since_last_check must be >= 0.0");
     // current ambient pressure for fractions
-    let ppn2 = (amb_pressure - water_vapor_pressure(temperature)) * FN2;
-    let pphe = (amb_pressure - water_vapor_pressure(temperature)) * FHE;
+    let ppn2 = (amb_pressure - water_vapor_pressure(temperature)) * gas.n2;
+    let pphe = (amb_pressure - water_vapor_pressure(temperature)) * gas.he;
 
     // current tissue load for fraction
     let p0n2 = tissue.load_n2;
     let p0he = tissue.load_he;
 
     // half life for the tissue in minutes
-    let kn2 = ZhL16cGf::N2_HALF_LIFE[tissue_index];
-    let khe = ZhL16cGf::HE_HALF_LIFE[tissue_index];
-
-    let k_n2 = logf(2.0) / kn2;
-    let k_he = logf(2.0) / khe;
-
-    let e_to_exponent_n2 = powf(core::f32::consts::E, -k_n2 * minutes_since_last_check);
-    let e_to_exponent_he = powf(core::f32::consts::E, -k_he * minutes_since_last_check);
+    let e_to_exponent_n2 = decay_factor(ZhL16cGf::N2_HALF_LIFE[tissue_index], minutes_since_last_check);
+    let e_to_exponent_he = decay_factor(ZhL16cGf::HE_HALF_LIFE[tissue_index], minutes_since_last_check);
 
+    // Haldane for both gases: the gap to the inspired pressure decays by e^(-kt)
     let fn2 = ppn2 + (p0n2 - ppn2) * e_to_exponent_n2;
-    let fhe = p0he + (pphe - p0he) * e_to_exponent_he;
+    let fhe = pphe + (p0he - pphe) * e_to_exponent_he;
 
     tissue.load_n2 = fn2;
     tissue.load_he = fhe;
 
     tissue
-}
\ No newline at end of file
+}
+/// e^(-kt) of a compartment with a half life of `half_life` minutes after `minutes`
+fn decay_factor(half_life: f32, minutes: f32) -> f32 {
+    let k = logf(2.0) / half_life;
+    powf(core::f32::consts::E, -k * minutes)
+}
+
+/// Tissue update for a fixed time step with the exponential decay of every compartment worked
+/// out once, so each update is a multiply-add per gas. Gives the same loads as
+/// `calculate_tissue_with_gas` for the same step.
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TissueUpdater {
+    minutes: f32,
+    n2: [f32; 16],      // e^(-kt) per compartment
+    he: [f32; 16],
+}
+
+impl TissueUpdater {
+    /// Step of `minutes` with the ZH-L16C half lives. Panics when `minutes` is negative; `try_new`
+    /// returns an error instead.
+    pub fn new(minutes: f32) -> Self {
+        Self::with_half_lives(&ZhL16cGf::N2_HALF_LIFE, &ZhL16cGf::HE_HALF_LIFE, minutes)
+    }
+
+    /// Step of `minutes` with other N2 and He half lives (min). Panics when `minutes` is negative;
+    /// `try_with_half_lives` returns an error instead.
+    pub fn with_half_lives(n2_half_life: &[f32; 16], he_half_life: &[f32; 16], minutes: f32) -> Self {
+        assert!(minutes >= 0.0, "minutes must be >= 0.0");
+        TissueUpdater {
+            minutes,
+            n2: core::array::from_fn(|i| decay_factor(n2_half_life[i], minutes)),
+            he: core::array::from_fn(|i| decay_factor(he_half_life[i], minutes)),
+        }
+    }
+
+    /// `new` that returns an error instead of panicking on a negative or NaN step.
+    pub fn try_new(minutes: f32) -> Result<Self, DecoError> {
+        Self::try_with_half_lives(&ZhL16cGf::N2_HALF_LIFE, &ZhL16cGf::HE_HALF_LIFE, minutes)
+    }
+
+    /// `with_half_lives` that returns an error instead of panicking or producing NaN on bad input.
+    pub fn try_with_half_lives(n2_half_life: &[f32; 16], he_half_life: &[f32; 16], minutes: f32) -> Result<Self, DecoError> {
+        for &half_life in n2_half_life.iter().chain(he_half_life.iter()) {
+            check_input(Input::Time, half_life, |half_life| half_life > 0.0)?;
+        }
+        check_input(Input::Time, minutes, |minutes| minutes >= 0.0)?;
+        Ok(Self::with_half_lives(n2_half_life, he_half_life, minutes))
+    }
+
+    /// Time step (min)
+    pub fn minutes(&self) -> f32 {
+        self.minutes
+    }
+
+    /// e^(-kt) of every compartment for N2 and He
+    pub(crate) fn factors(&self) -> (&[f32; 16], &[f32; 16]) {
+        (&self.n2, &self.he)
+    }
+
+    /// One compartment breathing `gas` at `amb_pressure` for the step
+    pub fn update(&self, mut tissue: Tissue, tissue_index: usize, amb_pressure: f32, temperature: f32, gas: Gas) -> Tissue {
+        let ppn2 = (amb_pressure - water_vapor_pressure(temperature)) * gas.n2;
+        let pphe = (amb_pressure - water_vapor_pressure(temperature)) * gas.he;
+        tissue.load_n2 = ppn2 + (tissue.load_n2 - ppn2) * self.n2[tissue_index];
+        tissue.load_he = pphe + (tissue.load_he - pphe) * self.he[tissue_index];
+        tissue
+    }
+
+    /// Every compartment breathing `gas` at `amb_pressure` for the step
+    pub fn update_all(&self, tissues: &mut [Tissue; 16], amb_pressure: f32, temperature: f32, gas: Gas) {
+        let inspired = amb_pressure - water_vapor_pressure(temperature);
+        let ppn2 = inspired * gas.n2;
+        let pphe = inspired * gas.he;
+        for ((tissue, n2), he) in tissues.iter_mut().zip(self.n2.iter()).zip(self.he.iter()) {
+            tissue.load_n2 = ppn2 + (tissue.load_n2 - ppn2) * n2;
+            tissue.load_he = pphe + (tissue.load_he - pphe) * he;
+        }
+    }
+}
+
+impl Default for TissueUpdater {
+    /// One second, the sample rate of the real-time loop
+    fn default() -> Self {
+        Self::new(1.0 / 60.0)
+    }
+}
@@ -0,0 +1,151 @@
+//! Structure-of-arrays form of the 16 compartments.
+//!
+//! `[Tissue; 16]` interleaves the N2 and He load of each compartment. `TissueState` keeps each gas
+//! in its own contiguous array next to the matching ZH-L16C coefficient arrays, and its operations
+//! are straight loops over all compartments, so the compiler can turn them into SIMD lanes. Only
+//! weighting the coefficients by the loads branches, for compartments without any load. Results
+//! match the per-compartment functions of `tissue`, `ceiling` and `m_value`.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use defmt::Format;
+
+use crate::ceiling::tolerated_pressure;
+use crate::tissue::{Tissue, TissueUpdater};
+use crate::zh16c::ZhL16cGf;
+use crate::{check_gas, check_gradient_factors, check_input, check_tissues, water_vapor_pressure, DecoError, Gas, Input};
+
+#[derive(Debug, Format, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TissueState {
+    pub load_n2: [f32; 16],     // bar
+    pub load_he: [f32; 16],     // bar
+}
+
+impl TissueState {
+    /// Every compartment at the same loads
+    pub fn uniform(load_n2: f32, load_he: f32) -> Self {
+        TissueState { load_n2: [load_n2; 16], load_he: [load_he; 16] }
+    }
+
+    pub fn tissue(&self, tissue_index: usize) -> Tissue {
+        Tissue { load_n2: self.load_n2[tissue_index], load_he: self.load_he[tissue_index] }
+    }
+
+    /// Every compartment breathing `gas` at `amb_pressure` for the step of `updater`
+    pub fn update(&mut self, updater: &TissueUpdater, amb_pressure: f32, temperature: f32, gas: Gas) {
+        let inspired = amb_pressure - water_vapor_pressure(temperature);
+        let ppn2 = inspired * gas.n2;
+        let pphe = inspired * gas.he;
+        let (n2_factors, he_factors) = updater.factors();
+        for (load, factor) in self.load_n2.iter_mut().zip(n2_factors.iter()) {
+            *load = ppn2 + (*load - ppn2) * factor;
+        }
+        for (load, factor) in self.load_he.iter_mut().zip(he_factors.iter()) {
+            *load = pphe + (*load - pphe) * factor;
+        }
+    }
+
+    /// `update` that returns an error instead of producing NaN on bad input.
+    pub fn try_update(&mut self, updater: &TissueUpdater, amb_pressure: f32, temperature: f32, gas: Gas) -> Result<(), DecoError> {
+        self.check()?;
+        check_input(Input::AmbientPressure, amb_pressure, |pressure| pressure > 0.0)?;
+        check_input(Input::Temperature, temperature, |_| true)?;
+        check_gas(gas)?;
+        self.update(updater, amb_pressure, temperature, gas);
+        Ok(())
+    }
+
+    /// Every load finite and not negative
+    fn check(&self) -> Result<(), DecoError> {
+        check_tissues(&<[Tissue; 16]>::from(self))
+    }
+
+    /// Bühlmann a and b of each compartment, weighted by its N2 and He loads
+    fn coefficients(&self) -> ([f32; 16], [f32; 16]) {
+        let mut a = [0.0; 16];
+        let mut b = [0.0; 16];
+        for i in 0..16 {
+            let pn2 = self.load_n2[i];
+            let phe = self.load_he[i];
+            let p_total = pn2 + phe;
+            // an empty compartment has no mix to weight by, take the N2 values
+            if p_total <= 0.0 {
+                a[i] = ZhL16cGf::N2_A[i];
+                b[i] = ZhL16cGf::N2_B[i];
+                continue;
+            }
+            a[i] = (ZhL16cGf::N2_A[i] * pn2 + ZhL16cGf::HE_A[i] * phe) / p_total;
+            b[i] = (ZhL16cGf::N2_B[i] * pn2 + ZhL16cGf::HE_B[i] * phe) / p_total;
+        }
+        (a, b)
+    }
+
+    /// Tolerated inert gas load of each compartment at `amb_pressure` (bar).
+    /// Compartments without any load get the N2 M-value.
+    pub fn m_values(&self, amb_pressure: f32) -> [f32; 16] {
+        let (a, b) = self.coefficients();
+        let mut m_values = [0.0; 16];
+        for i in 0..16 {
+            m_values[i] = amb_pressure / b[i] + a[i];
+        }
+        m_values
+    }
+
+    /// Lowest tolerated ambient pressure of each compartment with gradient factor `gf` (bar).
+    /// Compartments without any load get 0.
+    pub fn ceilings(&self, gf: f32) -> [f32; 16] {
+        let (a, b) = self.coefficients();
+        let mut ceilings = [0.0; 16];
+        for i in 0..16 {
+            let p_total = self.load_n2[i] + self.load_he[i];
+            let ceiling = tolerated_pressure(a[i], b[i], p_total, gf);
+            ceilings[i] = if p_total > 0.0 { ceiling } else { 0.0 };
+        }
+        ceilings
+    }
+
+    /// Deepest ceiling with gradient factor `gf` (bar) and the compartment it comes from
+    pub fn max_ceiling(&self, gf: f32) -> (f32, usize) {
+        self.ceilings(gf)
+            .iter()
+            .enumerate()
+            .fold((f32::NEG_INFINITY, 0), |max, (i, &ceiling)| if ceiling > max.0 { (ceiling, i) } else { max })
+    }
+
+    /// `ceilings` that returns an error instead of producing NaN on bad input.
+    pub fn try_ceilings(&self, gf: f32) -> Result<[f32; 16], DecoError> {
+        check_gradient_factors(gf, gf)?;
+        self.check()?;
+        Ok(self.ceilings(gf))
+    }
+
+    /// `max_ceiling` that returns an error instead of producing NaN on bad input.
+    pub fn try_max_ceiling(&self, gf: f32) -> Result<(f32, usize), DecoError> {
+        check_gradient_factors(gf, gf)?;
+        self.check()?;
+        Ok(self.max_ceiling(gf))
+    }
+}
+
+impl Default for TissueState {
+    fn default() -> Self {
+        Self::from(&[Tissue::default(); 16])
+    }
+}
+
+impl From<&[Tissue; 16]> for TissueState {
+    fn from(tissues: &[Tissue; 16]) -> Self {
+        TissueState {
+            load_n2: core::array::from_fn(|i| tissues[i].load_n2),
+            load_he: core::array::from_fn(|i| tissues[i].load_he),
+        }
+    }
+}
+
+impl From<&TissueState> for [Tissue; 16] {
+    fn from(state: &TissueState) -> Self {
+        core::array::from_fn(|i| state.tissue(i))
+    }
+}
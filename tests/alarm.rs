@@ -0,0 +1,105 @@
+use dive_computer_deco::alarm::{AlarmInputs, AlarmKind, AlarmMonitor, AlarmProfile, AlarmStatus};
+
+#[test]
+fn test_max_depth_alarm_hysteresis() {
+    let mut monitor = AlarmMonitor::default();
+    let mut inputs = AlarmInputs { depth: 41.0, ppo2: 1.0, ..Default::default() };
+
+    let raised = monitor.evaluate(&inputs);
+    assert!(raised.contains(AlarmKind::MaxDepth));
+    assert_eq!(monitor.status(AlarmKind::MaxDepth), AlarmStatus::Active);
+
+    // back above the threshold, but still within the hysteresis band
+    inputs.depth = 39.5;
+    assert!(monitor.evaluate(&inputs).is_empty());
+    assert_eq!(monitor.status(AlarmKind::MaxDepth), AlarmStatus::Active);
+
+    inputs.depth = 38.5;
+    monitor.evaluate(&inputs);
+    assert_eq!(monitor.status(AlarmKind::MaxDepth), AlarmStatus::Inactive);
+}
+
+#[test]
+fn test_acknowledged_alarm_stays_silent_until_cleared() {
+    let mut monitor = AlarmMonitor::default();
+    let mut inputs = AlarmInputs { depth: 20.0, ppo2: 1.0, ascent_rate: 18.0, ..Default::default() };
+
+    monitor.evaluate(&inputs);
+    monitor.acknowledge(AlarmKind::AscentRate);
+    assert_eq!(monitor.status(AlarmKind::AscentRate), AlarmStatus::Acknowledged);
+    assert!(monitor.evaluate(&inputs).is_empty());
+    assert!(!monitor.active().contains(AlarmKind::AscentRate));
+
+    inputs.ascent_rate = 9.0;
+    monitor.evaluate(&inputs);
+    inputs.ascent_rate = 15.0;
+    assert!(monitor.evaluate(&inputs).contains(AlarmKind::AscentRate));
+}
+
+#[test]
+fn test_low_limit_alarms() {
+    let mut monitor = AlarmMonitor::default();
+    let inputs = AlarmInputs { depth: 30.0, ndl: Some(2.0), ppo2: 0.15, gas_pressure: None, ..Default::default() };
+
+    let raised = monitor.evaluate(&inputs);
+    assert!(raised.contains(AlarmKind::NdlLow));
+    assert!(raised.contains(AlarmKind::Ppo2Low));
+    assert!(!raised.contains(AlarmKind::GasReserve), "no reading, no alarm");
+    assert_eq!(monitor.highest_active(), Some(AlarmKind::Ppo2Low));
+}
+
+#[test]
+fn test_ceiling_violation_and_disabled_rules() {
+    let mut profile = AlarmProfile::default();
+    profile.rule_mut(AlarmKind::Ppo2Low).unwrap().enabled = false;
+    let mut monitor = AlarmMonitor::new(profile);
+    let inputs = AlarmInputs { depth: 4.0, ceiling: 6.0, ppo2: 0.1, ..Default::default() };
+
+    let raised = monitor.evaluate(&inputs);
+    assert!(raised.contains(AlarmKind::CeilingViolation));
+    assert!(!raised.contains(AlarmKind::Ppo2Low));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_alarm_profile_round_trip() {
+    let mut profile = AlarmProfile::default();
+    profile.rule_mut(AlarmKind::MaxDepth).unwrap().threshold = 30.0;
+
+    let json = serde_json::to_string(&profile).unwrap();
+    let restored: AlarmProfile = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, profile);
+}
+
+#[test]
+fn test_rules_are_found_by_kind_in_any_order() {
+    let mut profile = AlarmProfile::default();
+    profile.rules.reverse();
+    profile.rule_mut(AlarmKind::MaxDepth).unwrap().threshold = 30.0;
+    assert_eq!(profile.rule(AlarmKind::MaxDepth).unwrap().threshold, 30.0);
+    assert_eq!(profile.rule(AlarmKind::GasReserve).unwrap().kind, AlarmKind::GasReserve);
+
+    let mut monitor = AlarmMonitor::new(profile);
+    assert!(monitor.evaluate(&AlarmInputs { depth: 31.0, ppo2: 1.0, ..Default::default() }).contains(AlarmKind::MaxDepth));
+
+    let sorted = AlarmProfile::from_rules(profile.rules).unwrap();
+    assert_eq!(sorted.rules[0].kind, AlarmKind::MaxDepth);
+    assert_eq!(sorted.rule(AlarmKind::MaxDepth).unwrap().threshold, 30.0);
+
+    profile.rules[0] = profile.rules[1];
+    assert_eq!(AlarmProfile::from_rules(profile.rules), None, "a kind without a rule");
+    assert_eq!(profile.rule(AlarmKind::GasReserve), None, "a hand-built profile does not panic");
+    assert!(profile.rule_mut(AlarmKind::GasReserve).is_none());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_stored_profile_needs_every_kind() {
+    let mut profile = AlarmProfile::default();
+    profile.rules.swap(0, 8);
+    let restored: AlarmProfile = serde_json::from_str(&serde_json::to_string(&profile).unwrap()).unwrap();
+    assert_eq!(restored.rule(AlarmKind::MaxDepth), profile.rule(AlarmKind::MaxDepth));
+
+    profile.rules[0] = profile.rules[1];
+    assert!(serde_json::from_str::<AlarmProfile>(&serde_json::to_string(&profile).unwrap()).is_err());
+}
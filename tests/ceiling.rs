@@ -1,4 +1,7 @@
-use dive_computer_deco::{ceiling::{binary_ceiling, ceiling, max_ceiling}, tissue::Tissue, DiveParameters};
+use dive_computer_deco::{ceiling::{binary_ceiling, ceiling, ceiling_with_gf_exact, max_ceiling, GfSlope}, tissue::{calculate_tissue, Tissue}, DiveParameters};
+use dive_computer_deco::ceiling::{binary_ceiling_depth, binary_ceiling_with_gf, ceiling_pressure, first_stop_pressure, ceiling_with_gf, max_ceiling_pressure, pressure_to_depth, round_to_stop, try_ceiling_pressure};
+use dive_computer_deco::tissue_state::TissueState;
+use dive_computer_deco::DecoError;
 
 #[test]
 fn test_ceiling_with_high_n2_load() {
@@ -659,4 +662,147 @@ fn test_comprehensive_binary_ceiling_comparison() {
                    regular_result, binary_result, diff);
         }
     }
-}
\ No newline at end of file
+}
+
+fn loaded_tissues() -> [Tissue; 16] {
+    let mut tissues = [Tissue::default(); 16];
+    for (i, tissue) in tissues.iter_mut().enumerate() {
+        tissue.load_n2 = 4.2 - i as f32 * 0.15;
+    }
+    tissues
+}
+
+#[test]
+fn test_gf_slope_runs_from_gf_low_at_the_first_stop_to_gf_high_at_the_surface() {
+    let mut slope = GfSlope::new(0.3, 0.85, 1.0);
+    let tissues = loaded_tissues();
+    slope.anchor(&tissues);
+    let first_stop = slope.first_stop_pressure.unwrap();
+    assert!(first_stop > 1.0);
+    assert_eq!(((first_stop - 1.0) * 10.0 / 3.0).round() * 3.0, ((first_stop - 1.0) * 10.0).round());
+
+    assert!((slope.gf_at(first_stop, first_stop) - 0.3).abs() < 1e-5);
+    assert!((slope.gf_at(1.0, first_stop) - 0.85).abs() < 1e-5);
+    let halfway = slope.gf_at((first_stop + 1.0) / 2.0, first_stop);
+    assert!((halfway - 0.575).abs() < 1e-4, "{}", halfway);
+
+    // the ceiling tolerated by the leading compartment uses the GF of the ceiling itself
+    let (ceiling, _, gf) = slope.max_ceiling(&tissues);
+    assert!((gf - slope.gf_at(1.0 + ceiling / 10.0, first_stop)).abs() < 1e-4);
+    assert!(ceiling <= (first_stop - 1.0) * 10.0 + 1e-3);
+}
+
+#[test]
+fn test_gf_slope_stays_anchored_while_the_tissues_off_gas() {
+    let mut slope = GfSlope::new(0.3, 0.85, 1.0);
+    let mut tissues = loaded_tissues();
+    slope.anchor(&tissues);
+    let first_stop = slope.first_stop_pressure;
+
+    let mut previous = slope.max_ceiling(&tissues).0;
+    for _ in 0..30 {
+        for (i, tissue) in tissues.iter_mut().enumerate() {
+            *tissue = calculate_tissue(*tissue, i, 1.6, 20.0, 1.0);
+        }
+        slope.anchor(&tissues);
+        assert_eq!(slope.first_stop_pressure, first_stop);
+        let ceiling = slope.max_ceiling(&tissues).0;
+        assert!(ceiling <= previous);
+        previous = ceiling;
+    }
+    // a slope taken again from the off-gassed tissues brings GF low shallower and the ceiling deeper
+    assert!(GfSlope::new(0.3, 0.85, 1.0).max_ceiling(&tissues).0 > previous);
+
+    slope.release();
+    assert_eq!(slope.first_stop_pressure, None);
+}
+
+#[test]
+fn test_gf_slope_without_a_slope_matches_a_single_gf() {
+    let tissues = loaded_tissues();
+    for gf in [0.5, 0.85, 1.0] {
+        let (ceiling, tissue_index, slope_gf) = GfSlope::new(gf, gf, 1.0).max_ceiling(&tissues);
+        let expected = (0..16)
+            .map(|i| (ceiling_with_gf_exact(gf, gf, &tissues[i], i, 1.0).0, i))
+            .fold((f32::NEG_INFINITY, 0), |max, current| if current.0 > max.0 { current } else { max });
+        assert!((ceiling - expected.0).abs() < 1e-3, "{} {}", ceiling, expected.0);
+        assert_eq!(tissue_index, expected.1);
+        assert_eq!(slope_gf, gf);
+    }
+
+    // no stop at all leaves the slope unanchored
+    let mut slope = GfSlope::new(0.3, 0.85, 1.0);
+    slope.anchor(&[Tissue { load_n2: 0.79, load_he: 0.0 }; 16]);
+    assert_eq!(slope.first_stop_pressure, None);
+}
+
+#[test]
+fn test_round_to_stop() {
+    assert_eq!(round_to_stop(-4.0), 0);
+    assert_eq!(round_to_stop(0.0), 0);
+    assert_eq!(round_to_stop(0.01), 3);
+    assert_eq!(round_to_stop(3.0), 3);
+    assert_eq!(round_to_stop(3.01), 6);
+    assert_eq!(round_to_stop(17.9), 18);
+    assert_eq!(pressure_to_depth(2.8, 1.0), 18.0);
+    assert!(pressure_to_depth(0.9, 1.0) < 0.0);
+}
+
+#[test]
+fn test_every_ceiling_shares_the_exact_pressure() {
+    let mut tissues = loaded_tissues();
+    tissues[3].load_he = 0.8;
+    let state = TissueState::from(&tissues);
+    for gf in [0.3, 0.7, 1.0] {
+        let ceilings = state.ceilings(gf);
+        for (i, tissue) in tissues.iter().enumerate() {
+            let pressure = ceiling_pressure(gf, tissue, i);
+            let depth = pressure_to_depth(pressure, 1.0);
+            assert!((ceilings[i] - pressure).abs() < 1e-6);
+
+            // a single GF leaves the analytic ceiling as the exact one, rounded separately
+            assert!((ceiling_with_gf_exact(gf, gf, tissue, i, 1.0).0 - depth).abs() < 1e-4);
+            assert_eq!(ceiling_with_gf(gf, gf, tissue, i, 1.0, true), round_to_stop(depth));
+            assert_eq!(ceiling_with_gf(gf, gf, tissue, i, 1.0, false), depth.max(0.0) as u32);
+
+            let binary = binary_ceiling_depth(gf, tissue, i);
+            assert_eq!(binary, depth.max(0.0));
+            assert_eq!(binary_ceiling_with_gf(gf, *tissue, i, false), binary as u32);
+        }
+        let (deepest, compartment) = max_ceiling_pressure(gf, &tissues);
+        assert_eq!((deepest, compartment), state.max_ceiling(gf));
+    }
+    assert_eq!(first_stop_pressure(&tissues, 1.0), max_ceiling_pressure(1.0, &tissues).0.max(1.0));
+    assert_eq!(first_stop_pressure(&tissues[..2], 9.0), 9.0);
+
+    assert_eq!(ceiling_pressure(0.5, &Tissue { load_n2: 0.0, load_he: 0.0 }, 0), 0.0);
+    assert!(matches!(try_ceiling_pressure(1.2, &tissues[0], 0), Err(DecoError::InvalidInput { .. })));
+    assert!(matches!(try_ceiling_pressure(0.5, &tissues[0], 16), Err(DecoError::InvalidTissueIndex(16))));
+}
+
+#[test]
+fn test_first_stop_pressure_is_the_buhlmann_tolerated_pressure() {
+    let mut tissues = [Tissue { load_n2: 0.7, load_he: 0.0 }; 16];
+    tissues[0].load_n2 = 4.0;
+
+    // b * (p - a) = 0.5050 * (4.0 - 1.2599) of compartment 1 at GF 1.0, not the former
+    // b * (p - a) / (1 - b) of 2.795 bar
+    let pressure = first_stop_pressure(&tissues, 1.0);
+    assert!((pressure - 1.383_75).abs() < 1e-5, "{}", pressure);
+}
+
+#[test]
+fn test_gf_slope_ceiling_in_bar_and_metres() {
+    let mut slope = GfSlope::new(0.3, 0.85, 1.013);
+    let tissues = loaded_tissues();
+    slope.anchor(&tissues);
+    let (pressure, compartment, gf) = slope.max_ceiling_pressure(&tissues);
+    let (depth, depth_compartment, depth_gf) = slope.max_ceiling(&tissues);
+    assert!((pressure_to_depth(pressure, 1.013) - depth).abs() < 1e-4);
+    assert_eq!((compartment, gf), (depth_compartment, depth_gf));
+
+    // clean tissues tolerate less than the surface, only the depth is clamped
+    let clean = [Tissue { load_n2: 0.79, load_he: 0.0 }; 16];
+    assert!(slope.max_ceiling_pressure(&clean).0 < 1.013);
+    assert_eq!(slope.max_ceiling(&clean).0, 0.0);
+}
@@ -0,0 +1,38 @@
+use dive_computer_deco::conservatism::{Conservatism, LOW_GRADIENT_FACTORS, MEDIUM_GRADIENT_FACTORS};
+use dive_computer_deco::DiveParameters;
+
+#[test]
+fn test_presets_map_to_gradient_factors() {
+    assert_eq!(Conservatism::Medium.gradient_factors(), MEDIUM_GRADIENT_FACTORS);
+    assert_eq!(Conservatism::from_gradient_factors(0.45, 0.95), Conservatism::Low);
+    assert_eq!(Conservatism::from_gradient_factors(0.3, 0.85), Conservatism::Custom { gf_low: 0.3, gf_high: 0.85 });
+    assert_eq!(Conservatism::from_name("HIGH"), Some(Conservatism::High));
+    assert_eq!(Conservatism::from_name("custom"), None);
+
+    // presets get more conservative from low to high
+    let levels = [Conservatism::Low, Conservatism::Medium, Conservatism::High].map(Conservatism::gradient_factors);
+    assert!(levels.windows(2).all(|pair| pair[0].0 > pair[1].0 && pair[0].1 > pair[1].1));
+}
+
+#[test]
+fn test_dive_parameters_carry_the_conservatism() {
+    let mut params = DiveParameters::with_conservatism(Conservatism::Low);
+    assert_eq!((params.gf_low, params.gf_high), LOW_GRADIENT_FACTORS);
+    assert_eq!(params.descent_speed, DiveParameters::new(0.85, 0.3).descent_speed);
+
+    params.set_conservatism(Conservatism::Custom { gf_low: 0.2, gf_high: 0.6 });
+    assert_eq!((params.gf_low, params.gf_high), (0.2, 0.6));
+    assert_eq!(DiveParameters::new(0.85, 0.4).conservatism, Conservatism::Medium);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_conservatism_serialises_with_the_dive_parameters() {
+    let params = DiveParameters::with_conservatism(Conservatism::High);
+    let json = serde_json::to_string(&params).unwrap();
+    assert!(json.contains("\"conservatism\":\"High\""), "{}", json);
+
+    let custom = DiveParameters::new(0.7, 0.25);
+    let restored: DiveParameters = serde_json::from_str(&serde_json::to_string(&custom).unwrap()).unwrap();
+    assert_eq!(restored.conservatism, Conservatism::Custom { gf_low: 0.25, gf_high: 0.7 });
+}
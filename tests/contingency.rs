@@ -0,0 +1,181 @@
+#![cfg(feature = "alloc")]
+
+use dive_computer_deco::contingency::{deeper_longer_plans, lost_gas_plans, slate, Contingency, Loss};
+use dive_computer_deco::gas::{Cylinder, CylinderRole};
+use dive_computer_deco::plan::{DivePlan, Segment, SegmentTime, WaterType};
+use dive_computer_deco::simulate::RuntimeKind;
+use dive_computer_deco::tissue::Tissue;
+use dive_computer_deco::{default_tissue_load, DiveParameters, Gas};
+
+fn surface_tissues(temperature: f32) -> [Tissue; 16] {
+    let mut tissues = [Tissue::default(); 16];
+    for tissue in tissues.iter_mut() {
+        tissue.load_n2 = default_tissue_load(temperature);
+        tissue.load_he = 0.0;
+    }
+    tissues
+}
+
+/// 45 m on air with EAN50 from 21 m and oxygen at 6 m, one cylinder per gas
+fn deco_plan() -> DivePlan {
+    let mut plan = DivePlan::new(1.0, WaterType::Salt);
+    let air = plan.add_gas(Gas::air());
+    let ean50 = plan.add_gas(Gas::new(0.5, 0.0));
+    let oxygen = plan.add_gas(Gas::new(1.0, 0.0));
+    plan.add_segment(Segment::new(45.0, SegmentTime::Duration(25.0 * 60.0), air));
+    plan.add_segment(Segment::new(21.0, SegmentTime::Duration(2.0 * 60.0), ean50));
+    plan.add_segment(Segment::new(9.0, SegmentTime::Duration(4.0 * 60.0), ean50));
+    plan.add_segment(Segment::new(6.0, SegmentTime::Duration(12.0 * 60.0), oxygen));
+    plan.add_cylinder(Cylinder::new(24.0, 232.0, 220.0, air));
+    plan.add_cylinder(Cylinder { role: CylinderRole::Deco, ..Cylinder::new(11.1, 207.0, 200.0, ean50) });
+    plan.add_cylinder(Cylinder { role: CylinderRole::Deco, ..Cylinder::new(5.7, 207.0, 200.0, oxygen) });
+    plan
+}
+
+#[test]
+fn test_lost_deco_gases_switch_and_extend_the_stops() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let plan = deco_plan();
+    let contingencies = lost_gas_plans(&plan, &params, &surface_tissues(temperature), temperature, 10.0, 1.6).unwrap();
+
+    let losses: Vec<Loss> = contingencies.iter().map(|contingency| contingency.loss).collect();
+    assert_eq!(losses, [Loss::Gas(1), Loss::Gas(2), Loss::Cylinder(0), Loss::Cylinder(1), Loss::Cylinder(2)]);
+
+    // without EAN50 the 21 and 9 m segments are breathed on air, oxygen is too rich there
+    let lost_ean50 = &contingencies[0];
+    assert_eq!(lost_ean50.plan.segments.iter().map(|segment| segment.gas).collect::<Vec<_>>(), [0, 0, 0, 2]);
+    assert_eq!(lost_ean50.plan.segments[1].time, SegmentTime::Duration(15.0 * 60.0), "held until the ceiling clears 9 m");
+    assert!(lost_ean50.extra_time > 60.0);
+    assert!(lost_ean50.runtime.iter().any(|entry| entry.kind == RuntimeKind::Stop));
+    assert!(lost_ean50.enough);
+    assert_eq!(lost_ean50.plan.cylinders.len(), 2);
+
+    // without oxygen EAN50 takes over at 6 m
+    let lost_oxygen = &contingencies[1];
+    assert_eq!(lost_oxygen.plan.segments[3].gas, 1);
+    assert!(lost_oxygen.extra_time < lost_ean50.extra_time, "GF low at 21 m makes the air stop there the longest");
+    assert_eq!(lost_oxygen.runtime[3].gas, Gas::new(0.5, 0.0));
+
+    // losing the only deco cylinder of a gas is losing the gas
+    assert_eq!(contingencies[3].plan.segments, lost_ean50.plan.segments);
+    assert_eq!(contingencies[4].total_runtime, lost_oxygen.total_runtime);
+}
+
+/// 45 m on air with EAN50 and oxygen carried, the stops left to the planner
+fn computed_deco_plan() -> DivePlan {
+    let mut plan = DivePlan::new(1.0, WaterType::Salt);
+    let air = plan.add_gas(Gas::air());
+    let ean50 = plan.add_gas(Gas::new(0.5, 0.0));
+    let oxygen = plan.add_gas(Gas::new(1.0, 0.0));
+    plan.add_segment(Segment::new(45.0, SegmentTime::Duration(25.0 * 60.0), air));
+    plan.add_cylinder(Cylinder::new(24.0, 232.0, 220.0, air));
+    plan.add_cylinder(Cylinder { role: CylinderRole::Deco, ..Cylinder::new(11.1, 207.0, 200.0, ean50) });
+    plan.add_cylinder(Cylinder { role: CylinderRole::Deco, ..Cylinder::new(5.7, 207.0, 200.0, oxygen) });
+    plan.deco_ppo2 = Some(1.6);
+    plan
+}
+
+#[test]
+fn test_lost_deco_gases_extend_computed_stops() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let plan = computed_deco_plan();
+    let contingencies = lost_gas_plans(&plan, &params, &surface_tissues(temperature), temperature, 10.0, 1.6).unwrap();
+
+    // carried gases are lost gases too, even when no segment names them
+    let losses: Vec<Loss> = contingencies.iter().map(|contingency| contingency.loss).collect();
+    assert_eq!(losses, [Loss::Gas(1), Loss::Gas(2), Loss::Cylinder(0), Loss::Cylinder(1), Loss::Cylinder(2)]);
+    let gases = |contingency: &Contingency| contingency.runtime.iter().map(|entry| entry.gas).collect::<Vec<_>>();
+
+    let lost_ean50 = &contingencies[0];
+    assert!(!gases(lost_ean50).contains(&Gas::new(0.5, 0.0)));
+    assert!(gases(lost_ean50).contains(&Gas::new(1.0, 0.0)), "oxygen is still switched to at 6 m");
+    assert!(lost_ean50.extra_time > 60.0);
+    assert!(lost_ean50.enough);
+
+    let lost_oxygen = &contingencies[1];
+    assert!(!gases(lost_oxygen).contains(&Gas::new(1.0, 0.0)));
+    assert_eq!(lost_oxygen.runtime.iter().rfind(|entry| entry.kind == RuntimeKind::Stop).unwrap().gas, Gas::new(0.5, 0.0));
+    assert!(lost_oxygen.extra_time > lost_ean50.extra_time);
+
+    // losing the only deco cylinder of a gas is losing the gas
+    assert_eq!(contingencies[3].total_runtime, lost_ean50.total_runtime);
+    assert_eq!(contingencies[4].total_runtime, lost_oxygen.total_runtime);
+    assert_eq!(contingencies[3].usage.cylinders.len(), 2);
+}
+
+#[test]
+fn test_lost_cylinder_checks_the_remaining_gas() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let mut plan = deco_plan();
+    let contingencies = lost_gas_plans(&plan, &params, &surface_tissues(temperature), temperature, 10.0, 1.6).unwrap();
+
+    // nothing else carries the bottom gas
+    let lost_back_gas = &contingencies[2];
+    assert_eq!(lost_back_gas.loss, Loss::Cylinder(0));
+    assert!(!lost_back_gas.enough);
+    assert!(lost_back_gas.usage.unassigned > 0.0);
+
+    // with sidemount cylinders the other one carries on, and has to hold the whole dive
+    plan.cylinders[0] = Cylinder::new(11.1, 207.0, 200.0, 0);
+    plan.cylinders.insert(1, Cylinder::new(11.1, 207.0, 200.0, 0));
+    let contingencies = lost_gas_plans(&plan, &params, &surface_tissues(temperature), temperature, 10.0, 1.6).unwrap();
+    let lost_side = contingencies.iter().find(|contingency| contingency.loss == Loss::Cylinder(0)).unwrap();
+    assert_eq!(lost_side.extra_time, 200.0, "the 21 m stop is held to 6 min like in every contingency");
+    assert_eq!(lost_side.plan.segments[0].gas, 0);
+    assert!(!lost_side.enough, "one AL80 does not last 25 min at 45 m");
+    assert!(lost_side.usage.cylinders[0].end_pressure < 0.0);
+}
+
+#[test]
+fn test_deeper_longer_grid() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let plan = deco_plan();
+    let variants = deeper_longer_plans(&plan, &params, &surface_tissues(temperature), temperature, 10.0, &[0.0, 3.0, 6.0], &[0.0, 3.0 * 60.0]).unwrap();
+
+    assert_eq!(variants.len(), 6);
+    assert_eq!((variants[3].deeper, variants[3].longer), (3.0, 180.0));
+    let planned = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    // the planned 2 min at 21 m do not clear the ceiling to 9 m, the stop is held 4 min longer
+    // and saves the 37 s the planned dive spends at 3 m
+    assert!((variants[0].total_runtime - *planned.times.last().unwrap() - 200.0).abs() < 0.01);
+    let stops: Vec<(f32, f32)> = variants[0].stops().iter().map(|(depth, duration)| (*depth, duration.round())).collect();
+    assert_eq!(stops, [(21.0, 360.0), (9.0, 240.0), (6.0, 720.0)]);
+
+    // deeper and longer both add decompression and gas, the combination most of all
+    assert_eq!(variants[5].plan.segments[0].depth, 51.0);
+    assert_eq!(variants[5].plan.segments[0].time, SegmentTime::Duration(28.0 * 60.0));
+    for variant in variants[1..].iter() {
+        assert!(variant.time_to_surface > variants[0].time_to_surface);
+        assert!(variant.usage.cylinders[0].litres > variants[0].usage.cylinders[0].litres);
+    }
+    assert!(variants[5].time_to_surface > variants[4].time_to_surface && variants[5].time_to_surface > variants[1].time_to_surface);
+    assert!(variants[5].stops().iter().any(|(depth, _)| *depth == 3.0));
+
+    let slate = slate(&variants);
+    assert_eq!(slate.lines().count(), 6);
+    assert!(slate.starts_with(" 45 m  25 min (+0/+0)  TTS "), "{}", slate);
+    assert!(slate.lines().nth(5).unwrap().starts_with(" 51 m  28 min (+6/+3)"));
+}
+
+#[test]
+fn test_bottom_time_is_not_held_for_the_ceiling() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    // the ceiling at the end of 20 min at 60 m is far below the 12 m stop
+    let mut plan = DivePlan::new(1.0, WaterType::Salt);
+    let air = plan.add_gas(Gas::air());
+    plan.add_segment(Segment::new(60.0, SegmentTime::Duration(20.0 * 60.0), air));
+    plan.add_segment(Segment::new(12.0, SegmentTime::Duration(60.0), air));
+    plan.add_segment(Segment::new(6.0, SegmentTime::Duration(60.0), air));
+    let planned = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    assert!(planned.ceilings[planned.times.iter().position(|time| *time >= 20.0 * 60.0).unwrap()] > 12);
+
+    let variants = deeper_longer_plans(&plan, &params, &surface_tissues(temperature), temperature, 10.0, &[0.0], &[0.0]).unwrap();
+    let held = &variants[0].plan;
+    assert_eq!(held.segments[0].time, SegmentTime::Duration(20.0 * 60.0), "bottom time stays as planned");
+    assert!(matches!(held.segments[1].time, SegmentTime::Duration(duration) if duration > 60.0), "the 12 m stop waits for the ceiling");
+}
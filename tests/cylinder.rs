@@ -0,0 +1,55 @@
+use dive_computer_deco::cylinder::{find, find_by_size, Material, CATALOGUE, CUBIC_FOOT};
+use dive_computer_deco::gas::{compressibility, Cylinder};
+use dive_computer_deco::Gas;
+
+#[test]
+fn test_compressibility() {
+    let air = Gas::air();
+    let helium = Gas::new(0.0, 1.0);
+    let trimix = Gas::new(0.18, 0.45);
+
+    assert!((compressibility(&air, 1.0) - 1.0).abs() < 0.001);
+    assert!(compressibility(&air, 232.0) > 1.04 && compressibility(&air, 232.0) < 1.07);
+    assert!(compressibility(&air, 300.0) > compressibility(&air, 232.0));
+    assert!(compressibility(&helium, 232.0) > 1.09 && compressibility(&helium, 232.0) < 1.13);
+    assert!(compressibility(&trimix, 232.0) > compressibility(&air, 232.0));
+    assert!(compressibility(&trimix, 232.0) < compressibility(&helium, 232.0));
+}
+
+#[test]
+fn test_real_gas_volume() {
+    let air = Gas::air();
+    let s12 = Cylinder::new(12.0, 300.0, 300.0, 0);
+
+    // the ideal gas law overstates the contents by about 5% at 232 bar and 10% at 300 bar
+    for (pressure, low, high) in [(232.0, 0.04, 0.07), (300.0, 0.09, 0.12)] {
+        let overstated = 12.0 * pressure / s12.gas_volume(pressure, &air) - 1.0;
+        assert!(overstated > low && overstated < high, "{} at {} bar", overstated, pressure);
+    }
+    assert!((s12.gas_volume(20.0, &air) - 240.0).abs() / 240.0 < 0.01, "close to ideal at low pressure");
+
+    for gas in [air, Gas::new(0.32, 0.0), Gas::new(0.18, 0.45), Gas::new(0.0, 1.0)] {
+        let litres = s12.gas_volume(250.0, &gas);
+        assert!((s12.pressure_for(litres, &gas) - 250.0).abs() < 0.01);
+        assert!((s12.pressure_drop(s12.gas_volume(300.0, &gas) - litres, &gas) - 50.0).abs() < 0.01);
+    }
+}
+
+#[test]
+fn test_catalogue() {
+    let air = Gas::air();
+    for spec in CATALOGUE.iter() {
+        assert!(spec.volume > 0.0 && spec.working_pressure > 0.0, "{}", spec.name);
+        // imperial rated capacities are real-gas volumes, LP cylinders are rated with a 10% overfill
+        if let Some(rated) = spec.rated_capacity {
+            let capacity = spec.capacity(&air) / CUBIC_FOOT;
+            assert!((capacity - rated).abs() / rated < 0.05, "{}: {} cuft", spec.name, capacity);
+        }
+    }
+
+    let al80 = find("al80").unwrap();
+    assert_eq!(al80.material, Material::Aluminium);
+    assert_eq!(find_by_size(11.1, 207.0), Some(al80));
+    assert_eq!(al80.cylinder(200.0, 1), Cylinder::new(11.1, 207.0, 200.0, 1));
+    assert!(find("AL81").is_none());
+}
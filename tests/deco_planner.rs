@@ -0,0 +1,153 @@
+#![cfg(all(feature = "std", feature = "serde"))]
+
+use std::process::Command;
+
+use dive_computer_deco::gas::{turn_pressure, Cylinder, GasRule};
+use dive_computer_deco::Gas;
+
+fn planner(args: &[&str]) -> (i32, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_deco-planner")).args(args).output().unwrap();
+    (output.status.code().unwrap(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn test_json_output_within_limits() {
+    let (code, stdout) = planner(&["--depth", "40", "--time", "20", "--gf", "30/85", "--format", "json"]);
+    assert_eq!(code, 0);
+
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let runtime = report["runtime"].as_array().unwrap();
+    assert_eq!(runtime[0]["kind"], "Level");
+    assert_eq!(runtime.last().unwrap()["kind"], "Surface");
+    assert!(report["deco_time"].as_f64().unwrap() > 0.0);
+}
+
+#[test]
+fn test_csv_output_and_limit_exit_code() {
+    let (code, stdout) = planner(&["--level", "40:15:50", "--format", "csv"]);
+    assert_eq!(code, 2, "EAN50 at 40 m is above the ppO2 limit");
+    assert!(stdout.starts_with("kind,depth_m,duration_min,runtime_min,o2,he,gas_used_l\nlevel,40.0,15.00"));
+}
+
+#[test]
+fn test_invalid_arguments() {
+    assert_eq!(planner(&["--depth", "30"]).0, 1);
+    assert_eq!(planner(&["--depth", "30", "--time", "20", "--conservatism", "extreme"]).0, 1);
+    assert_eq!(planner(&["--depth", "30", "--time", "20", "--gf", "90/120"]).0, 1);
+    assert_eq!(planner(&["--depth", "30", "--time", "20", "--ascent-rate", "0"]).0, 1);
+    assert_eq!(planner(&["--gas", "banana"]).0, 1);
+    assert_eq!(planner(&[]).0, 1);
+}
+
+#[test]
+fn test_cylinder_gas_report() {
+    let (code, stdout) = planner(&["--depth", "30", "--time", "20", "--sac", "15", "--cylinder", "12:200", "--format", "json"]);
+    assert_eq!(code, 2, "the reserve for the 6 m and 3 m stops is a bar more than is left");
+
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let used: f64 = report["runtime"].as_array().unwrap().iter().map(|row| row["gas_used"].as_f64().unwrap()).sum();
+    let cylinder = &report["cylinders"][0];
+    assert!((cylinder["used"].as_f64().unwrap() - used).abs() < 0.5);
+    assert!((cylinder["end_pressure"].as_f64().unwrap() - (200.0 - cylinder["used_bar"].as_f64().unwrap())).abs() < 0.1);
+    assert!(cylinder["used_bar"].as_f64().unwrap() > used / 12.0, "real-gas volumes");
+
+    assert!(!cylinder["minimum_gas"]["pass"].as_bool().unwrap());
+    assert!(cylinder.get("turn").is_none(), "no gas rule, no turn pressure");
+
+    let (code, _) = planner(&["--depth", "30", "--time", "40", "--cylinder", "7:200"]);
+    assert_eq!(code, 2, "a 7 L cylinder does not last 40 min at 30 m");
+
+    let (code, stdout) = planner(&["--depth", "30", "--time", "20", "--sac", "15", "--cylinder", "12:150"]);
+    assert_eq!(code, 2, "150 bar leaves less than the minimum gas at the end of the bottom time");
+    assert!(stdout.contains("minimum gas is"));
+}
+
+#[test]
+fn test_cylinder_roles_and_reserve_warning() {
+    let (code, stdout) = planner(&["--depth", "30", "--time", "25", "--cylinder", "AL80:200::stage", "--cylinder", "D7 232:200", "--cylinder", "AL40:200::bailout", "--format", "json"]);
+    assert_eq!(code, 0, "a reserve breach only warns");
+
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let cylinders = report["cylinders"].as_array().unwrap();
+    assert_eq!(cylinders[0]["cylinder"]["role"], "Stage");
+    assert!(cylinders[0]["below_reserve"].as_f64().is_some());
+    assert!(cylinders[1]["used"].as_f64().unwrap() > 0.0);
+    assert_eq!(cylinders[2]["used"], 0.0);
+    assert!(report["warnings"][0].as_str().unwrap().starts_with("cylinder 1 (Air) drops below its 50 bar reserve"));
+
+    let (_, stdout) = planner(&["--depth", "30", "--time", "25", "--cylinder", "AL80:200::stage", "--reserve", "30"]);
+    assert!(!stdout.contains("WARNING"), "{}", stdout);
+    assert_eq!(planner(&["--depth", "30", "--time", "25", "--cylinder", "AL80:200:21:side"]).0, 1);
+}
+
+#[test]
+fn test_lost_gas_contingencies() {
+    let (_, stdout) = planner(&["--level", "45:25", "--level", "21:2:50", "--level", "9:4:50", "--level", "6:12:100", "--cylinder", "24:220", "--cylinder", "11.1:200:50:deco", "--cylinder", "5.7:200:100:deco", "--lost-gas", "--format", "json"]);
+
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let contingencies = report["contingencies"].as_array().unwrap();
+    assert_eq!(contingencies.len(), 5);
+    assert_eq!(contingencies[0]["lost"], "EAN50");
+    assert!(contingencies[0]["extra_time"].as_f64().unwrap() > 0.0);
+    assert_eq!(contingencies[0]["enough"], true);
+    assert_eq!(contingencies[2]["lost"], "cylinder 1 (Air)");
+    assert_eq!(contingencies[2]["enough"], false);
+
+    // computed stops switch to the carried deco gas, losing it lengthens them
+    let (_, stdout) = planner(&["--depth", "40", "--time", "25", "--cylinder", "24:220", "--cylinder", "7:200:50:deco", "--lost-gas", "--format", "json"]);
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert!(report["cylinders"][1]["end_pressure"].as_f64().unwrap() < 200.0);
+    let contingencies = report["contingencies"].as_array().unwrap();
+    assert_eq!(contingencies[0]["lost"], "EAN50");
+    assert!(contingencies[0]["extra_time"].as_f64().unwrap() > 60.0);
+
+    let (_, stdout) = planner(&["--depth", "30", "--time", "20", "--format", "json"]);
+    assert!(!stdout.contains("contingencies"));
+}
+
+#[test]
+fn test_deeper_longer_slate() {
+    let (_, stdout) = planner(&["--depth", "40", "--time", "20", "--cylinder", "24:220", "--deeper", "3", "--longer", "3", "--format", "json"]);
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let variants = report["variants"].as_array().unwrap();
+    assert_eq!(variants.len(), 4);
+    assert_eq!((variants[3]["deeper"].as_f64(), variants[3]["longer"].as_f64()), (Some(3.0), Some(180.0)));
+    assert!(variants[3]["time_to_surface"].as_f64().unwrap() > variants[0]["time_to_surface"].as_f64().unwrap());
+    assert!((variants[0]["total_runtime"].as_f64().unwrap() - report["total_runtime"].as_f64().unwrap()).abs() <= 10.0);
+
+    let (_, stdout) = planner(&["--depth", "40", "--time", "20", "--deeper", "3,6"]);
+    assert!(stdout.contains("\n 46 m  20 min (+6/+0)  TTS"), "{}", stdout);
+    assert_eq!(planner(&["--depth", "40", "--time", "20", "--longer", "0"]).0, 1);
+}
+
+#[test]
+fn test_decompression_table() {
+    let (code, stdout) = planner(&["--table-depths", "30,40", "--table-times", "20,30", "--gf", "40/80", "--ascent-rate", "9", "--format", "csv"]);
+    assert_eq!(code, 2, "40 m for 30 min cuts the 3 m stop at the stop time limit");
+    assert_eq!(stdout.lines().count(), 5);
+    assert!(stdout.lines().nth(3).unwrap().starts_with("40,20,"));
+    assert!(stdout.lines().nth(4).unwrap().ends_with(",LIMIT"), "{}", stdout);
+
+    let (code, stdout) = planner(&["--table-depths", "30", "--table-times", "20", "--gas", "32"]);
+    assert_eq!(code, 0);
+    assert!(stdout.starts_with("O2 32% He 0%, GF 30/85, descent 20 m/min, ascent 10 m/min\n\n### 30 m, NDL"), "{}", stdout);
+    let (_, stdout) = planner(&["--table-depths", "30", "--table-times", "20", "--conservatism", "high"]);
+    assert!(stdout.starts_with("O2 21% He 0%, GF 35/75,"), "{}", stdout);
+
+    assert_eq!(planner(&["--table-depths", "30"]).0, 1);
+    assert_eq!(planner(&["--depth", "30", "--time", "20", "--format", "markdown"]).0, 1);
+}
+
+#[test]
+fn test_turn_pressure_with_mismatched_buddy() {
+    let (code, stdout) = planner(&["--depth", "20", "--time", "40", "--sac", "15", "--cylinder", "D12 232:220", "--rule", "thirds", "--buddy", "AL80:200", "--format", "json"]);
+    assert_eq!(code, 0);
+
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let turn = &report["cylinders"][0]["turn"];
+    let expected = turn_pressure(&Cylinder::new(24.0, 232.0, 220.0, 0), &Gas::air(), GasRule::Thirds, &[Cylinder::new(11.1, 207.0, 200.0, 0)]);
+    assert!((turn["turn_pressure"].as_f64().unwrap() - expected as f64).abs() < 0.1);
+    assert_eq!(turn["depth"], 20.0);
+
+    assert_eq!(planner(&["--depth", "20", "--time", "40", "--rule", "2"]).0, 1);
+}
@@ -0,0 +1,285 @@
+#![cfg(feature = "alloc")]
+
+use dive_computer_deco::gas::{gas_usage, minimum_gas, sac_rate_from_usage, turn_points, turn_pressure, Cylinder, CylinderRole, GasRule, MinimumGasSettings};
+use dive_computer_deco::plan::{DivePlan, Segment, SegmentTime, WaterType};
+use dive_computer_deco::simulate::RuntimeKind;
+use dive_computer_deco::tissue::Tissue;
+use dive_computer_deco::{default_tissue_load, DiveParameters, Gas};
+
+fn surface_tissues(temperature: f32) -> [Tissue; 16] {
+    let mut tissues = [Tissue::default(); 16];
+    for tissue in tissues.iter_mut() {
+        tissue.load_n2 = default_tissue_load(temperature);
+        tissue.load_he = 0.0;
+    }
+    tissues
+}
+
+fn single_level_plan(depth: f32, minutes: f32) -> DivePlan {
+    let mut plan = DivePlan::new(1.0, WaterType::Salt);
+    let air = plan.add_gas(Gas::air());
+    plan.add_segment(Segment::new(depth, SegmentTime::Duration(minutes * 60.0), air));
+    plan.add_cylinder(Cylinder::new(12.0, 232.0, 200.0, air));
+    plan
+}
+
+#[test]
+fn test_single_level_consumption() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let plan = single_level_plan(30.0, 20.0);
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    let usage = gas_usage(&plan, &params, &outputs);
+
+    // 1.5 min descent at 2.5 bar on average, then 20 min at 4 bar, at 20 L/min
+    let level = usage.segments[0];
+    assert_eq!(level.entry.kind, RuntimeKind::Level);
+    assert!((level.litres - 20.0 * (1.5 * 2.5 + 20.0 * 4.0)).abs() < 1.0, "{}", level.litres);
+    assert!((level.bar - plan.cylinders[0].pressure_drop(level.litres, &Gas::air())).abs() < 0.01);
+    assert_eq!(usage.segments.last().unwrap().entry.kind, RuntimeKind::Surface);
+
+    let cylinder = usage.cylinders[0];
+    assert!((cylinder.litres - usage.total_litres()).abs() < 0.1);
+    assert!((cylinder.end_pressure - (200.0 - plan.cylinders[0].pressure_drop(cylinder.litres, &Gas::air()))).abs() < 0.01);
+    assert!(cylinder.bar > cylinder.litres / 12.0, "real gas drops faster than volume x pressure");
+    let by_segment: f32 = usage.segments.iter().map(|segment| segment.bar).sum();
+    assert!((by_segment - cylinder.bar).abs() < 0.01);
+    assert_eq!(usage.unassigned, 0.0);
+    assert_eq!(usage.remaining[0].len(), outputs.times.len());
+    assert_eq!(usage.remaining[0][0], 200.0);
+    assert_eq!(*usage.remaining[0].last().unwrap(), cylinder.end_pressure);
+}
+
+#[test]
+fn test_deco_sac_applies_after_the_last_level() {
+    let temperature = 20.0;
+    let mut params = DiveParameters::new(0.85, 0.3);
+    let plan = single_level_plan(40.0, 25.0);
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+
+    let same = gas_usage(&plan, &params, &outputs);
+    params.deco_sac_rate = params.sac_rate * 2.0;
+    let doubled = gas_usage(&plan, &params, &outputs);
+
+    assert!(same.segments.iter().any(|segment| segment.entry.kind == RuntimeKind::Stop));
+    assert_eq!(doubled.segments[0].litres, same.segments[0].litres);
+    for (doubled, same) in doubled.segments.iter().zip(same.segments.iter()).skip(1) {
+        assert!((doubled.litres - 2.0 * same.litres).abs() < 0.01);
+    }
+}
+
+#[test]
+fn test_gases_draw_from_their_own_cylinder() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let mut plan = DivePlan::new(1.0, WaterType::Salt);
+    let air = plan.add_gas(Gas::air());
+    let ean50 = plan.add_gas(Gas::new(0.5, 0.0));
+    let ean80 = plan.add_gas(Gas::new(0.8, 0.0));
+    plan.add_segment(Segment::new(40.0, SegmentTime::Duration(20.0 * 60.0), air));
+    plan.add_segment(Segment::new(21.0, SegmentTime::Duration(3.0 * 60.0), ean50));
+    plan.add_segment(Segment::new(6.0, SegmentTime::Duration(10.0 * 60.0), ean80));
+    plan.add_cylinder(Cylinder::new(24.0, 232.0, 220.0, air));
+    plan.add_cylinder(Cylinder::new(7.0, 207.0, 200.0, ean50));
+
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    let usage = gas_usage(&plan, &params, &outputs);
+
+    assert_eq!(usage.segments[0].cylinder, Some(0));
+    assert_eq!(usage.segments[1].cylinder, Some(1));
+    assert_eq!(usage.segments[2].cylinder, Some(1), "only the move up to 6 m, still on EAN50, comes from a cylinder");
+    assert!(usage.cylinders[0].litres > usage.cylinders[1].litres);
+    assert!(usage.cylinders[1].litres > 0.0);
+    assert!(usage.unassigned > 0.0, "EAN80 has no cylinder");
+    assert!((usage.cylinders[0].litres + usage.cylinders[1].litres + usage.unassigned - usage.total_litres()).abs() < 0.5);
+
+    // the deco cylinder stays full until the switch at 21 m
+    let switch = outputs.gases.iter().position(|gas| *gas == Gas::new(0.5, 0.0)).unwrap();
+    assert!(usage.remaining[1][..switch].iter().all(|pressure| *pressure == 200.0));
+}
+
+#[test]
+fn test_cylinders_of_one_gas_follow_their_roles() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let mut plan = single_level_plan(30.0, 30.0);
+    plan.cylinders[0] = Cylinder { role: CylinderRole::Bailout, ..plan.cylinders[0] };
+    let stage = plan.add_cylinder(Cylinder { role: CylinderRole::Stage, reserve: 100.0, ..Cylinder::new(11.1, 207.0, 200.0, 0) });
+    let left = plan.add_cylinder(Cylinder::new(11.1, 207.0, 200.0, 0));
+    let right = plan.add_cylinder(Cylinder::new(11.1, 207.0, 180.0, 0));
+
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    let usage = gas_usage(&plan, &params, &outputs);
+
+    // the stage is breathed down to its reserve first and the bailout is never touched
+    assert_eq!(usage.sources[1], Some(stage));
+    assert_eq!(usage.cylinders[0].litres, 0.0);
+    assert!((usage.cylinders[stage].end_pressure - 100.0).abs() < 2.0, "{}", usage.cylinders[stage].end_pressure);
+    assert!(usage.cylinders[stage].below_reserve.is_some());
+
+    // sidemount cylinders are then breathed in turn and end up balanced
+    assert!(usage.cylinders[left].litres > usage.cylinders[right].litres);
+    assert!((usage.cylinders[left].end_pressure - usage.cylinders[right].end_pressure).abs() < 2.0);
+    assert!(usage.cylinders[left].below_reserve.is_none());
+    assert_eq!(usage.unassigned, 0.0);
+    assert!((usage.cylinders.iter().map(|cylinder| cylinder.litres).sum::<f32>() - usage.total_litres()).abs() < 0.5);
+}
+
+#[test]
+fn test_bailout_is_breathed_when_nothing_else_is_left() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let mut plan = single_level_plan(30.0, 30.0);
+    plan.cylinders[0] = Cylinder::new(7.0, 232.0, 100.0, 0);
+    let bailout = plan.add_cylinder(Cylinder { role: CylinderRole::Bailout, ..Cylinder::new(7.0, 232.0, 200.0, 0) });
+
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    let usage = gas_usage(&plan, &params, &outputs);
+
+    // the back gas is breathed past its reserve, the bailout only once it is empty
+    assert!(usage.cylinders[0].below_reserve.is_some());
+    let first_bailout = usage.sources.iter().position(|source| *source == Some(bailout)).unwrap();
+    assert!(usage.remaining[0][first_bailout - 1] <= 0.0);
+    assert!(usage.remaining[0][first_bailout - 2] > 0.0);
+    assert!(usage.cylinders[bailout].litres > 0.0);
+}
+
+#[test]
+fn test_sac_rate_from_usage_inverts_consumption() {
+    let temperature = 20.0;
+    let mut params = DiveParameters::new(0.85, 0.3);
+    params.sac_rate = 14.0;
+    params.deco_sac_rate = 14.0;
+    let plan = single_level_plan(25.0, 30.0);
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    let usage = gas_usage(&plan, &params, &outputs);
+
+    let sac_rate = sac_rate_from_usage(usage.total_litres(), &outputs.times, &outputs.pressures).unwrap();
+    assert!((sac_rate - 14.0).abs() < 0.01);
+    assert_eq!(sac_rate_from_usage(100.0, &[0.0], &[1.0]), None);
+}
+
+#[test]
+fn test_minimum_gas_from_the_worst_point() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let plan = single_level_plan(30.0, 20.0);
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    let settings = MinimumGasSettings::default();
+    let minimum = minimum_gas(&plan, &params, &outputs, &settings, temperature, 10.0);
+
+    // around the end of the bottom time: deepest, most loaded and least gas left
+    let reserve = minimum[0];
+    assert!(reserve.depth > 29.0, "{} m", reserve.depth);
+    assert!(reserve.time > 20.0 * 60.0 && reserve.time <= 22.0 * 60.0, "{} s", reserve.time);
+
+    // at least the problem solving time at 4 bar and a direct ascent at 2.5 bar on average, for two divers
+    let direct = 2.0 * 30.0 * (1.0 * 4.0 + 3.0 * 2.5);
+    assert!(reserve.litres > direct, "{} L", reserve.litres);
+    assert!((plan.cylinders[0].gas_volume(reserve.bar, &Gas::air()) - reserve.litres).abs() < 0.5);
+    let usage = gas_usage(&plan, &params, &outputs);
+    let at_worst = outputs.times.iter().position(|time| *time == reserve.time).unwrap();
+    assert_eq!(reserve.available, usage.remaining[0][at_worst]);
+    assert_eq!(reserve.pass, reserve.available >= reserve.bar);
+
+    let alone = minimum_gas(&plan, &params, &outputs, &MinimumGasSettings { divers: 1, ..settings }, temperature, 10.0);
+    assert!((alone[0].litres * 2.0 - reserve.litres).abs() < 0.1);
+}
+
+#[test]
+fn test_minimum_gas_pass_and_fail() {
+    let temperature = 20.0;
+    let mut params = DiveParameters::new(0.85, 0.3);
+    params.sac_rate = 15.0;
+    params.deco_sac_rate = 15.0;
+    let mut plan = single_level_plan(30.0, 20.0);
+    let unused = plan.add_gas(Gas::new(0.5, 0.0));
+    plan.add_cylinder(Cylinder::new(7.0, 207.0, 200.0, unused));
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+
+    let minimum = minimum_gas(&plan, &params, &outputs, &MinimumGasSettings::default(), temperature, 10.0);
+    assert!(!minimum[0].pass, "with a 6 m first stop a full 12 L is short of the reserve after 20 min at 30 m");
+    assert!(minimum[0].bar - minimum[0].available < 2.0);
+    assert_eq!(minimum[1].litres, 0.0);
+    assert!(minimum[1].pass, "a cylinder that is never breathed needs no reserve");
+
+    plan.cylinders[0].start_pressure = 150.0;
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    let minimum = minimum_gas(&plan, &params, &outputs, &MinimumGasSettings::default(), temperature, 10.0);
+    assert!(!minimum[0].pass);
+    assert!(minimum[0].available < minimum[0].bar);
+}
+
+#[test]
+fn test_minimum_gas_of_a_deco_stage_within_its_mod() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let mut plan = DivePlan::new(1.0, WaterType::Salt);
+    let air = plan.add_gas(Gas::air());
+    let ean50 = plan.add_gas(Gas::new(0.5, 0.0));
+    plan.add_segment(Segment::new(40.0, SegmentTime::Duration(25.0 * 60.0), air));
+    // switched too deep: EAN50 at 27 m is past its MOD
+    plan.add_segment(Segment::new(27.0, SegmentTime::Duration(60.0), ean50));
+    plan.add_cylinder(Cylinder::new(24.0, 232.0, 220.0, air));
+    plan.add_cylinder(Cylinder { role: CylinderRole::Deco, ..Cylinder::new(7.0, 207.0, 200.0, ean50) });
+    plan.deco_ppo2 = Some(1.6);
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+
+    let settings = MinimumGasSettings::default();
+    let stage = minimum_gas(&plan, &params, &outputs, &settings, temperature, 10.0)[1];
+    let at_worst = outputs.times.iter().position(|time| *time == stage.time).unwrap();
+    assert_eq!(outputs.gases[at_worst], Gas::new(0.5, 0.0));
+    assert!(outputs.pressures[at_worst] * 0.5 <= settings.max_ppo2, "{} m", stage.depth);
+    assert!(stage.depth < 22.0, "{} m", stage.depth);
+
+    // counting the samples past the MOD moves the worst point down to them
+    let past_mod = minimum_gas(&plan, &params, &outputs, &MinimumGasSettings { max_ppo2: 2.0, ..settings }, temperature, 10.0)[1];
+    assert!(past_mod.depth > 26.0, "{} m", past_mod.depth);
+    assert!(past_mod.bar > stage.bar);
+}
+
+#[test]
+fn test_turn_pressure_rules_and_mismatched_buddy() {
+    let air = Gas::air();
+    let twin12 = Cylinder::new(24.0, 232.0, 210.0, 0);
+    let supply = twin12.gas_volume(210.0, &air);
+    let used_at_turn = |cylinder: &Cylinder, turn: f32| cylinder.gas_volume(cylinder.start_pressure, &air) - cylinder.gas_volume(turn, &air);
+    for (rule, fraction) in [(GasRule::Thirds, 1.0 / 3.0), (GasRule::Halves, 0.5), (GasRule::Fraction(0.25), 0.25)] {
+        let turn = turn_pressure(&twin12, &air, rule, &[]);
+        assert!((used_at_turn(&twin12, turn) - supply * fraction).abs() < 1.0, "{:?}", rule);
+    }
+    assert!(turn_pressure(&twin12, &air, GasRule::Thirds, &[]) < 140.0, "a third of the gas is more than a third of the pressure");
+
+    // a bigger buddy supply does not change anything, a smaller one limits both divers
+    assert_eq!(turn_pressure(&twin12, &air, GasRule::Thirds, &[Cylinder::new(30.0, 232.0, 230.0, 0)]), turn_pressure(&twin12, &air, GasRule::Thirds, &[]));
+    let al80 = Cylinder::new(11.1, 207.0, 207.0, 0);
+    let al80_supply = al80.gas_volume(207.0, &air);
+    let limited = turn_pressure(&twin12, &air, GasRule::Thirds, &[al80]);
+    assert!((used_at_turn(&twin12, limited) - al80_supply / 3.0).abs() < 1.0);
+    assert_eq!(turn_pressure(&al80, &air, GasRule::Thirds, &[twin12]), turn_pressure(&al80, &air, GasRule::Thirds, &[]));
+}
+
+#[test]
+fn test_turn_point_on_the_profile() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let plan = single_level_plan(20.0, 40.0);
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    let usage = gas_usage(&plan, &params, &outputs);
+
+    let turn = turn_points(&plan, &usage, &outputs, GasRule::Thirds, &[])[0];
+    assert_eq!(turn.turn_pressure, turn_pressure(&plan.cylinders[0], &Gas::air(), GasRule::Thirds, &[]));
+    let time = turn.time.unwrap();
+    let i = outputs.times.iter().position(|sample| *sample == time).unwrap();
+    assert!(usage.remaining[0][i] <= turn.turn_pressure && usage.remaining[0][i - 1] > turn.turn_pressure);
+    assert_eq!(turn.depth, Some(20.0));
+
+    // buddies with another gas are not part of this cylinder's team
+    let other_gas = turn_points(&plan, &usage, &outputs, GasRule::Thirds, &[Cylinder::new(7.0, 200.0, 200.0, 1)])[0];
+    assert_eq!(other_gas, turn);
+
+    let short = single_level_plan(20.0, 10.0);
+    let outputs = short.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    let never = turn_points(&short, &gas_usage(&short, &params, &outputs), &outputs, GasRule::Halves, &[])[0];
+    assert_eq!((never.time, never.depth), (None, None));
+}
@@ -0,0 +1,156 @@
+use dive_computer_deco::mode::{DiveMode, ModeState, GAUGE_LOCKOUT_SECONDS};
+use dive_computer_deco::tissue::Tissue;
+use dive_computer_deco::{default_tissue_load, DecoError};
+
+fn surface_tissues(temperature: f32) -> [Tissue; 16] {
+    let mut tissues = [Tissue::default(); 16];
+    for tissue in tissues.iter_mut() {
+        tissue.load_n2 = default_tissue_load(temperature);
+        tissue.load_he = 0.0;
+    }
+    tissues
+}
+
+#[test]
+fn test_gauge_mode_leaves_tissues_untouched() {
+    let temperature = 20.0;
+    let mut tissues = surface_tissues(temperature);
+    let initial = tissues;
+    let mut state = ModeState::new(DiveMode::Gauge);
+    state.gauge.start_stopwatch();
+
+    for _ in 0..600 {
+        state.update(&mut tissues, 30.0, temperature, 1.0).unwrap();
+    }
+
+    assert_eq!(tissues[0].load_n2, initial[0].load_n2);
+    assert_eq!(state.gauge.max_depth, 30.0);
+    assert_eq!(state.gauge.dive_time, 600.0);
+    assert_eq!(state.gauge.stopwatch, 600.0);
+    assert!(state.tissue_state_unknown);
+}
+
+#[test]
+fn test_tissue_state_unknown_persists_until_lockout() {
+    let temperature = 20.0;
+    let mut tissues = surface_tissues(temperature);
+    let mut state = ModeState::new(DiveMode::Gauge);
+    state.update(&mut tissues, 20.0, temperature, 60.0).unwrap();
+
+    state.set_mode(DiveMode::Decompression);
+    state.update(&mut tissues, 0.0, temperature, GAUGE_LOCKOUT_SECONDS / 2.0).unwrap();
+    assert!(state.tissue_state_unknown, "flag must survive a mode change");
+
+    state.update(&mut tissues, 0.0, temperature, GAUGE_LOCKOUT_SECONDS / 2.0).unwrap();
+    assert!(!state.tissue_state_unknown, "flag clears after the lockout period");
+}
+
+#[test]
+fn test_freedive_rejects_slow_sampling() {
+    let temperature = 20.0;
+    let mut tissues = surface_tissues(temperature);
+    let mut state = ModeState::new(DiveMode::Freedive);
+
+    let result = state.update(&mut tissues, 10.0, temperature, 2.0);
+    assert!(matches!(result, Err(DecoError::InvalidSampleInterval)));
+}
+
+#[test]
+fn test_freedive_tracks_apneas_and_nitrogen() {
+    let temperature = 20.0;
+    let mut tissues = surface_tissues(temperature);
+    let initial_load = tissues[0].load_n2;
+    let mut state = ModeState::new(DiveMode::Freedive);
+    state.freedive.settings.depth_alarm = 25.0;
+    state.freedive.settings.time_alarm = 90.0;
+
+    let mut depth_alarm = false;
+    let mut time_alarm = false;
+    // 100 s apnea to 30 m, then a 30 s surface interval and a second descent
+    for second in 0..100 {
+        let depth = if second < 50 { second as f32 * 0.6 } else { (100 - second) as f32 * 0.6 };
+        let alarms = state.update(&mut tissues, depth.max(2.0), temperature, 1.0).unwrap();
+        depth_alarm |= alarms.depth;
+        time_alarm |= alarms.time;
+    }
+    for _ in 0..30 {
+        state.update(&mut tissues, 0.0, temperature, 1.0).unwrap();
+    }
+    let alarms = state.update(&mut tissues, 5.0, temperature, 1.0).unwrap();
+
+    assert!(depth_alarm);
+    assert!(time_alarm);
+    assert!(alarms.surface_interval, "second apnea started too early");
+    assert_eq!(state.freedive.apnea_count, 2);
+
+    let last = state.freedive.last.unwrap();
+    assert_eq!(last.duration, 100.0);
+    assert!(last.max_depth >= 29.0);
+    assert!(tissues[0].load_n2 > initial_load, "nitrogen loading is tracked across apneas");
+}
+
+#[test]
+fn test_deco_mode_keeps_gf_low_at_the_first_stop() {
+    use dive_computer_deco::ceiling::GfSlope;
+    use dive_computer_deco::mode::SUBMERGED_DEPTH;
+
+    let temperature = 20.0;
+    let mut tissues = surface_tissues(temperature);
+    let mut state = ModeState::new(DiveMode::Decompression);
+    state.deco.gf_slope = GfSlope::new(0.3, 0.85, 1.0);
+
+    for _ in 0..25 {
+        state.update(&mut tissues, 45.0, temperature, 60.0).unwrap();
+    }
+    assert_eq!(state.deco.gf_slope.first_stop_pressure, None, "no anchor before the ascent");
+
+    state.update(&mut tissues, 30.0, temperature, 60.0).unwrap();
+    let anchored = state.deco.gf_slope.first_stop_pressure.expect("anchored on the way up");
+    assert_eq!(anchored, GfSlope::new(0.3, 0.85, 1.0).deepest_stop_pressure(&tissues));
+    assert!(state.deco.max_ceiling(&tissues).0 > 0.0);
+
+    // off-gassing at a stop does not move GF low
+    for depth in [18.0, 18.0, 18.5, 12.0] {
+        state.update(&mut tissues, depth, temperature, 60.0).unwrap();
+        assert_eq!(state.deco.gf_slope.first_stop_pressure, Some(anchored));
+    }
+
+    // going back down is a new level, its ascent anchors at its own deepest stop
+    state.update(&mut tissues, 21.0, temperature, 60.0).unwrap();
+    assert_eq!(state.deco.gf_slope.first_stop_pressure, None, "released on the way down");
+    state.update(&mut tissues, 15.0, temperature, 60.0).unwrap();
+    let reanchored = state.deco.gf_slope.first_stop_pressure.expect("anchored on the next ascent");
+    assert!(reanchored < anchored);
+
+    state.update(&mut tissues, SUBMERGED_DEPTH / 2.0, temperature, 60.0).unwrap();
+    assert_eq!(state.deco.gf_slope.first_stop_pressure, None, "released at the surface");
+}
+
+#[test]
+fn test_deco_mode_ignores_depth_noise() {
+    use dive_computer_deco::ceiling::GfSlope;
+
+    let temperature = 20.0;
+    let mut tissues = surface_tissues(temperature);
+    let mut state = ModeState::new(DiveMode::Decompression);
+    state.deco.gf_slope = GfSlope::new(0.3, 0.85, 1.0);
+
+    // a sensor wobbling by 0.1 m around the bottom is no ascent
+    for i in 0..25 * 60 {
+        let depth = if i % 2 == 0 { 45.0 } else { 44.9 };
+        state.update(&mut tissues, depth, temperature, 1.0).unwrap();
+    }
+    assert_eq!(state.deco.gf_slope.first_stop_pressure, None, "not anchored by noise");
+
+    for i in 0..60 {
+        state.update(&mut tissues, 45.0 - i as f32 / 6.0, temperature, 1.0).unwrap();
+    }
+    let anchored = state.deco.gf_slope.first_stop_pressure.expect("anchored on the ascent");
+
+    // nor is it a descent at the stop
+    for i in 0..5 * 60 {
+        let depth = if i % 2 == 0 { 21.0 } else { 21.3 };
+        state.update(&mut tissues, depth, temperature, 1.0).unwrap();
+    }
+    assert_eq!(state.deco.gf_slope.first_stop_pressure, Some(anchored));
+}
@@ -1,7 +1,9 @@
 use dive_computer_deco::ndl::binary_ndl;
 use dive_computer_deco::ndl::ndl;
+use dive_computer_deco::ceiling::GfSlope;
+use dive_computer_deco::ndl::ndl_with_limit;
 use dive_computer_deco::simulate::simulate;
-use dive_computer_deco::{water_vapor_pressure, DiveParameters, FHE, FN2};
+use dive_computer_deco::{water_vapor_pressure, DiveParameters, Gas, FHE, FN2};
 use dive_computer_deco::tissue::Tissue;
 // Binary NDL tests
 #[test]
@@ -323,3 +325,84 @@ fn test_comprehensive_binary_ndl_comparison() {
         }
     }
 }
+
+#[test]
+fn test_ndl_with_limit_matches_regular() {
+    fn reset_tissues(tissues: &mut [Tissue; 16], amb_pressure: f32, temperature: f32) {
+        for i in 0..tissues.len() {
+            tissues[i].load_n2 = (amb_pressure - water_vapor_pressure(temperature)) * FN2;
+            tissues[i].load_he = (amb_pressure - water_vapor_pressure(temperature)) * FHE;
+        }
+    }
+
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.85);
+    let slope = GfSlope::new(params.gf_low, params.gf_high, 1.0);
+
+    for target_depth in [20.0, 30.0, 40.0] {
+        let mut tissues = [Tissue::default(); 16];
+        reset_tissues(&mut tissues, 1.0, temperature);
+        let amb_pressure = target_depth / 10.0 + 1.0;
+
+        let limited = ndl_with_limit(&slope, &tissues, amb_pressure, temperature, Gas::air(), 500);
+        let regular = ndl(params, &mut tissues, amb_pressure, temperature);
+        assert_eq!(limited, Some(regular), "depth {}", target_depth);
+    }
+
+    // shallow dives hit the cap instead of looping forever
+    let mut tissues = [Tissue::default(); 16];
+    reset_tissues(&mut tissues, 1.0, temperature);
+    assert_eq!(ndl_with_limit(&slope, &tissues, 1.5, temperature, Gas::air(), 99), Some(99.0));
+}
+
+#[test]
+fn test_try_ndl_errors() {
+    use dive_computer_deco::ndl::{try_binary_ndl, try_ndl, try_ndl_with_limit};
+    use dive_computer_deco::{DecoError, Input};
+
+    let temperature = 20.0;
+    let mut tissues = [Tissue::default(); 16];
+    for tissue in tissues.iter_mut() {
+        tissue.load_n2 = (1.0 - water_vapor_pressure(temperature)) * FN2;
+        tissue.load_he = 0.0;
+    }
+    let params = DiveParameters::new(0.85, 0.3);
+
+    let expected = ndl(params, &mut tissues.clone(), 4.0, temperature);
+    assert_eq!(try_ndl(params, &mut tissues.clone(), 4.0, temperature), Ok(expected));
+
+    // Shallow enough that no ceiling ever appears
+    assert_eq!(try_ndl(params, &mut tissues.clone(), 1.2, temperature), Err(DecoError::NoConvergence { iterations: 10000 }));
+    assert_eq!(ndl(params, &mut tissues.clone(), 1.2, temperature), 10000.0, "saturates instead of panicking");
+    assert_eq!(binary_ndl(params, &mut tissues.clone(), 1.2, temperature), 10000.0);
+    assert_eq!(try_ndl(DiveParameters::new(1.2, 0.3), &mut tissues.clone(), 4.0, temperature),
+        Err(DecoError::InvalidInput { input: Input::GradientFactor, value: 1.2 }));
+    assert_eq!(try_binary_ndl(DiveParameters::new(1.2, 0.3), &mut tissues.clone(), 4.0, temperature),
+        Err(DecoError::InvalidInput { input: Input::GradientFactor, value: 1.2 }), "GF high is checked too");
+    assert!(matches!(try_ndl_with_limit(&GfSlope::new(0.3, 0.85, 1.0), &tissues, f32::NAN, temperature, Gas::air(), 99),
+        Err(DecoError::InvalidInput { input: Input::AmbientPressure, .. })));
+}
+
+#[test]
+fn test_ndl_with_limit_follows_gas_and_surface() {
+    use dive_computer_deco::ndl::try_ndl_with_limit;
+    use dive_computer_deco::{DecoError, Input};
+
+    let temperature = 20.0;
+    let mut tissues = [Tissue::default(); 16];
+    for tissue in tissues.iter_mut() {
+        tissue.load_n2 = (1.0 - water_vapor_pressure(temperature)) * FN2;
+        tissue.load_he = 0.0;
+    }
+    let slope = GfSlope::new(0.3, 0.85, 1.0);
+    let air = ndl_with_limit(&slope, &tissues, 4.0, temperature, Gas::air(), 200).unwrap();
+    let ean32 = ndl_with_limit(&slope, &tissues, 4.0, temperature, Gas::new(0.32, 0.0), 200).unwrap();
+    assert!(ean32 > air, "EAN32 {} air {}", ean32, air);
+
+    // the same tissues and depth below a lake at altitude leave less time
+    let altitude = ndl_with_limit(&GfSlope::new(0.3, 0.85, 0.8), &tissues, 3.8, temperature, Gas::air(), 200).unwrap();
+    assert!(altitude < air, "altitude {} sea level {}", altitude, air);
+
+    assert!(matches!(try_ndl_with_limit(&slope, &tissues, 4.0, temperature, Gas::new(0.8, 0.4), 99),
+        Err(DecoError::InvalidInput { input: Input::GasFraction, .. })));
+}
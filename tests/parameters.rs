@@ -0,0 +1,43 @@
+use dive_computer_deco::conservatism::{Conservatism, HIGH_GRADIENT_FACTORS};
+use dive_computer_deco::parameters::ParameterProblem;
+use dive_computer_deco::DiveParameters;
+
+#[test]
+fn test_builder_sets_every_field() {
+    let params = DiveParameters::builder()
+        .conservatism(Conservatism::High)
+        .descent_speed(18.0 / 60.0)
+        .ascent_speed(9.0 / 60.0)
+        .safety_stop(5.0, 180.0, 3.0 / 60.0)
+        .sac_rate(15.0)
+        .deco_sac_rate(12.0)
+        .build()
+        .unwrap();
+    assert_eq!((params.gf_low, params.gf_high), HIGH_GRADIENT_FACTORS);
+    assert_eq!(params.ascent_speed, 9.0 / 60.0);
+    assert_eq!(params.safety_stop_duration, 180.0);
+    assert_eq!((params.sac_rate, params.deco_sac_rate), (15.0, 12.0));
+
+    assert!(DiveParameters::default().validate().is_ok());
+    assert!(DiveParameters::new(0.85, 0.3).validate().is_ok());
+    assert_eq!(DiveParameters::builder().sac_rate(18.0).build().unwrap().deco_sac_rate, 18.0);
+}
+
+#[test]
+fn test_builder_lists_every_problem() {
+    let errors = DiveParameters::builder()
+        .gradient_factors(0.9, 1.2)
+        .ascent_speed(0.0)
+        .safety_stop(-3.0, 180.0, f32::NAN)
+        .build()
+        .unwrap_err();
+    let problems: Vec<ParameterProblem> = errors.problems().collect();
+    assert_eq!(problems.len(), 4, "{:?}", problems);
+    assert_eq!(problems[0], ParameterProblem::GfHigh(1.2));
+    assert_eq!(problems[1], ParameterProblem::AscentSpeed(0.0));
+    assert!(matches!(problems[2], ParameterProblem::SafetyStopAscentSpeed(speed) if speed.is_nan()));
+    assert_eq!(problems[3], ParameterProblem::SafetyStopDepth(-3.0));
+
+    let errors = DiveParameters::builder().gradient_factors(0.8, 0.3).sac_rate(f32::INFINITY).build().unwrap_err();
+    assert_eq!(errors.to_string(), "GF low 0.8 is above GF high 0.3; SAC rate inf L/min is not a finite number above 0; deco SAC rate inf L/min is not a finite number above 0");
+}
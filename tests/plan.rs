@@ -0,0 +1,147 @@
+#![cfg(feature = "alloc")]
+
+use dive_computer_deco::plan::{DivePlan, Segment, SegmentTime, WaterType};
+use dive_computer_deco::simulate::{simulate, DivePhase, SimulationEventKind};
+use dive_computer_deco::tissue::Tissue;
+use dive_computer_deco::{default_tissue_load, DecoError, DiveParameters, Gas};
+
+fn surface_tissues(temperature: f32) -> [Tissue; 16] {
+    let mut tissues = [Tissue::default(); 16];
+    for tissue in tissues.iter_mut() {
+        tissue.load_n2 = default_tissue_load(temperature);
+        tissue.load_he = 0.0;
+    }
+    tissues
+}
+
+#[test]
+fn test_single_level_plan_matches_simulate() {
+    let temperature = 20.0;
+    let mut params = DiveParameters::new(0.85, 0.3);
+
+    let mut plan = DivePlan::new(1.0, WaterType::Salt);
+    let air = plan.add_gas(Gas::air());
+    plan.add_segment(Segment::new(40.0, SegmentTime::Duration(20.0 * 60.0), air));
+
+    let mut plan_tissues = surface_tissues(temperature);
+    let from_plan = plan.simulate(&params, &mut plan_tissues, temperature, 10.0).unwrap();
+    let mut tissues = surface_tissues(temperature);
+    let direct = simulate(&mut params, &mut tissues, 1.0, 40.0, temperature, 10.0, 20.0 * 60.0);
+
+    assert_eq!(from_plan.times, direct.times);
+    assert_eq!(from_plan.ceilings, direct.ceilings);
+    assert_eq!(from_plan.events, direct.events);
+    assert_eq!(plan_tissues[15].load_n2, tissues[15].load_n2);
+}
+
+#[test]
+fn test_multi_level_plan_is_continuous() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let mut tissues = surface_tissues(temperature);
+
+    let mut plan = DivePlan::new(1.0, WaterType::Salt);
+    let air = plan.add_gas(Gas::air());
+    let ean50 = plan.add_gas(Gas::new(0.5, 0.0));
+    plan.add_segment(Segment::new(40.0, SegmentTime::Duration(15.0 * 60.0), air));
+    plan.add_segment(Segment { rate: Some(5.0 / 60.0), ..Segment::new(21.0, SegmentTime::Runtime(30.0 * 60.0), ean50) });
+
+    let outputs = plan.simulate(&params, &mut tissues, temperature, 10.0).unwrap();
+
+    assert_eq!(outputs.times.iter().filter(|time| **time == 0.0).count(), 1, "one initial sample");
+    assert!(outputs.times.windows(2).all(|pair| pair[1] > pair[0]));
+    assert!(outputs.depths.iter().all(|depth| *depth <= 40.0));
+
+    let switch = outputs.events.iter().find(|event| matches!(event.kind, SimulationEventKind::GasSwitch { .. })).unwrap();
+    assert_eq!(switch.kind, SimulationEventKind::GasSwitch { o2: 0.5, he: 0.0 });
+    assert_eq!(switch.depth, 21.0, "the ascent keeps the gas it starts on");
+
+    // 15 min at 40 m after a 2 min descent, then 19 m at 5 m/min
+    let leave_21m = outputs.events.iter()
+        .find(|event| event.kind == SimulationEventKind::PhaseChange(DivePhase::Ascent) && event.depth == 21.0)
+        .unwrap();
+    assert!((leave_21m.time - 30.0 * 60.0).abs() <= 1.0);
+    assert_eq!(outputs.events.last().unwrap().kind, SimulationEventKind::PhaseChange(DivePhase::Surface));
+
+    let on_nitrox = outputs.times.iter().position(|time| *time > switch.time).unwrap();
+    assert_eq!(outputs.gases[on_nitrox], Gas::new(0.5, 0.0));
+}
+
+#[test]
+fn test_ascent_keeps_the_gas_until_the_new_depth() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let mut plan = DivePlan::new(1.0, WaterType::Salt);
+    let air = plan.add_gas(Gas::air());
+    let ean50 = plan.add_gas(Gas::new(0.5, 0.0));
+    plan.add_segment(Segment::new(40.0, SegmentTime::Duration(25.0 * 60.0), air));
+    plan.add_segment(Segment::new(21.0, SegmentTime::Duration(60.0), ean50));
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+
+    let switch = outputs.events.iter().find(|event| matches!(event.kind, SimulationEventKind::GasSwitch { .. })).unwrap();
+    assert_eq!(switch.depth, 21.0);
+    let switched = outputs.times.iter().position(|time| *time > switch.time).unwrap();
+    for i in 0..outputs.times.len() {
+        let expected = if i >= switched { Gas::new(0.5, 0.0) } else { Gas::air() };
+        assert_eq!(outputs.gases[i], expected, "{} s at {} m", outputs.times[i], outputs.depths[i]);
+        assert!(outputs.pressures[i] * outputs.gases[i].o2() <= 1.6, "{} s at {} m", outputs.times[i], outputs.depths[i]);
+    }
+    assert!(outputs.depths[switched - 5..switched].iter().all(|depth| *depth > 21.0), "the samples of the move up are on air");
+}
+
+#[test]
+fn test_ascent_switches_to_carried_gases_by_mod() {
+    use dive_computer_deco::gas::{Cylinder, CylinderRole};
+
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let mut plan = DivePlan::new(1.0, WaterType::Salt);
+    let air = plan.add_gas(Gas::air());
+    let ean50 = plan.add_gas(Gas::new(0.5, 0.0));
+    let oxygen = plan.add_gas(Gas::new(1.0, 0.0));
+    plan.add_segment(Segment::new(40.0, SegmentTime::Duration(25.0 * 60.0), air));
+    plan.add_cylinder(Cylinder::new(24.0, 232.0, 220.0, air));
+    plan.add_cylinder(Cylinder { role: CylinderRole::Deco, ..Cylinder::new(7.0, 207.0, 200.0, ean50) });
+    plan.add_cylinder(Cylinder { role: CylinderRole::Bailout, ..Cylinder::new(3.0, 207.0, 200.0, oxygen) });
+
+    // without deco_ppo2 the ascent stays on the bottom gas
+    let scripted = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    assert!(scripted.gases.iter().all(|gas| *gas == Gas::air()));
+
+    plan.deco_ppo2 = Some(1.6);
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+    let switches: Vec<_> = outputs.events.iter().filter(|event| matches!(event.kind, SimulationEventKind::GasSwitch { .. })).collect();
+    assert_eq!(switches.len(), 1, "bailout gas is never switched to");
+    assert_eq!(switches[0].kind, SimulationEventKind::GasSwitch { o2: 0.5, he: 0.0 });
+    assert!(switches[0].depth <= 22.0 && switches[0].depth > 21.0, "at the MOD of EAN50");
+    for i in 0..outputs.times.len() {
+        assert!(outputs.pressures[i] * outputs.gases[i].o2() <= 1.6 + 1e-4);
+    }
+    assert!(*outputs.times.last().unwrap() < *scripted.times.last().unwrap());
+}
+
+#[test]
+fn test_fresh_water_reduces_pressure() {
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+
+    let mut plan = DivePlan::new(0.8, WaterType::Fresh);
+    let air = plan.add_gas(Gas::air());
+    plan.add_segment(Segment::new(30.0, SegmentTime::Duration(10.0 * 60.0), air));
+    let outputs = plan.simulate(&params, &mut surface_tissues(temperature), temperature, 10.0).unwrap();
+
+    let bottom = outputs.depths.iter().position(|depth| *depth == 30.0).unwrap();
+    assert!((outputs.pressures[bottom] - (0.8 + 30.0 / 10.3)).abs() < 1e-4);
+}
+
+#[test]
+fn test_invalid_plans() {
+    let params = DiveParameters::new(0.85, 0.3);
+    let mut tissues = surface_tissues(20.0);
+
+    let mut plan = DivePlan::new(1.0, WaterType::Salt);
+    assert!(matches!(plan.simulate(&params, &mut tissues, 20.0, 10.0), Err(DecoError::InvalidPlan)));
+
+    plan.add_segment(Segment::new(30.0, SegmentTime::Duration(60.0), 0));
+    assert!(matches!(plan.validate(), Err(DecoError::InvalidPlan)));
+}
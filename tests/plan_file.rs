@@ -0,0 +1,107 @@
+#![cfg(all(feature = "serde", feature = "alloc"))]
+
+use dive_computer_deco::conservatism::{Conservatism, HIGH_GRADIENT_FACTORS};
+use dive_computer_deco::gas::{Cylinder, CylinderRole};
+use dive_computer_deco::plan::{DivePlan, Segment, SegmentTime, WaterType};
+use dive_computer_deco::plan_file::{PlanFile, PlanFileError, PLAN_FILE_VERSION};
+use dive_computer_deco::{DiveParameters, Gas};
+
+#[test]
+fn test_migrates_unversioned_gui_plan() {
+    let legacy = r#"{
+        "gf_low": 0.3,
+        "gf_high": 0.8,
+        "surface_pressure": 0.95,
+        "descent_speed": 18.0,
+        "ascent_speed": 9.0,
+        "dive_steps": [{ "depth": 30.0, "duration": 20.0 }, { "depth": 15.0, "duration": 10.0 }]
+    }"#;
+
+    let file = PlanFile::from_json(legacy).unwrap();
+    assert_eq!(file.version, PLAN_FILE_VERSION);
+    assert_eq!(file.surface_pressure(), 0.95);
+    assert_eq!(file.gases.len(), 1);
+
+    let params = file.dive_parameters();
+    assert_eq!(params.gf_low, 0.3);
+    assert_eq!(params.gf_high, 0.8);
+    assert!((params.ascent_speed - 9.0 / 60.0).abs() < 1e-6);
+
+    let plan = file.dive_plan().unwrap();
+    assert_eq!(plan.segments[1], Segment::new(15.0, SegmentTime::Duration(600.0), 0));
+    assert_eq!(plan.gases[0], Gas::air());
+}
+
+#[test]
+fn test_round_trip_keeps_plan() {
+    let mut plan = DivePlan::new(0.9, WaterType::Fresh);
+    let air = plan.add_gas(Gas::air());
+    let ean50 = plan.add_gas(Gas::new(0.5, 0.0));
+    plan.add_segment(Segment::new(30.0, SegmentTime::Duration(20.0 * 60.0), air));
+    plan.add_segment(Segment { rate: Some(5.0 / 60.0), ..Segment::new(21.0, SegmentTime::Runtime(35.0 * 60.0), ean50) });
+    plan.add_cylinder(Cylinder::new(12.0, 232.0, 200.0, air));
+    plan.add_cylinder(Cylinder { role: CylinderRole::Deco, reserve: 70.0, ..Cylinder::new(7.0, 232.0, 200.0, ean50) });
+    let params = DiveParameters::new(0.85, 0.3);
+
+    let json = PlanFile::from_plan(&plan, &params).to_json().unwrap();
+    let file = PlanFile::from_json(&json).unwrap();
+    let restored = file.dive_plan().unwrap();
+
+    assert_eq!(restored.water, WaterType::Fresh);
+    assert_eq!(restored.surface_pressure, 0.9);
+    assert_eq!(restored.segments[0], plan.segments[0]);
+    assert!((restored.segments[1].rate.unwrap() - 5.0 / 60.0).abs() < 1e-6);
+    assert!((restored.gases[1].o2() - 0.5).abs() < 1e-6);
+    assert_eq!(restored.cylinders, plan.cylinders);
+    assert_eq!(file.dive_parameters().gf_high, 0.85);
+}
+
+#[test]
+fn test_minimal_file_uses_defaults() {
+    let json = r#"{
+        "version": 1,
+        "gf_low": 0.4,
+        "gf_high": 0.85,
+        "altitude": 1500.0,
+        "gases": [{ "o2": 0.32 }],
+        "segments": [{ "depth": 25.0, "duration": 30.0 }]
+    }"#;
+
+    let file = PlanFile::from_json(json).unwrap();
+    assert_eq!(file.water, WaterType::Salt);
+    assert!(file.cylinders.is_empty());
+    assert!(file.surface_pressure() < 0.85 && file.surface_pressure() > 0.83);
+    assert_eq!(file.dive_parameters().descent_speed, DiveParameters::default().descent_speed);
+    assert_eq!(file.dive_plan().unwrap().segments[0].gas, 0);
+}
+
+#[test]
+fn test_rejects_newer_and_broken_files() {
+    let newer = r#"{ "version": 99, "gf_low": 0.4, "gf_high": 0.85, "gases": [], "segments": [] }"#;
+    assert!(matches!(PlanFile::from_json(newer), Err(PlanFileError::UnsupportedVersion(99))));
+    assert!(matches!(PlanFile::from_json("{ \"version\": 1 }"), Err(PlanFileError::Json(_))));
+
+    let no_time = r#"{ "version": 1, "gf_low": 0.4, "gf_high": 0.85, "gases": [{ "o2": 0.21 }], "segments": [{ "depth": 25.0 }] }"#;
+    assert!(PlanFile::from_json(no_time).unwrap().dive_plan().is_err());
+}
+
+#[test]
+fn test_conservatism_preset_sets_gradient_factors() {
+    let json = r#"{
+        "version": 1,
+        "gf_low": 0.3,
+        "gf_high": 0.7,
+        "conservatism": "High",
+        "gases": [{ "o2": 0.21 }],
+        "segments": [{ "depth": 30.0, "duration": 20.0 }]
+    }"#;
+    let params = PlanFile::from_json(json).unwrap().dive_parameters();
+    assert_eq!(params.conservatism, Conservatism::High);
+    assert_eq!((params.gf_low, params.gf_high), HIGH_GRADIENT_FACTORS);
+
+    let plan = PlanFile::from_json(json).unwrap().dive_plan().unwrap();
+    let json = PlanFile::from_plan(&plan, &params).to_json().unwrap();
+    assert!(json.contains("\"conservatism\": \"High\""));
+    let custom = PlanFile::from_plan(&plan, &DiveParameters::new(0.8, 0.2)).to_json().unwrap();
+    assert!(!custom.contains("conservatism"), "custom gradient factors are in gf_low and gf_high");
+}
@@ -0,0 +1,335 @@
+use dive_computer_deco::tissue::Tissue;
+use dive_computer_deco::{default_tissue_load, DiveParameters, Gas};
+
+fn surface_tissues(temperature: f32) -> [Tissue; 16] {
+    let mut tissues = [Tissue::default(); 16];
+    for tissue in tissues.iter_mut() {
+        tissue.load_n2 = default_tissue_load(temperature);
+        tissue.load_he = 0.0;
+    }
+    tissues
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_simulation_timeline_and_events() {
+    use dive_computer_deco::alarm::{AlarmKind, AlarmMonitor};
+    use dive_computer_deco::simulate::{simulate, DivePhase, SimulationEventKind};
+
+    let temperature = 20.0;
+    let mut tissues = surface_tissues(temperature);
+    let mut params = DiveParameters::new(0.85, 0.3);
+    let mut outputs = simulate(&mut params, &mut tissues, 1.0, 50.0, temperature, 10.0, 20.0 * 60.0);
+
+    assert_eq!(outputs.times.len(), outputs.depths.len());
+    assert!(outputs.times.windows(2).all(|pair| pair[1] > pair[0]));
+
+    let phases: Vec<DivePhase> = outputs.events.iter()
+        .filter_map(|event| match event.kind {
+            SimulationEventKind::PhaseChange(phase) => Some(phase),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(phases[..3], [DivePhase::Descent, DivePhase::Bottom, DivePhase::Ascent]);
+    assert!(phases.contains(&DivePhase::Stop));
+    assert_eq!(phases.last(), Some(&DivePhase::Surface));
+
+    let stops = outputs.stops();
+    assert!(!stops.is_empty());
+    assert!(stops.windows(2).all(|pair| pair[0].0 > pair[1].0), "stops get shallower");
+    assert!(outputs.events.iter().all(|event| !matches!(event.kind, SimulationEventKind::CeilingViolation { .. })));
+
+    outputs.record_alarms(&mut AlarmMonitor::default());
+    assert!(outputs.events.iter().any(|event| event.kind == SimulationEventKind::Alarm(AlarmKind::MaxDepth)));
+    assert!(outputs.events.windows(2).all(|pair| pair[1].time >= pair[0].time));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_append_shifts_time_axis() {
+    use dive_computer_deco::simulate::{simulate_with_ascent, simulate_with_ascent_from_depth};
+
+    let temperature = 20.0;
+    let mut tissues = surface_tissues(temperature);
+    let mut params = DiveParameters::new(0.85, 0.3);
+    let mut outputs = simulate_with_ascent(&mut params, &mut tissues, 1.0, 30.0, temperature, 10.0, 10.0 * 60.0, false);
+    let first_end = *outputs.times.last().unwrap();
+    let second = simulate_with_ascent_from_depth(&mut params, &mut tissues, 1.0, 30.0, 20.0, temperature, 10.0, 10.0 * 60.0, true);
+    let second_events = second.events.len();
+
+    outputs.append(second);
+    assert!(outputs.times.windows(2).all(|pair| pair[1] >= pair[0]));
+    assert!(outputs.events[outputs.events.len() - second_events].time >= first_end);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_per_sample_deco_state() {
+    use dive_computer_deco::ceiling::{round_to_stop, GfSlope};
+    use dive_computer_deco::simulate::{SimulationOutputs, Simulator};
+
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let mut simulator = Simulator::new(params, surface_tissues(temperature), 1.0, 0.0, 45.0, temperature, 10.0, 25.0 * 60.0, true);
+    assert_eq!(simulator.gf_slope().first_stop_pressure, None);
+    let mut outputs = SimulationOutputs::new();
+    simulator.run_into(&mut outputs);
+
+    let samples = outputs.times.len();
+    assert_eq!(outputs.ceilings.len(), samples);
+    assert_eq!(outputs.exact_ceilings.len(), samples);
+    assert_eq!(outputs.controlling_compartments.len(), samples);
+    assert_eq!(outputs.ndls.len(), samples);
+    assert_eq!(outputs.gradient_factors.len(), samples);
+
+    // the simulator keeps GF low at the deepest stop found when the ascent starts,
+    // before that every sample anchors the slope at its own deepest stop
+    let ascent = simulator.gf_slope();
+    assert!(ascent.first_stop_pressure.is_some());
+    let ascent_start = (1..samples).find(|&i| outputs.depths[i] < outputs.depths[i - 1]).unwrap();
+    for i in 0..samples {
+        let slope = if i < ascent_start { GfSlope::new(params.gf_low, params.gf_high, 1.0) } else { ascent };
+        let ceiling = round_to_stop(slope.max_ceiling(&outputs.tissues_per_interval[i]).0);
+        assert_eq!(outputs.ceilings[i], ceiling, "recorded ceiling matches the simulator's ceiling");
+        assert!(outputs.exact_ceilings[i] <= outputs.ceilings[i] as f32 + 0.001);
+        assert_eq!(outputs.ndls[i].is_none(), ceiling > 0);
+        assert!(outputs.gradient_factors[i] >= params.gf_low && outputs.gradient_factors[i] <= params.gf_high);
+    }
+
+    // early in the dive there is an NDL, at the end of the bottom phase there is a ceiling
+    assert!(outputs.ndls[1].unwrap() > 0.0);
+    assert!(outputs.ceilings.iter().any(|&ceiling| ceiling > 0));
+}
+
+#[test]
+fn test_buffer_sink_without_allocation() {
+    use dive_computer_deco::simulate::{simulate_with_ascent_from_depth_into, BufferSink, SimulationEvent, SimulationEventKind, SimulationSample, DivePhase};
+
+    let temperature = 20.0;
+    let mut tissues = surface_tissues(temperature);
+    let mut params = DiveParameters::new(0.85, 0.3);
+
+    let empty_sample = SimulationSample {
+        time: 0.0,
+        depth: 0.0,
+        pressure: 0.0,
+        tissues: [Tissue::default(); 16],
+        ceiling: 0,
+        exact_ceiling: 0.0,
+        controlling_compartment: 0,
+        ndl: None,
+        gradient_factor: 0.0,
+        gas: Gas::air(),
+    };
+    let empty_event = SimulationEvent { time: 0.0, depth: 0.0, kind: SimulationEventKind::StopArrival };
+    let mut samples = [empty_sample; 64];
+    let mut events = [empty_event; 32];
+    let mut sink = BufferSink::new(&mut samples, &mut events);
+
+    simulate_with_ascent_from_depth_into(&mut params, &mut tissues, 1.0, 0.0, 30.0, temperature, 60.0, 15.0 * 60.0, true, &mut sink);
+
+    assert!(!sink.samples().is_empty());
+    assert_eq!(sink.samples()[0].time, 0.0);
+    assert_eq!(sink.events()[0].kind, SimulationEventKind::PhaseChange(DivePhase::Descent));
+    assert_eq!(sink.dropped_events, 0);
+
+    // a tiny buffer keeps the first samples and counts the rest
+    let mut samples = [empty_sample; 4];
+    let mut events = [empty_event; 1];
+    let mut tissues = surface_tissues(temperature);
+    let mut small_sink = BufferSink::new(&mut samples, &mut events);
+    simulate_with_ascent_from_depth_into(&mut params, &mut tissues, 1.0, 0.0, 30.0, temperature, 60.0, 15.0 * 60.0, true, &mut small_sink);
+    assert_eq!(small_sink.samples().len(), 4);
+    assert!(small_sink.dropped_samples > 0);
+    assert!(small_sink.dropped_events > 0);
+}
+
+#[test]
+fn test_callback_sink() {
+    use dive_computer_deco::simulate::{simulate_with_ascent_from_depth_into, CallbackSink};
+
+    let temperature = 20.0;
+    let mut tissues = surface_tissues(temperature);
+    let mut params = DiveParameters::new(0.85, 0.3);
+    let mut max_depth = 0.0_f32;
+    let mut event_count = 0;
+    let mut sink = CallbackSink {
+        on_sample: |sample: &dive_computer_deco::simulate::SimulationSample| max_depth = max_depth.max(sample.depth),
+        on_event: |_: &dive_computer_deco::simulate::SimulationEvent| event_count += 1,
+    };
+
+    simulate_with_ascent_from_depth_into(&mut params, &mut tissues, 1.0, 0.0, 25.0, temperature, 10.0, 10.0 * 60.0, true, &mut sink);
+
+    assert_eq!(max_depth, 25.0);
+    assert!(event_count >= 4);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_simulator_matches_collected_outputs() {
+    use dive_computer_deco::simulate::{simulate, SimulationRecord, Simulator};
+
+    let temperature = 20.0;
+    let mut tissues = surface_tissues(temperature);
+    let mut params = DiveParameters::new(0.85, 0.3);
+    let simulator = Simulator::new(params, tissues, 1.0, 0.0, 40.0, temperature, 10.0, 20.0 * 60.0, true);
+    let outputs = simulate(&mut params, &mut tissues, 1.0, 40.0, temperature, 10.0, 20.0 * 60.0);
+
+    let (mut samples, mut events) = (0, 0);
+    for record in simulator {
+        match record {
+            SimulationRecord::Sample(sample) => {
+                assert_eq!(sample.time, outputs.times[samples]);
+                assert_eq!(sample.ceiling, outputs.ceilings[samples]);
+                samples += 1;
+            }
+            SimulationRecord::Event(event) => {
+                assert_eq!(event, outputs.events[events]);
+                events += 1;
+            }
+        }
+    }
+    assert_eq!(samples, outputs.times.len());
+    assert_eq!(events, outputs.events.len());
+}
+
+#[test]
+fn test_simulator_stops_early_and_steps() {
+    use dive_computer_deco::simulate::{SimulationRecord, Simulator};
+
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let mut simulator = Simulator::new(params, surface_tissues(temperature), 1.0, 0.0, 30.0, temperature, 1.0, 60.0 * 60.0, true);
+
+    // downsample to one sample per minute and stop at the first deco obligation
+    let first_ceiling = simulator.by_ref()
+        .filter_map(|record| match record {
+            SimulationRecord::Sample(sample) => Some(sample),
+            SimulationRecord::Event(_) => None,
+        })
+        .filter(|sample| sample.time % 60.0 == 0.0)
+        .find(|sample| sample.ceiling > 0)
+        .unwrap();
+    assert_eq!(first_ceiling.depth, 30.0);
+    assert!(!simulator.is_finished());
+
+    let time = simulator.time();
+    assert!(simulator.step(120.0, &mut ()));
+    assert!(simulator.time() >= time + 120.0);
+    assert!(simulator.sample().tissues[0].load_n2 > first_ceiling.tissues[0].load_n2);
+
+    while simulator.step(600.0, &mut ()) {}
+    assert!(simulator.is_finished());
+    assert_eq!(simulator.depth(), 0.0);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_runtime_table() {
+    use dive_computer_deco::simulate::{simulate, RuntimeKind};
+
+    let temperature = 20.0;
+    let mut tissues = surface_tissues(temperature);
+    let mut params = DiveParameters::new(0.85, 0.3);
+    let outputs = simulate(&mut params, &mut tissues, 1.0, 40.0, temperature, 10.0, 20.0 * 60.0);
+    let table = outputs.runtime_table();
+
+    assert_eq!(table[0].kind, RuntimeKind::Level);
+    assert_eq!(table[0].depth, 40.0);
+    assert!((table[0].duration - 20.0 * 60.0).abs() < 0.01);
+    assert_eq!(table.last().unwrap().kind, RuntimeKind::Surface);
+    assert!(table.windows(2).all(|pair| pair[1].runtime >= pair[0].runtime));
+
+    let stops: Vec<f32> = table.iter().filter(|entry| entry.kind == RuntimeKind::Stop).map(|entry| entry.depth).collect();
+    let expected: Vec<f32> = outputs.stops().iter().map(|stop| stop.0).collect();
+    assert_eq!(stops, expected);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_try_simulate_errors() {
+    use dive_computer_deco::simulate::{simulate, try_simulate};
+    use dive_computer_deco::DecoError;
+
+    let temperature = 20.0;
+    let mut params = DiveParameters::new(0.85, 0.3);
+    let expected = simulate(&mut params, &mut surface_tissues(temperature), 1.0, 30.0, temperature, 10.0, 20.0 * 60.0);
+    let outputs = try_simulate(&mut params, &mut surface_tissues(temperature), 1.0, 30.0, temperature, 10.0, 20.0 * 60.0).unwrap();
+    assert_eq!(outputs.times, expected.times);
+    assert_eq!(outputs.events, expected.events);
+
+    assert!(matches!(try_simulate(&mut params, &mut surface_tissues(temperature), 1.0, 30.0, temperature, 0.0, 20.0 * 60.0),
+        Err(DecoError::InvalidSampleInterval)));
+    let mut invalid = DiveParameters { ascent_speed: 0.0, ..params };
+    assert!(matches!(try_simulate(&mut invalid, &mut surface_tissues(temperature), 1.0, 30.0, temperature, 10.0, 20.0 * 60.0),
+        Err(DecoError::InvalidParameters(errors)) if errors.len() == 1));
+
+    // An hour at 70 m on air needs a shallow stop longer than the 20 minute per-stop limit
+    let result = try_simulate(&mut params, &mut surface_tissues(temperature), 1.0, 70.0, temperature, 10.0, 60.0 * 60.0);
+    assert!(matches!(result, Err(DecoError::ExcessiveDeco { depth }) if depth > 0.0), "{:?}", result.map(|outputs| outputs.stops()));
+}
+
+#[test]
+fn test_deco_error_display() {
+    use dive_computer_deco::{DecoError, Input};
+
+    assert_eq!(DecoError::InvalidInput { input: Input::Time, value: -1.0 }.to_string(), "invalid time -1");
+    assert_eq!(DecoError::InvalidTissueIndex(16).to_string(), "tissue index 16 is not between 0 and 15");
+    let errors = DiveParameters { sac_rate: 0.0, ..DiveParameters::default() }.validate().unwrap_err();
+    assert_eq!(DecoError::from(errors).to_string(), format!("invalid dive parameters: {}", errors));
+}
+
+#[test]
+fn test_sample_ndl_uses_the_breathing_gas_and_surface() {
+    use dive_computer_deco::ceiling::GfSlope;
+    use dive_computer_deco::ndl::ndl_with_limit;
+    use dive_computer_deco::plan::WaterType;
+    use dive_computer_deco::simulate::{Simulator, NDL_LIMIT_MINUTES};
+
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let ean32 = Gas::new(0.32, 0.0);
+    let mut simulator = Simulator::new(params, surface_tissues(temperature), 0.8, 0.0, 30.0, temperature, 10.0, 30.0 * 60.0, true);
+    simulator.set_environment(0.8, WaterType::Fresh, ean32, None);
+    simulator.step(10.0 * 60.0, &mut ());
+
+    let sample = simulator.sample();
+    let slope = GfSlope::new(params.gf_low, params.gf_high, 0.8);
+    let expected = ndl_with_limit(&slope, &sample.tissues, sample.pressure, temperature, ean32, NDL_LIMIT_MINUTES);
+    assert!(expected.is_some());
+    assert_eq!(sample.ndl, expected);
+    assert_ne!(sample.ndl, ndl_with_limit(&slope, &sample.tissues, sample.pressure, temperature, Gas::air(), NDL_LIMIT_MINUTES));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_repeated_continue_to_keeps_the_last_records() {
+    use dive_computer_deco::simulate::{DivePhase, SimulationEventKind, SimulationRecord, Simulator};
+
+    let temperature = 20.0;
+    let params = DiveParameters::new(0.85, 0.3);
+    let mut simulator = Simulator::new(params, surface_tissues(temperature), 1.0, 0.0, 30.0, temperature, 10.0, 10.0 * 60.0, false);
+    simulator.run_into(&mut ());
+    assert!(simulator.is_finished());
+
+    // reconfigured many times before running, and once after only part of the records were read
+    let ean50 = Gas::new(0.5, 0.0);
+    for i in 0..20 {
+        let gas = if i % 2 == 0 { ean50 } else { Gas::new(0.32, 0.0) };
+        simulator.continue_to(if i % 2 == 0 { 21.0 } else { 40.0 }, 60.0, gas, None, false);
+    }
+    assert!(matches!(simulator.next(), Some(SimulationRecord::Event(_))));
+    simulator.continue_to(21.0, 60.0, ean50, None, false);
+
+    let events: Vec<SimulationEventKind> = simulator
+        .filter_map(|record| match record {
+            SimulationRecord::Event(event) => Some(event.kind),
+            SimulationRecord::Sample(_) => None,
+        })
+        .collect();
+    assert_eq!(events, [
+        SimulationEventKind::PhaseChange(DivePhase::Ascent),
+        SimulationEventKind::PhaseChange(DivePhase::Bottom),
+        SimulationEventKind::GasSwitch { o2: 0.5, he: 0.0 },
+    ]);
+}
@@ -0,0 +1,76 @@
+use dive_computer_deco::ceiling::GfSlope;
+use dive_computer_deco::stop::{time_to_ceiling, try_time_to_ceiling};
+use dive_computer_deco::tissue::{calculate_tissue_with_gas, Tissue};
+use dive_computer_deco::{default_tissue_load, DecoError, Gas, Input};
+
+/// Surface-saturated tissues after `minutes` at `amb_pressure` on `gas`
+fn loaded_tissues(amb_pressure: f32, minutes: f32, gas: Gas) -> [Tissue; 16] {
+    let mut tissues = [Tissue { load_n2: default_tissue_load(20.0), load_he: 0.0 }; 16];
+    for (i, tissue) in tissues.iter_mut().enumerate() {
+        *tissue = calculate_tissue_with_gas(*tissue, i, amb_pressure, 20.0, minutes, gas);
+    }
+    tissues
+}
+
+/// The stop length found by waiting one second at a time
+fn stepped_time_to_ceiling(slope: &GfSlope, tissues: &[Tissue; 16], amb_pressure: f32, gas: Gas, ceiling: f32) -> Option<u32> {
+    let mut tissues = *tissues;
+    for seconds in 0..=10000 {
+        if slope.max_ceiling(&tissues).0 < ceiling {
+            return Some(seconds);
+        }
+        for (i, tissue) in tissues.iter_mut().enumerate() {
+            *tissue = calculate_tissue_with_gas(*tissue, i, amb_pressure, 20.0, 1.0 / 60.0, gas);
+        }
+    }
+    None
+}
+
+#[test]
+fn test_time_to_ceiling_matches_stepping() {
+    let cases = [
+        (loaded_tissues(5.0, 30.0, Gas::air()), Gas::air(), 1.9, 6.0),
+        (loaded_tissues(5.0, 30.0, Gas::air()), Gas::new(0.5, 0.0), 1.9, 6.0),
+        (loaded_tissues(6.0, 25.0, Gas::new(0.21, 0.35)), Gas::new(0.21, 0.35), 2.2, 9.0),
+        (loaded_tissues(6.0, 25.0, Gas::new(0.21, 0.35)), Gas::new(0.5, 0.0), 2.2, 9.0),
+        (loaded_tissues(4.0, 40.0, Gas::air()), Gas::new(1.0, 0.0), 1.3, 1.0),
+    ];
+    for (tissues, gas, stop_pressure, ceiling) in cases {
+        let mut slope = GfSlope::new(0.3, 0.85, 1.0);
+        slope.anchor(&tissues);
+        let expected = stepped_time_to_ceiling(&slope, &tissues, stop_pressure, gas, ceiling);
+        let solved = time_to_ceiling(&slope, &tissues, stop_pressure, 20.0, gas, ceiling, 10000);
+        assert!(expected.is_some_and(|seconds| seconds > 0));
+        // stepping accumulates rounding that the closed form does not
+        assert!(solved.unwrap().abs_diff(expected.unwrap()) <= 1, "{:?} vs {:?}", solved, expected);
+    }
+}
+
+#[test]
+fn test_time_to_ceiling_limits() {
+    let tissues = loaded_tissues(5.0, 30.0, Gas::air());
+    let slope = GfSlope::new(0.3, 0.85, 1.0);
+    assert_eq!(time_to_ceiling(&slope, &tissues, 1.9, 20.0, Gas::air(), 100.0, 600), Some(0));
+    assert_eq!(time_to_ceiling(&slope, &tissues, 1.9, 20.0, Gas::air(), 6.0, 10), None);
+    // held deeper than the ceiling it has to clear, the compartments never get there
+    assert_eq!(time_to_ceiling(&slope, &tissues, 5.0, 20.0, Gas::air(), 3.0, 100000), None);
+}
+
+#[test]
+fn test_try_time_to_ceiling_errors() {
+    let tissues = loaded_tissues(5.0, 30.0, Gas::air());
+    let slope = GfSlope::new(0.3, 0.85, 1.0);
+    let solved = time_to_ceiling(&slope, &tissues, 1.9, 20.0, Gas::air(), 6.0, 10000);
+    assert_eq!(try_time_to_ceiling(&slope, &tissues, 1.9, 20.0, Gas::air(), 6.0, 10000), Ok(solved));
+
+    assert_eq!(try_time_to_ceiling(&GfSlope::new(0.0, 0.85, 1.0), &tissues, 1.9, 20.0, Gas::air(), 6.0, 10000),
+        Err(DecoError::InvalidInput { input: Input::GradientFactor, value: 0.0 }));
+    assert!(matches!(try_time_to_ceiling(&GfSlope::new(0.3, 0.85, -1.0), &tissues, 1.9, 20.0, Gas::air(), 6.0, 10000),
+        Err(DecoError::InvalidInput { input: Input::SurfacePressure, .. })));
+    assert!(matches!(try_time_to_ceiling(&slope, &tissues, 0.0, 20.0, Gas::air(), 6.0, 10000),
+        Err(DecoError::InvalidInput { input: Input::AmbientPressure, .. })));
+    assert!(matches!(try_time_to_ceiling(&slope, &tissues, 1.9, 20.0, Gas::new(0.9, 0.5), 6.0, 10000),
+        Err(DecoError::InvalidInput { input: Input::GasFraction, .. })));
+    assert!(matches!(try_time_to_ceiling(&slope, &tissues, 1.9, 20.0, Gas::air(), f32::NAN, 10000),
+        Err(DecoError::InvalidInput { input: Input::Depth, .. })));
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "alloc")]
+
+use dive_computer_deco::plan::WaterType;
+use dive_computer_deco::table::deco_table;
+use dive_computer_deco::{DiveParameters, Gas};
+
+#[test]
+fn test_table_rows_cross_the_ndl() {
+    let params = DiveParameters::new(0.85, 0.3);
+    let table = deco_table(&params, Gas::air(), 1.0, WaterType::Salt, 20.0, 10.0, &[18.0, 30.0, 45.0], &[20.0 * 60.0, 40.0 * 60.0]).unwrap();
+    assert_eq!(table.rows.len(), 6);
+    assert_eq!((table.rows[1].depth, table.rows[1].bottom_time), (18.0, 40.0 * 60.0));
+
+    let ndls: Vec<f32> = table.rows.iter().step_by(2).map(|row| row.ndl.unwrap()).collect();
+    assert!(ndls[0] > ndls[1] && ndls[1] > ndls[2], "{:?}", ndls);
+
+    for row in table.rows.iter() {
+        let descent = row.depth / params.descent_speed / 60.0;
+        assert_eq!(row.first_stop.is_none(), row.bottom_time / 60.0 <= row.ndl.unwrap() + descent, "{:?}", row);
+        assert_eq!(row.first_stop, row.stops.first().map(|stop| stop.0));
+        let stop_time: f32 = row.stops.iter().map(|stop| stop.1).sum();
+        assert!(row.time_to_surface >= stop_time + row.depth / params.ascent_speed - 1.0, "{:?}", row);
+    }
+    assert!(table.rows[5].first_stop.unwrap() > table.rows[3].first_stop.unwrap());
+}
+
+#[test]
+fn test_table_csv_and_markdown() {
+    let table = deco_table(&DiveParameters::new(0.85, 0.3), Gas::new(0.32, 0.0), 1.0, WaterType::Salt, 20.0, 10.0, &[30.0], &[20.0 * 60.0, 50.0 * 60.0]).unwrap();
+    let csv = table.to_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("depth_m,bottom_time_min,ndl_min,first_stop_m,stop_"));
+    assert!(lines[0].ends_with(",stop_3m_min,time_to_surface_min,stop_time_limit"));
+    assert!(lines[1].starts_with("30,20,"));
+    assert_eq!(lines[1].split(',').count(), lines[0].split(',').count());
+
+    let markdown = table.to_markdown();
+    assert!(markdown.starts_with("O2 32% He 0%, GF 30/85, descent 20 m/min, ascent 10 m/min"));
+    assert!(markdown.contains(&format!("### 30 m, NDL {:.0} min", table.rows[0].ndl.unwrap())));
+    assert!(markdown.contains("| 20 | no stop |"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_table_json() {
+    let table = deco_table(&DiveParameters::new(0.85, 0.3), Gas::air(), 1.0, WaterType::Fresh, 20.0, 10.0, &[40.0], &[25.0 * 60.0]).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&table.to_json().unwrap()).unwrap();
+    assert_eq!(value["water"], "Fresh");
+    assert_eq!(value["rows"][0]["stops"].as_array().unwrap().len(), table.rows[0].stops.len());
+}
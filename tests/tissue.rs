@@ -115,4 +115,80 @@ fn test_calculate_multi_tissues() {
             println!("{:?}", result);
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_try_calculate_tissue_rejects_invalid_input() {
+    use dive_computer_deco::tissue::try_calculate_tissue;
+    use dive_computer_deco::{DecoError, Input};
+
+    let tissue = Tissue { load_n2: 0.79, load_he: 0.0 };
+    let expected = calculate_tissue(tissue, 3, 4.0, 20.0, 2.0);
+    let result = try_calculate_tissue(tissue, 3, 4.0, 20.0, 2.0).unwrap();
+    assert_eq!((result.load_n2, result.load_he), (expected.load_n2, expected.load_he));
+
+    assert!(matches!(try_calculate_tissue(tissue, 16, 4.0, 20.0, 2.0), Err(DecoError::InvalidTissueIndex(16))));
+    assert!(matches!(try_calculate_tissue(tissue, 3, 4.0, 20.0, -1.0), Err(DecoError::InvalidInput { input: Input::Time, value: -1.0 })));
+    assert!(matches!(try_calculate_tissue(tissue, 3, f32::NAN, 20.0, 2.0),
+        Err(DecoError::InvalidInput { input: Input::AmbientPressure, .. })));
+    let infinite_load = Tissue { load_n2: f32::INFINITY, load_he: 0.0 };
+    assert!(matches!(try_calculate_tissue(infinite_load, 3, 4.0, 20.0, 2.0),
+        Err(DecoError::InvalidInput { input: Input::TissueLoad, .. })));
+}
+
+#[test]
+fn test_tissue_updater_matches_calculate_tissue() {
+    use dive_computer_deco::tissue::{calculate_tissue_with_gas, TissueUpdater};
+    use dive_computer_deco::Gas;
+
+    let gas = Gas::new(0.21, 0.35);
+    let updater = TissueUpdater::new(1.0 / 60.0);
+    let mut expected = [Tissue { load_n2: 0.75, load_he: 0.0 }; 16];
+    let mut tissues = expected;
+    for _ in 0..600 {
+        for (i, tissue) in expected.iter_mut().enumerate() {
+            *tissue = calculate_tissue_with_gas(*tissue, i, 5.0, 20.0, 1.0 / 60.0, gas);
+        }
+        updater.update_all(&mut tissues, 5.0, 20.0, gas);
+    }
+
+    for i in 0..16 {
+        assert_eq!(tissues[i].load_n2, expected[i].load_n2);
+        assert_eq!(tissues[i].load_he, expected[i].load_he);
+        let single = updater.update(expected[i], i, 1.0, 20.0, gas);
+        assert_eq!(single.load_n2, calculate_tissue_with_gas(expected[i], i, 1.0, 20.0, 1.0 / 60.0, gas).load_n2);
+    }
+}
+
+#[test]
+fn test_try_tissue_updater_rejects_bad_steps() {
+    use dive_computer_deco::tissue::TissueUpdater;
+    use dive_computer_deco::zh16c::ZhL16cGf;
+    use dive_computer_deco::{DecoError, Input};
+
+    assert_eq!(TissueUpdater::try_new(0.5), Ok(TissueUpdater::new(0.5)));
+    assert_eq!(TissueUpdater::try_new(-1.0).err(), Some(DecoError::InvalidInput { input: Input::Time, value: -1.0 }));
+    assert!(matches!(TissueUpdater::try_new(f32::NAN), Err(DecoError::InvalidInput { input: Input::Time, .. })));
+
+    let mut he_half_life = ZhL16cGf::HE_HALF_LIFE;
+    he_half_life[3] = 0.0;
+    assert_eq!(TissueUpdater::try_with_half_lives(&ZhL16cGf::N2_HALF_LIFE, &he_half_life, 1.0).err(),
+        Some(DecoError::InvalidInput { input: Input::Time, value: 0.0 }));
+}
+
+#[test]
+fn test_helium_loads_towards_the_inspired_pressure() {
+    use dive_computer_deco::tissue::calculate_tissue_with_gas;
+    use dive_computer_deco::Gas;
+
+    // two helium half lives of compartment 1 (2 * 1.51 min) close 3/4 of the gap: on trimix 21/35
+    // at 4 bar from 0.2 bar the load is 0.2 + (pphe - 0.2) * 3/4
+    let trimix = Gas::new(0.21, 0.35);
+    let pphe = (4.0 - water_vapor_pressure(20.0)) * 0.35;
+    let loaded = calculate_tissue_with_gas(Tissue { load_n2: 0.75, load_he: 0.2 }, 0, 4.0, 20.0, 2.0 * 1.51, trimix);
+    assert!((loaded.load_he - (0.2 + (pphe - 0.2) * 0.75)).abs() < 1e-5, "{}", loaded.load_he);
+
+    // and off-gasses on air towards 0: 1 bar of helium keeps 1/4
+    let unloaded = calculate_tissue_with_gas(Tissue { load_n2: 0.75, load_he: 1.0 }, 0, 1.0, 20.0, 2.0 * 1.51, Gas::air());
+    assert!((unloaded.load_he - 0.25).abs() < 1e-5, "{}", unloaded.load_he);
+}
@@ -0,0 +1,96 @@
+use dive_computer_deco::ceiling::{ceiling_with_gf_exact, max_ceiling_with_gf_exact};
+use dive_computer_deco::tissue::{calculate_tissue_with_gas, Tissue, TissueUpdater};
+use dive_computer_deco::tissue_state::TissueState;
+use dive_computer_deco::{default_tissue_load, DecoError, Gas, Input};
+
+/// Surface-saturated tissues after 25 minutes at 45 m on trimix 21/35
+fn loaded_tissues() -> [Tissue; 16] {
+    let mut tissues = [Tissue { load_n2: default_tissue_load(20.0), load_he: 0.0 }; 16];
+    for (i, tissue) in tissues.iter_mut().enumerate() {
+        *tissue = calculate_tissue_with_gas(*tissue, i, 5.5, 20.0, 25.0, Gas::new(0.21, 0.35));
+    }
+    tissues
+}
+
+#[test]
+fn test_update_matches_tissue_array() {
+    let gas = Gas::new(0.21, 0.35);
+    let updater = TissueUpdater::new(1.0 / 60.0);
+    let mut tissues = loaded_tissues();
+    let mut state = TissueState::from(&tissues);
+    for _ in 0..300 {
+        updater.update_all(&mut tissues, 2.8, 20.0, gas);
+        state.update(&updater, 2.8, 20.0, gas);
+    }
+
+    let back: [Tissue; 16] = (&state).into();
+    for i in 0..16 {
+        assert_eq!(back[i].load_n2, tissues[i].load_n2);
+        assert_eq!(back[i].load_he, tissues[i].load_he);
+    }
+}
+
+#[test]
+fn test_ceilings_match_per_compartment_ceiling() {
+    let tissues = loaded_tissues();
+    let state = TissueState::from(&tissues);
+    let gf = 0.7;
+
+    let ceilings = state.ceilings(gf);
+    for (i, tissue) in tissues.iter().enumerate() {
+        let (expected, _) = ceiling_with_gf_exact(gf, gf, tissue, i, 1.0);
+        assert!(((ceilings[i] - 1.0) * 10.0 - expected).abs() < 1e-4, "compartment {}: {} vs {}", i, ceilings[i], expected);
+    }
+
+    let (max_ceiling, compartment) = state.max_ceiling(gf);
+    let (expected, expected_compartment, _) = max_ceiling_with_gf_exact(gf, gf, &tissues, 1.0);
+    assert_eq!(compartment, expected_compartment);
+    assert!(((max_ceiling - 1.0) * 10.0 - expected).abs() < 1e-4);
+}
+
+#[test]
+fn test_m_values() {
+    let state = TissueState::uniform(0.79, 0.0);
+    let m_values = state.m_values(1.0);
+    for (i, m_value) in m_values.iter().enumerate() {
+        assert!((m_value - dive_computer_deco::m_value::calculate_m_values(1.0, i)).abs() < 1e-6);
+    }
+
+    // At a ceiling with GF 1 each compartment sits exactly on its M-value
+    let state = TissueState::from(&loaded_tissues());
+    let ceilings = state.ceilings(1.0);
+    let (compartment_ceiling, compartment) = state.max_ceiling(1.0);
+    let m_value = state.m_values(compartment_ceiling)[compartment];
+    assert!((m_value - state.load_n2[compartment] - state.load_he[compartment]).abs() < 1e-4);
+    assert_eq!(ceilings[compartment], compartment_ceiling);
+}
+
+#[test]
+fn test_try_variants_report_bad_input() {
+    let mut state = TissueState::from(&loaded_tissues());
+    assert_eq!(state.try_ceilings(0.7), Ok(state.ceilings(0.7)));
+    assert_eq!(state.try_max_ceiling(0.7), Ok(state.max_ceiling(0.7)));
+    assert_eq!(state.try_max_ceiling(1.1), Err(DecoError::InvalidInput { input: Input::GradientFactor, value: 1.1 }));
+
+    let updater = TissueUpdater::new(1.0);
+    assert!(matches!(state.try_update(&updater, -2.0, 20.0, Gas::air()),
+        Err(DecoError::InvalidInput { input: Input::AmbientPressure, .. })));
+    assert_eq!(state.try_update(&updater, 2.0, 20.0, Gas::air()), Ok(()));
+
+    state.load_he[7] = f32::INFINITY;
+    assert!(matches!(state.try_ceilings(0.7), Err(DecoError::InvalidInput { input: Input::TissueLoad, .. })));
+}
+
+#[test]
+fn test_empty_compartments_have_no_nan() {
+    use dive_computer_deco::m_value::calculate_m_values;
+
+    let mut tissues = loaded_tissues();
+    tissues[2] = Tissue { load_n2: 0.0, load_he: 0.0 };
+    let state = TissueState::from(&tissues);
+    let m_values = state.m_values(1.5);
+    assert!(m_values.iter().all(|m_value| m_value.is_finite()));
+    assert!((m_values[2] - calculate_m_values(1.5, 2)).abs() < 1e-6);
+    assert_eq!(state.ceilings(0.7)[2], 0.0);
+    assert!(state.max_ceiling(0.7).0.is_finite());
+}